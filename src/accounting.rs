@@ -0,0 +1,445 @@
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::database::Database;
+use crate::invoices::{tax_breakdown, SHIPPING_TAX_GROUP};
+use crate::order_filter::OrderFilter;
+use crate::orders::get_order_details;
+use crate::types::order::OrderDetails;
+
+/// Payment statuses of orders which have been paid, and therefore result in journal entries
+const BOOKED_PAYMENT_STATUSES: &[&str] = &["Paid", "PartiallyRefunded", "FullyRefunded"];
+
+/// What a ledger account is used for in the journal entries
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccountType {
+    /// Received payments, by payment method
+    Payment,
+
+    /// Revenue, by tax group
+    Sales,
+
+    /// Tax payable, by tax group
+    Tax,
+
+    /// Shipping revenue
+    Shipping,
+
+    /// Discounts given
+    Discount,
+
+    /// Revenue reversed by refunds, by tax group
+    Refund
+}
+
+impl AccountType {
+    fn from_str(value: &str) -> Option<AccountType> {
+        match value {
+            "PAYMENT" => Some(AccountType::Payment),
+            "SALES" => Some(AccountType::Sales),
+            "TAX" => Some(AccountType::Tax),
+            "SHIPPING" => Some(AccountType::Shipping),
+            "DISCOUNT" => Some(AccountType::Discount),
+            "REFUND" => Some(AccountType::Refund),
+            _ => None
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::Payment => "PAYMENT",
+            AccountType::Sales => "SALES",
+            AccountType::Tax => "TAX",
+            AccountType::Shipping => "SHIPPING",
+            AccountType::Discount => "DISCOUNT",
+            AccountType::Refund => "REFUND"
+        }
+    }
+}
+
+/// Maps postings to a ledger account of the instance's bookkeeping.
+/// The most specific mapping wins: one matching key and currency, then key, then currency, then neither
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountMapping {
+    pub account_type:   AccountType,
+
+    /// Payment method for PAYMENT, tax group ID for SALES, TAX and REFUND. Empty to match any
+    pub key:            String,
+
+    /// Currency code. Empty to match any
+    pub currency:       String,
+
+    /// Ledger account number, e.g. '8000'
+    pub account:        String
+}
+
+/// A single posting of a journal entry
+#[derive(Serialize)]
+pub struct JournalLine {
+    pub account:        String,
+    pub account_type:   AccountType,
+    pub description:    String,
+    pub debit:          Decimal,
+    pub credit:         Decimal
+}
+
+/// A balanced journal entry for a sale or refund
+#[derive(Serialize)]
+pub struct JournalEntry {
+    /// Epoch seconds
+    pub date:           i64,
+    pub order_number:   i64,
+    pub description:    String,
+    pub currency:       String,
+    pub lines:          Vec<JournalLine>
+}
+
+/**
+Get the ledger account mapping of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the mapping for
+
+## Returns
+    **Ok**: The mapping
+    **Err**: A summary of what went wrong
+*/
+pub fn get_account_mappings(database: Database, instance_id: String) -> Result<Vec<AccountMapping>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT account_type, mapping_key, currency, account FROM ledger_accounts WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut mappings = Vec::new();
+    for row in result.unwrap() {
+        let account_type: String = row.get("account_type").unwrap();
+        let account_type = match AccountType::from_str(&account_type) {
+            Some(account_type) => account_type,
+            None => continue
+        };
+
+        mappings.push(AccountMapping {
+            account_type,
+            key:        row.get("mapping_key").unwrap(),
+            currency:   row.get("currency").unwrap(),
+            account:    row.get("account").unwrap()
+        });
+    }
+
+    Ok(mappings)
+}
+
+/**
+Replace the ledger account mapping of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to set the mapping for
+    **mappings** The new mapping
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn set_account_mappings(database: Database, instance_id: String, mappings: Vec<AccountMapping>) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let result = tx.exec_drop("DELETE FROM ledger_accounts WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    for mapping in mappings {
+        let result = tx.exec_drop("INSERT INTO ledger_accounts (instance_id, account_type, mapping_key, currency, account) VALUES (:instance_id, :account_type, :mapping_key, :currency, :account)", params! {
+            "instance_id" => instance_id.clone(),
+            "account_type" => mapping.account_type.as_str(),
+            "mapping_key" => mapping.key,
+            "currency" => mapping.currency,
+            "account" => mapping.account
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Build the journal entries for the sales and refunds of a period.
+Every paid order placed in the period results in an entry debiting the payment and crediting revenue, shipping and tax,
+every refund made in the period in an entry reversing revenue and tax in proportion to the order's tax breakdown.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The period and orders to book. 'from' and 'to' also select the refunds
+
+## Returns
+    **Ok**: The entries, by date
+    **Err**: A summary of what went wrong. This includes postings for which no ledger account is mapped
+*/
+pub fn build_journal(database: Database, filter: &OrderFilter) -> Result<Vec<JournalEntry>, String> {
+    let mappings = get_account_mappings(database.clone(), filter.instance_id.clone())?;
+    let mut conn = database.pool.get_conn().unwrap();
+
+    let (condition, mut params) = filter.to_sql();
+    let statuses: Vec<String> = BOOKED_PAYMENT_STATUSES.iter().enumerate().map(|(i, status)| {
+        params.push((format!("status_{}", i), mysql::Value::from(*status)));
+        format!(":status_{}", i)
+    }).collect();
+
+    let result = conn.exec::<Row, String, Params>(format!("SELECT o.order_id FROM orders o WHERE {} AND o.payment_status IN ({}) ORDER BY o.order_date ASC",
+        condition, statuses.join(", ")), Params::from(params));
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut entries = Vec::new();
+    for row in result.unwrap() {
+        let order_id: String = row.get("order_id").unwrap();
+        if let Some(details) = get_order_details(database.clone(), &order_id)? {
+            entries.push(sale_entry(&details, &mappings)?);
+        }
+    }
+
+    //Refunds are booked in the period they were made, which need not be the period of the order
+    let (condition, mut params) = OrderFilter { from: None, to: None, ..filter.clone() }.to_sql();
+    let mut refund_conditions = vec![condition];
+    if let Some(from) = filter.from {
+        refund_conditions.push("r.refund_date >= :refund_from".to_string());
+        params.push(("refund_from".to_string(), mysql::Value::from(from)));
+    }
+
+    if let Some(to) = filter.to {
+        refund_conditions.push("r.refund_date < :refund_to".to_string());
+        params.push(("refund_to".to_string(), mysql::Value::from(to)));
+    }
+
+    let result = conn.exec::<Row, String, Params>(format!("SELECT r.refund_id, r.refund_date, r.amount, o.order_id FROM order_refunds r INNER JOIN orders o ON o.order_id = r.order_id \
+        WHERE {} ORDER BY r.refund_date ASC", refund_conditions.join(" AND ")), Params::from(params));
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    for row in result.unwrap() {
+        let order_id: String = row.get("order_id").unwrap();
+        if let Some(details) = get_order_details(database.clone(), &order_id)? {
+            entries.push(refund_entry(&details, row.get("refund_date").unwrap(), row.get("amount").unwrap(), &mappings)?);
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.date);
+    Ok(entries)
+}
+
+fn sale_entry(details: &OrderDetails, mappings: &[AccountMapping]) -> Result<JournalEntry, String> {
+    let order = &details.order;
+    let mut lines = Vec::new();
+
+    for tax_line in tax_breakdown(details) {
+        if tax_line.tax_group_id == SHIPPING_TAX_GROUP {
+            lines.push(posting(mappings, AccountType::Shipping, "", &order.currency, "Shipping", -tax_line.net)?);
+        } else {
+            //Revenue is booked before discounts, the discounts are booked separately
+            let discount = order.money(details.items.iter()
+                .filter(|item| item.tax_group_id == tax_line.tax_group_id)
+                .map(|item| item.discount)
+                .sum()).round().amount;
+
+            lines.push(posting(mappings, AccountType::Sales, &tax_line.tax_group_id, &order.currency, &format!("Sales {}%", tax_line.rate), -(tax_line.net + discount))?);
+            if !discount.is_zero() {
+                lines.push(posting(mappings, AccountType::Discount, "", &order.currency, &format!("Discount {}%", tax_line.rate), discount)?);
+            }
+        }
+
+        if !tax_line.tax.is_zero() {
+            lines.push(posting(mappings, AccountType::Tax, &tax_line.tax_group_id, &order.currency, &format!("Tax {}%", tax_line.rate), -tax_line.tax)?);
+        }
+    }
+
+    //The payment balances the entry
+    let balance = lines.iter().map(|line| line.credit - line.debit).sum();
+    lines.insert(0, posting(mappings, AccountType::Payment, &order.payment_method, &order.currency, &format!("Payment {}", order.payment_method), balance)?);
+
+    Ok(JournalEntry {
+        date:           order.order_date,
+        order_number:   order.wix_order_id,
+        description:    format!("Order #{}", order.wix_order_id),
+        currency:       order.currency.clone(),
+        lines
+    })
+}
+
+fn refund_entry(details: &OrderDetails, refund_date: i64, amount: Decimal, mappings: &[AccountMapping]) -> Result<JournalEntry, String> {
+    let order = &details.order;
+    let factor = amount.checked_div(order.total_price).unwrap_or_default();
+    let mut lines = Vec::new();
+
+    for tax_line in tax_breakdown(details) {
+        lines.push(posting(mappings, AccountType::Refund, &tax_line.tax_group_id, &order.currency, &format!("Refund {}%", tax_line.rate), order.money(tax_line.net * factor).round().amount)?);
+
+        if !tax_line.tax.is_zero() {
+            lines.push(posting(mappings, AccountType::Tax, &tax_line.tax_group_id, &order.currency, &format!("Tax {}%", tax_line.rate), order.money(tax_line.tax * factor).round().amount)?);
+        }
+    }
+
+    //Put any rounding difference on the first revenue line, so the entry matches the refunded amount
+    let difference = amount - lines.iter().map(|line| line.debit - line.credit).sum::<Decimal>();
+    if !difference.is_zero() {
+        if let Some(line) = lines.iter_mut().find(|line| line.account_type == AccountType::Refund) {
+            line.debit += difference;
+        }
+    }
+
+    lines.insert(0, posting(mappings, AccountType::Payment, &order.payment_method, &order.currency, &format!("Refund {}", order.payment_method), -amount)?);
+
+    Ok(JournalEntry {
+        date:           refund_date,
+        order_number:   order.wix_order_id,
+        description:    format!("Refund order #{}", order.wix_order_id),
+        currency:       order.currency.clone(),
+        lines
+    })
+}
+
+/// Create a posting on the mapped account. Positive amounts are debited, negative amounts credited
+fn posting(mappings: &[AccountMapping], account_type: AccountType, key: &str, currency: &str, description: &str, amount: Decimal) -> Result<JournalLine, String> {
+    let account = match find_account(mappings, account_type, key, currency) {
+        Some(account) => account,
+        None => return Err(format!("No ledger account is mapped for {} '{}' in {}", account_type.as_str(), key, currency))
+    };
+
+    Ok(JournalLine {
+        account,
+        account_type,
+        description:    description.to_string(),
+        debit:          if amount.is_sign_positive() { amount } else { Decimal::ZERO },
+        credit:         if amount.is_sign_negative() { -amount } else { Decimal::ZERO }
+    })
+}
+
+fn find_account(mappings: &[AccountMapping], account_type: AccountType, key: &str, currency: &str) -> Option<String> {
+    let candidates = [(key, currency), (key, ""), ("", currency), ("", "")];
+
+    candidates.iter()
+        .filter_map(|(key, currency)| mappings.iter().find(|m| m.account_type == account_type && m.key == *key && m.currency == *currency))
+        .map(|m| m.account.clone())
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::types::order::{Order, OrderItem};
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn mappings() -> Vec<AccountMapping> {
+        [(AccountType::Payment, "1100"), (AccountType::Sales, "8000"), (AccountType::Tax, "1500"), (AccountType::Shipping, "8100"), (AccountType::Discount, "8200")]
+            .iter()
+            .map(|(account_type, account)| AccountMapping { account_type: *account_type, key: String::new(), currency: String::new(), account: account.to_string() })
+            .collect()
+    }
+
+    /// Two mugs of 50.00 with 10.00 discount and 21% tax over the discounted price, shipped for 5.00 plus tax
+    fn discounted_order() -> OrderDetails {
+        OrderDetails {
+            order: Order {
+                order_id:           "order".to_string(),
+                instance_id:        "instance".to_string(),
+                wix_order_id:       10042,
+                order_date:         1614556800,
+                currency:           "EUR".to_string(),
+                payment_status:     "Paid".to_string(),
+                payment_method:     "CreditCard".to_string(),
+                channel:            "Web".to_string(),
+                fulfillment_status: "NotFulfilled".to_string(),
+                delivery_method:    "SHIP".to_string(),
+                requires_shipping:  true,
+                total_price:        decimal("114.95"),
+                subtotal:           decimal("100.00"),
+                tax:                decimal("19.95"),
+                shipping:           decimal("5.00"),
+                discount:           decimal("10.00"),
+                quantity:           2,
+                weight:             0.0,
+                weight_unit:        "KG".to_string(),
+                weight_kg:          None
+            },
+            buyer_name:         "Jan de Vries".to_string(),
+            buyer_email:        "jan@example.com".to_string(),
+            buyer_phone:        String::new(),
+            buyer_note:         String::new(),
+            billing_address:    None,
+            shipping_address:   None,
+            items:              vec![OrderItem {
+                order_item_id:          "mug".to_string(),
+                product_id:             None,
+                variant_id:             None,
+                item_type:              "PHYSICAL".to_string(),
+                name:                   "Mug".to_string(),
+                sku:                    "SKU-MUG".to_string(),
+                quantity:               2,
+                weight:                 0.0,
+                weight_kg:              None,
+                price:                  decimal("50.00"),
+                total:                  decimal("90.00"),
+                tax:                    decimal("18.90"),
+                tax_group_id:           "standard".to_string(),
+                tax_included_in_price:  false,
+                discount:               decimal("10.00"),
+                options:                Vec::new()
+            }]
+        }
+    }
+
+    fn amount(entry: &JournalEntry, account_type: AccountType) -> Decimal {
+        entry.lines.iter().filter(|line| line.account_type == account_type).map(|line| line.debit - line.credit).sum()
+    }
+
+    #[test]
+    fn discounts_are_net_on_the_invoice_and_separate_in_the_journal() {
+        let details = discounted_order();
+
+        //The invoice charges tax over the discounted price, and adds up to what the buyer paid
+        let tax_lines = tax_breakdown(&details);
+        let item_line = tax_lines.iter().find(|line| line.tax_group_id == "standard").unwrap();
+        assert_eq!((item_line.rate, item_line.net, item_line.tax), (decimal("21"), decimal("90.00"), decimal("18.90")));
+        assert_eq!(tax_lines.iter().map(|line| line.net + line.tax).sum::<Decimal>(), details.order.total_price);
+
+        //The journal books the revenue before the discount and the discount itself, which net to the invoiced amount
+        let entry = sale_entry(&details, &mappings()).unwrap();
+        assert_eq!(amount(&entry, AccountType::Sales), decimal("-100.00"));
+        assert_eq!(amount(&entry, AccountType::Discount), decimal("10.00"));
+        assert_eq!(amount(&entry, AccountType::Sales) + amount(&entry, AccountType::Discount), -item_line.net);
+        assert_eq!(amount(&entry, AccountType::Shipping), decimal("-5.00"));
+        assert_eq!(amount(&entry, AccountType::Tax), decimal("-19.95"));
+        assert_eq!(amount(&entry, AccountType::Payment), details.order.total_price);
+        assert_eq!(entry.lines.iter().map(|line| line.debit - line.credit).sum::<Decimal>(), Decimal::ZERO);
+    }
+}
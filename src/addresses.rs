@@ -0,0 +1,410 @@
+use serde::Serialize;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+
+/// An address as it was received from Wix, before normalization
+pub struct RawAddress<'a> {
+    /// Street name, when Wix sent the address line as a structured street
+    pub street_name:    Option<&'a str>,
+    pub street_number:  Option<&'a str>,
+
+    /// Free text address line, when Wix sent no structured street
+    pub address_line_1: Option<&'a str>,
+    pub address_line_2: &'a str,
+    pub zip_code:       &'a str,
+    pub city:           &'a str,
+    pub country:        &'a str
+}
+
+/// An address with its street, house number and addition kept separately
+#[derive(Serialize, Default)]
+pub struct NormalizedAddress {
+    pub street:                 String,
+    pub house_number:           String,
+
+    /// House number addition, e.g. 'A' in '12A' or 'bis' in '12 bis'
+    pub house_number_addition:  String,
+
+    /// The street line as it is printed in the country, e.g. 'Hoofdstraat 12A' or '12 Main Street'
+    pub address_line_1:         String,
+    pub address_line_2:         String,
+    pub zip_code:               String,
+    pub city:                   String,
+
+    /// ISO 3166-1 alpha-2 country code
+    pub country:                String,
+
+    /// Why the address failed validation. Empty if it is valid
+    pub issues:                 Vec<String>
+}
+
+/// A stored address which failed validation on import
+#[derive(Serialize)]
+pub struct FlaggedAddress {
+    pub address_id:         String,
+    pub order_id:           String,
+    pub wix_order_id:       i64,
+
+    /// 'BILLING', 'SHIPMENT' or 'PICKUP'
+    pub address_type:       String,
+    pub full_name:          String,
+    pub company:            String,
+    pub address_line_1:     String,
+    pub address_line_2:     String,
+    pub zip_code:           String,
+    pub city:               String,
+    pub country:            String,
+    pub validation_issues:  String
+}
+
+/**
+Normalize an address. Never fails: problems are listed in the issues of the result, so the address can be reviewed
+
+## Params
+    **raw** The address as received from Wix
+
+## Returns
+    The normalized address
+*/
+pub fn normalize(raw: &RawAddress) -> NormalizedAddress {
+    let mut address = NormalizedAddress {
+        address_line_2: raw.address_line_2.trim().to_string(),
+        city:           raw.city.trim().to_string(),
+        ..NormalizedAddress::default()
+    };
+
+    match normalize_country(raw.country) {
+        Some(country) => address.country = country,
+        None => {
+            address.country = raw.country.trim().to_string();
+            address.issues.push(format!("Unknown country '{}'", raw.country));
+        }
+    }
+
+    let (street, number) = match (raw.street_name, raw.street_number, raw.address_line_1) {
+        (Some(name), number, _) => (name.trim().to_string(), number.unwrap_or_default().trim().to_string()),
+        (None, _, Some(line)) => split_street(line),
+        (None, _, None) => (String::new(), String::new())
+    };
+
+    let (house_number, addition) = split_house_number(&number);
+    address.street = street;
+    address.house_number = house_number;
+    address.house_number_addition = addition;
+    address.address_line_1 = format_street_line(&address.street, &address.house_number, &address.house_number_addition, &address.country);
+
+    if address.street.is_empty() {
+        address.issues.push("The address has no street".to_string());
+    }
+
+    if address.house_number.is_empty() && requires_house_number(&address.country) {
+        address.issues.push("The address has no house number".to_string());
+    }
+
+    if address.city.is_empty() {
+        address.issues.push("The address has no city".to_string());
+    }
+
+    match normalize_zip_code(raw.zip_code, &address.country) {
+        Ok(zip_code) => address.zip_code = zip_code,
+        Err(e) => {
+            address.zip_code = raw.zip_code.trim().to_string();
+            address.issues.push(e);
+        }
+    }
+
+    address
+}
+
+/**
+Get the addresses of an instance's orders which failed validation on import
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the addresses for
+
+## Returns
+    **Ok**: The flagged addresses, newest order first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_flagged_addresses(database: Database, instance_id: String) -> Result<Vec<FlaggedAddress>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT a.address_id, o.order_id, o.wix_order_id, a.address_type, a.full_name, a.company, a.address_line_1, a.address_line_2, a.zip_code, a.city, a.country, a.validation_issues \
+        FROM addresses a INNER JOIN orders o ON a.address_id = o.billing_address_id OR a.address_id = o.shipping_address_id \
+        WHERE o.instance_id = :instance_id AND a.needs_review = TRUE ORDER BY o.order_date DESC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| FlaggedAddress {
+            address_id:         row.get("address_id").unwrap(),
+            order_id:           row.get("order_id").unwrap(),
+            wix_order_id:       row.get("wix_order_id").unwrap(),
+            address_type:       row.get("address_type").unwrap(),
+            full_name:          row.get("full_name").unwrap(),
+            company:            row.get("company").unwrap(),
+            address_line_1:     row.get("address_line_1").unwrap(),
+            address_line_2:     row.get("address_line_2").unwrap(),
+            zip_code:           row.get("zip_code").unwrap(),
+            city:               row.get("city").unwrap(),
+            country:            row.get("country").unwrap(),
+            validation_issues:  row.get("validation_issues").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Get the ISO 3166-1 alpha-2 code of a country, given as a code or a common name
+fn normalize_country(country: &str) -> Option<String> {
+    let country = country.trim().to_uppercase();
+    let code = match country.as_str() {
+        "NLD" | "NETHERLANDS" | "THE NETHERLANDS" | "NEDERLAND" | "HOLLAND" => "NL",
+        "BEL" | "BELGIUM" | "BELGIË" | "BELGIE" | "BELGIQUE" | "BELGIEN" => "BE",
+        "DEU" | "GERMANY" | "DEUTSCHLAND" | "DUITSLAND" => "DE",
+        "GBR" | "UK" | "UNITED KINGDOM" | "GREAT BRITAIN" => "GB",
+        "USA" | "UNITED STATES" | "UNITED STATES OF AMERICA" => "US",
+        code if code.len() == 2 && code.chars().all(|c| c.is_ascii_uppercase()) => code,
+        _ => return None
+    };
+
+    Some(code.to_string())
+}
+
+/// Format a postal code the way the country's postal service writes it
+fn normalize_zip_code(zip_code: &str, country: &str) -> Result<String, String> {
+    let compact: String = zip_code.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+    let digits = |value: &str, count: usize| value.len() == count && value.chars().all(|c| c.is_ascii_digit());
+    let invalid = || Err(format!("Invalid postal code '{}' for {}", zip_code.trim(), country));
+
+    match country {
+        //1234 AB, the first digit is never 0
+        "NL" => {
+            if compact.len() == 6 && compact.is_ascii() && digits(&compact[..4], 4) && !compact.starts_with('0') && compact[4..].chars().all(|c| c.is_ascii_uppercase()) {
+                Ok(format!("{} {}", &compact[..4], &compact[4..]))
+            } else {
+                invalid()
+            }
+        },
+        "BE" if digits(&compact, 4) && !compact.starts_with('0') => Ok(compact),
+        "DE" if digits(&compact, 5) => Ok(compact),
+        //Outward code of 2 to 4 characters, inward code of a digit and two letters: SW1A 1AA
+        "GB" => {
+            let valid = compact.len() >= 5 && compact.len() <= 7 && compact.is_ascii()
+                && compact.starts_with(|c: char| c.is_ascii_uppercase())
+                && compact.chars().all(|c| c.is_ascii_alphanumeric())
+                && compact[compact.len() - 3..].starts_with(|c: char| c.is_ascii_digit())
+                && compact[compact.len() - 2..].chars().all(|c| c.is_ascii_uppercase());
+
+            if valid {
+                Ok(format!("{} {}", &compact[..compact.len() - 3], &compact[compact.len() - 3..]))
+            } else {
+                invalid()
+            }
+        },
+        //12345 or ZIP+4: 12345-6789
+        "US" => {
+            let compact = compact.replace('-', "");
+            if digits(&compact, 5) {
+                Ok(compact)
+            } else if digits(&compact, 9) {
+                Ok(format!("{}-{}", &compact[..5], &compact[5..]))
+            } else {
+                invalid()
+            }
+        },
+        "BE" | "DE" => invalid(),
+        //Other countries are not validated yet
+        _ => Ok(zip_code.trim().to_string())
+    }
+}
+
+/// Split a free text street line into the street and the house number with its addition,
+/// e.g. 'Hoofdstraat 12A' or '12 Main Street'
+fn split_street(line: &str) -> (String, String) {
+    let line = line.trim();
+    let starts_with_digit = |part: &str| part.starts_with(|c: char| c.is_ascii_digit());
+
+    //House number after the street, possibly followed by a separate addition: 'Hoofdstraat 12 bis'
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if let Some(position) = parts.iter().rposition(|part| starts_with_digit(part)) {
+        if position > 0 && parts.len() - position <= 2 {
+            return (parts[..position].join(" "), parts[position..].join(" "));
+        }
+
+        //House number before the street: '12 Main Street'
+        if position == 0 && parts.len() > 1 {
+            return (parts[1..].join(" "), parts[0].to_string());
+        }
+    }
+
+    (line.to_string(), String::new())
+}
+
+/// Split a house number from its addition, e.g. '12A', '12-1' or '12 bis'
+fn split_house_number(number: &str) -> (String, String) {
+    let number = number.trim();
+    let end = number.find(|c: char| !c.is_ascii_digit()).unwrap_or(number.len());
+    let addition = number[end..].trim_start_matches(['-', ' ', '/']).trim();
+    (number[..end].to_string(), addition.to_string())
+}
+
+/// In these countries the house number precedes the street
+fn number_first(country: &str) -> bool {
+    matches!(country, "GB" | "US" | "IE" | "CA" | "AU" | "NZ" | "FR")
+}
+
+/// Postal services in these countries need a house number to deliver
+fn requires_house_number(country: &str) -> bool {
+    matches!(country, "NL" | "BE" | "DE")
+}
+
+fn format_street_line(street: &str, house_number: &str, addition: &str, country: &str) -> String {
+    let number = match (house_number.is_empty(), addition.is_empty()) {
+        (true, _) => return street.to_string(),
+        (false, true) => house_number.to_string(),
+        //NL writes 12A and 12-1, other countries separate the addition with a space
+        (false, false) if country == "NL" && addition.len() == 1 => format!("{}{}", house_number, addition),
+        (false, false) if country == "NL" => format!("{}-{}", house_number, addition),
+        (false, false) => format!("{} {}", house_number, addition)
+    };
+
+    if number_first(country) {
+        format!("{} {}", number, street)
+    } else {
+        format!("{} {}", street, number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An address as Wix sends it with a free text address line
+    fn line_address<'a>(line: &'a str, zip_code: &'a str, city: &'a str, country: &'a str) -> RawAddress<'a> {
+        RawAddress { street_name: None, street_number: None, address_line_1: Some(line), address_line_2: "", zip_code, city, country }
+    }
+
+    /// An address as Wix sends it with a structured street
+    fn street_address<'a>(name: &'a str, number: &'a str, zip_code: &'a str, city: &'a str, country: &'a str) -> RawAddress<'a> {
+        RawAddress { street_name: Some(name), street_number: Some(number), address_line_1: None, address_line_2: "", zip_code, city, country }
+    }
+
+    #[test]
+    fn dutch_postal_codes_get_a_space() {
+        assert_eq!(normalize_zip_code("1234AB", "NL"), Ok("1234 AB".to_string()));
+        assert_eq!(normalize_zip_code(" 1234 ab ", "NL"), Ok("1234 AB".to_string()));
+        assert!(normalize_zip_code("0234AB", "NL").is_err());
+        assert!(normalize_zip_code("1234A", "NL").is_err());
+        assert!(normalize_zip_code("12345", "NL").is_err());
+    }
+
+    #[test]
+    fn british_postal_codes_are_split_in_outward_and_inward_code() {
+        assert_eq!(normalize_zip_code("sw1a1aa", "GB"), Ok("SW1A 1AA".to_string()));
+        assert_eq!(normalize_zip_code("M1 1AE", "GB"), Ok("M1 1AE".to_string()));
+        assert_eq!(normalize_zip_code("B338TH", "GB"), Ok("B33 8TH".to_string()));
+        assert!(normalize_zip_code("1A 1AA", "GB").is_err());
+        assert!(normalize_zip_code("SW1A 1A1", "GB").is_err());
+        assert!(normalize_zip_code("SW1A1AAA1", "GB").is_err());
+    }
+
+    #[test]
+    fn us_zip_codes_may_have_four_more_digits() {
+        assert_eq!(normalize_zip_code("90210", "US"), Ok("90210".to_string()));
+        assert_eq!(normalize_zip_code("902101234", "US"), Ok("90210-1234".to_string()));
+        assert_eq!(normalize_zip_code("90210-1234", "US"), Ok("90210-1234".to_string()));
+        assert!(normalize_zip_code("9021", "US").is_err());
+        assert!(normalize_zip_code("90210-12", "US").is_err());
+    }
+
+    #[test]
+    fn belgian_and_german_postal_codes_are_checked() {
+        assert_eq!(normalize_zip_code("9000", "BE"), Ok("9000".to_string()));
+        assert!(normalize_zip_code("0900", "BE").is_err());
+        assert!(normalize_zip_code("90000", "BE").is_err());
+        assert_eq!(normalize_zip_code("10115", "DE"), Ok("10115".to_string()));
+        assert!(normalize_zip_code("1011", "DE").is_err());
+        assert_eq!(normalize_zip_code(" 75001 ", "FR"), Ok("75001".to_string()));
+    }
+
+    #[test]
+    fn countries_are_normalized_to_codes() {
+        for (country, code) in &[("Nederland", "NL"), ("nld", "NL"), ("België", "BE"), ("BELGIQUE", "BE"), ("Deutschland", "DE"),
+            ("United Kingdom", "GB"), ("uk", "GB"), ("United States of America", "US"), ("fr", "FR")] {
+            assert_eq!(normalize_country(country).as_deref(), Some(*code), "{}", country);
+        }
+
+        assert_eq!(normalize_country("Atlantis"), None);
+    }
+
+    #[test]
+    fn street_lines_are_split() {
+        assert_eq!(split_street("Hoofdstraat 12"), ("Hoofdstraat".to_string(), "12".to_string()));
+        assert_eq!(split_street("Hoofdstraat 12 bis"), ("Hoofdstraat".to_string(), "12 bis".to_string()));
+        assert_eq!(split_street("Van der Does de Willeboissingel 3A"), ("Van der Does de Willeboissingel".to_string(), "3A".to_string()));
+        assert_eq!(split_street("12 Main Street"), ("Main Street".to_string(), "12".to_string()));
+        assert_eq!(split_street("Main Street"), ("Main Street".to_string(), String::new()));
+
+        assert_eq!(split_house_number("12 bis"), ("12".to_string(), "bis".to_string()));
+        assert_eq!(split_house_number("12A"), ("12".to_string(), "A".to_string()));
+        assert_eq!(split_house_number("12-1"), ("12".to_string(), "1".to_string()));
+    }
+
+    #[test]
+    fn free_text_addresses_are_normalized() {
+        let address = normalize(&line_address("Hoofdstraat 12 bis", "1234ab", "Utrecht", "Netherlands"));
+        assert_eq!(address.street, "Hoofdstraat");
+        assert_eq!(address.house_number, "12");
+        assert_eq!(address.house_number_addition, "bis");
+        assert_eq!(address.address_line_1, "Hoofdstraat 12-bis");
+        assert_eq!(address.zip_code, "1234 AB");
+        assert_eq!(address.country, "NL");
+        assert!(address.issues.is_empty(), "{:?}", address.issues);
+
+        let address = normalize(&line_address("12 Main Street", "902101234", "Beverly Hills", "USA"));
+        assert_eq!(address.address_line_1, "12 Main Street");
+        assert_eq!(address.zip_code, "90210-1234");
+        assert!(address.issues.is_empty(), "{:?}", address.issues);
+    }
+
+    #[test]
+    fn structured_addresses_are_normalized() {
+        let address = normalize(&street_address("Hoofdstraat", "12A", "1234 AB", "Utrecht", "NL"));
+        assert_eq!(address.address_line_1, "Hoofdstraat 12A");
+        assert_eq!(address.house_number_addition, "A");
+        assert!(address.issues.is_empty(), "{:?}", address.issues);
+
+        let address = normalize(&street_address("Downing Street", "10", "SW1A2AA", "London", "GB"));
+        assert_eq!(address.address_line_1, "10 Downing Street");
+        assert_eq!(address.zip_code, "SW1A 2AA");
+
+        let address = normalize(&street_address("Rue de la Loi", "16 bis", "1000", "Bruxelles", "BE"));
+        assert_eq!(address.address_line_1, "Rue de la Loi 16 bis");
+
+        let address = normalize(&street_address("Unter den Linden", "77", "10117", "Berlin", "DE"));
+        assert_eq!(address.address_line_1, "Unter den Linden 77");
+        assert!(address.issues.is_empty(), "{:?}", address.issues);
+    }
+
+    #[test]
+    fn incomplete_addresses_are_flagged() {
+        let address = normalize(&street_address("", "", "1234 AB", "", "NL"));
+        assert!(address.issues.contains(&"The address has no street".to_string()));
+        assert!(address.issues.contains(&"The address has no house number".to_string()));
+        assert!(address.issues.contains(&"The address has no city".to_string()));
+
+        //Not every country needs a house number
+        let address = normalize(&line_address("Main Street", "90210", "Beverly Hills", "US"));
+        assert!(address.issues.is_empty(), "{:?}", address.issues);
+
+        let address = normalize(&line_address("Hoofdstraat 12", "0234AB", "Utrecht", "XX1"));
+        assert_eq!(address.zip_code, "0234AB");
+        assert_eq!(address.issues.len(), 1, "{:?}", address.issues);
+        assert!(address.issues[0].starts_with("Unknown country"));
+
+        let address = normalize(&line_address("Hoofdstraat 12", "0234AB", "Utrecht", "NL"));
+        assert_eq!(address.issues, vec!["Invalid postal code '0234AB' for NL".to_string()]);
+    }
+}
@@ -0,0 +1,154 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Serialize, Deserialize};
+
+use crate::environment::get_environment;
+
+/// Header holding the session ID of the staff member making the request
+const SESSION_HEADER: &str = "Authorization";
+
+/// Error returned by check_session for sessions the authentication server does not know
+const UNAUTHENTICATED: &str = "The session is not valid";
+
+/// A staff member whose session the authentication server confirmed to be an admin's
+pub struct Admin {
+    /// User ID at the authentication server, recorded in audit trails
+    pub user_id:    String
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionCheckRequest<'a> {
+    session_id:     &'a str
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionCheckResponse {
+    authenticated:  bool,
+    user_id:        Option<String>,
+
+    #[serde(default)]
+    is_admin:       bool
+}
+
+/**
+Require the request to be made by an admin. The session ID is read from the Authorization header, with or without a 'Bearer ' prefix,
+and checked with the authentication server
+
+## Params
+    **req** The request
+
+## Returns
+    **Ok**: The admin making the request
+    **Err**: The response to return instead: 401 without a valid session, 403 if the user is not an admin, 500 if the check failed
+*/
+pub async fn require_admin(req: &HttpRequest) -> Result<Admin, HttpResponse> {
+    let session_id = match req.headers().get(SESSION_HEADER).and_then(|header| header.to_str().ok()) {
+        Some(header) => header.trim_start_matches("Bearer ").trim().to_string(),
+        None => return Err(HttpResponse::Unauthorized().json("Missing required header 'Authorization'"))
+    };
+
+    if session_id.is_empty() {
+        return Err(HttpResponse::Unauthorized().json("Missing required header 'Authorization'"));
+    }
+
+    let result = web::block(move || {
+        let env = get_environment();
+        check_session(&env.auth_host, &env.auth_apikey, &session_id)
+    }).await;
+
+    match result {
+        Ok(Some(admin)) => Ok(admin),
+        Ok(None) => Err(HttpResponse::Forbidden().json("Only admins may make this request")),
+        Err(actix_web::error::BlockingError::Error(e)) if e == UNAUTHENTICATED => Err(HttpResponse::Unauthorized().json("The session is not valid")),
+        Err(e) => Err(HttpResponse::InternalServerError().body(e.to_string()))
+    }
+}
+
+/**
+Check a session with the authentication server
+
+## Params
+    **auth_host** Base URL of the authentication server
+    **api_key** OrderSync's key for the authentication server
+    **session_id** The session to check
+
+## Returns
+    **Ok**: The admin the session belongs to, or None if the user is not an admin
+    **Err**: UNAUTHENTICATED for an invalid session, or a summary of what went wrong
+*/
+fn check_session(auth_host: &str, api_key: &str, session_id: &str) -> Result<Option<Admin>, String> {
+    let result = reqwest::blocking::Client::new().post(&format!("{}/session/check", auth_host.trim_end_matches('/')))
+        .header("Authorization", api_key)
+        .json(&SessionCheckRequest { session_id })
+        .send();
+
+    let response = match result {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => return Err(format!("The authentication server responded with {}", response.status())),
+        Err(e) => return Err(e.to_string())
+    };
+
+    let response: SessionCheckResponse = match response.json() {
+        Ok(response) => response,
+        Err(e) => return Err(format!("Unexpected response from the authentication server: {}", e))
+    };
+
+    match (response.authenticated, response.user_id) {
+        (true, Some(user_id)) if response.is_admin => Ok(Some(Admin { user_id })),
+        (true, Some(_)) => Ok(None),
+        _ => Err(UNAUTHENTICATED.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    fn auth_host(path: &str) -> String {
+        format!("{}/{}", mockito::server_url(), path)
+    }
+
+    #[test]
+    fn admins_are_accepted() {
+        let server = mock("POST", "/admin/session/check")
+            .match_header("authorization", "api-key")
+            .match_body(Matcher::Json(serde_json::json!({ "sessionId": "session" })))
+            .with_status(200)
+            .with_body(r#"{"authenticated": true, "userId": "user-1", "isAdmin": true}"#)
+            .create();
+
+        let admin = check_session(&auth_host("admin"), "api-key", "session").unwrap().unwrap();
+        server.assert();
+        assert_eq!(admin.user_id, "user-1");
+    }
+
+    #[test]
+    fn other_users_are_not_admins() {
+        let _server = mock("POST", "/user/session/check")
+            .with_status(200)
+            .with_body(r#"{"authenticated": true, "userId": "user-2", "isAdmin": false}"#)
+            .create();
+
+        assert!(check_session(&auth_host("user"), "api-key", "session").unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_sessions_are_rejected() {
+        let _server = mock("POST", "/invalid/session/check")
+            .with_status(200)
+            .with_body(r#"{"authenticated": false}"#)
+            .create();
+
+        assert_eq!(check_session(&auth_host("invalid"), "api-key", "session").err().unwrap(), UNAUTHENTICATED);
+    }
+
+    #[test]
+    fn failing_checks_are_errors() {
+        let _server = mock("POST", "/down/session/check").with_status(502).create();
+
+        let error = check_session(&auth_host("down"), "api-key", "session").err().unwrap();
+        assert!(error.contains("502"));
+    }
+}
\ No newline at end of file
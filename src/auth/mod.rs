@@ -1 +1,2 @@
-pub mod wix_access_token;
\ No newline at end of file
+pub mod wix_access_token;
+pub mod admin;
\ No newline at end of file
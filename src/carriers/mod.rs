@@ -0,0 +1,68 @@
+pub mod parcel_api;
+
+use crate::shipping::ShippingSettings;
+use crate::types::order::Address;
+
+/// A parcel to be sent, as handed to a carrier
+pub struct ShipmentRequest {
+    /// Our reference for the shipment, printed on the label. The order number
+    pub reference:      String,
+    pub recipient_name: String,
+
+    /// Company of the recipient, empty for private persons
+    pub company:        String,
+    pub email:          String,
+    pub phone:          String,
+    pub address:        Address,
+    pub weight_kg:      f64
+}
+
+/// A shipment as registered with a carrier
+pub struct CreatedShipment {
+    pub tracking_number:    String,
+
+    /// Page where the recipient can follow the parcel, if the carrier provides one
+    pub tracking_url:       Option<String>,
+
+    /// The shipping label, as a PDF
+    pub label_pdf:          Vec<u8>
+}
+
+/// A shipping carrier OrderSync can create shipments with
+pub trait Carrier {
+    /// Name of the carrier, e.g. 'PostNL'. Also used as the shipping provider in Wix
+    fn name(&self) -> &str;
+
+    /**
+    Register a shipment with the carrier
+
+    ## Params
+        **shipment** The parcel to send
+
+    ## Returns
+        **Ok**: The tracking number and label
+        **Err**: A summary of what went wrong, e.g. an address the carrier rejected
+    */
+    fn create_shipment(&self, shipment: &ShipmentRequest) -> Result<CreatedShipment, String>;
+}
+
+/**
+Get the carrier configured for an instance
+
+## Params
+    **settings** The instance's shipping settings
+
+## Returns
+    **Ok**: The carrier
+    **Err**: The carrier is not supported or not configured
+*/
+pub fn carrier_for(settings: &ShippingSettings) -> Result<Box<dyn Carrier>, String> {
+    if settings.api_url.is_empty() || settings.api_key.is_empty() {
+        return Err(format!("No API credentials are configured for {}", settings.carrier));
+    }
+
+    match settings.carrier.as_str() {
+        "PostNL" | "DHL" => Ok(Box::new(parcel_api::ParcelApiCarrier::new(&settings.carrier, &settings.api_url, &settings.api_key, &settings.customer_code))),
+        carrier => Err(format!("Carrier '{}' is not supported", carrier))
+    }
+}
\ No newline at end of file
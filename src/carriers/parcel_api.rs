@@ -0,0 +1,212 @@
+use serde::{Serialize, Deserialize};
+
+use crate::carriers::{Carrier, CreatedShipment, ShipmentRequest};
+
+/// Carrier with a REST shipment API in the style of PostNL and DHL Parcel:
+/// a shipment is POSTed as JSON with an API key header, the response holds the barcode and a base64 encoded label
+pub struct ParcelApiCarrier {
+    name:           String,
+
+    /// Base URL of the API, so a test or sandbox environment can be used
+    api_url:        String,
+    api_key:        String,
+
+    /// Customer code assigned by the carrier
+    customer_code:  String
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShipmentBody<'a> {
+    customer_code:  &'a str,
+    reference:      &'a str,
+    label_format:   &'static str,
+    receiver:       Receiver<'a>,
+
+    /// Weight in grams
+    weight:         i64
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Receiver<'a> {
+    name:                   &'a str,
+    company_name:           &'a str,
+    email:                  &'a str,
+    phone_number:           &'a str,
+    street:                 &'a str,
+    house_number:           &'a str,
+    house_number_addition:  &'a str,
+    additional:             &'a str,
+    postal_code:            &'a str,
+    city:                   &'a str,
+    country_code:           &'a str
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ShipmentResponse {
+    barcode:        String,
+    tracking_url:   Option<String>,
+    labels:         Vec<Label>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Label {
+    /// Base64 encoded
+    content:        String,
+    label_type:     String
+}
+
+impl ParcelApiCarrier {
+    pub fn new(name: &str, api_url: &str, api_key: &str, customer_code: &str) -> ParcelApiCarrier {
+        ParcelApiCarrier {
+            name:           name.to_string(),
+            api_url:        api_url.trim_end_matches('/').to_string(),
+            api_key:        api_key.to_string(),
+            customer_code:  customer_code.to_string()
+        }
+    }
+}
+
+impl Carrier for ParcelApiCarrier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn create_shipment(&self, shipment: &ShipmentRequest) -> Result<CreatedShipment, String> {
+        let body = ShipmentBody {
+            customer_code:  &self.customer_code,
+            reference:      &shipment.reference,
+            label_format:   "PDF",
+            receiver: Receiver {
+                name:                   &shipment.recipient_name,
+                company_name:           &shipment.company,
+                email:                  &shipment.email,
+                phone_number:           &shipment.phone,
+                street:                 &shipment.address.street,
+                house_number:           &shipment.address.house_number,
+                house_number_addition:  &shipment.address.house_number_addition,
+                additional:             &shipment.address.address_line_2,
+                postal_code:            &shipment.address.zip_code,
+                city:                   &shipment.address.city,
+                country_code:           &shipment.address.country
+            },
+            weight: (shipment.weight_kg * 1000.0).round() as i64
+        };
+
+        let result = reqwest::blocking::Client::new().post(&format!("{}/shipments", self.api_url))
+            .header("apikey", &self.api_key)
+            .json(&body)
+            .send();
+
+        let response = match result {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => return Err(format!("{} responded with status {}: {}", self.name, response.status(), response.text().unwrap_or_default())),
+            Err(e) => return Err(e.to_string())
+        };
+
+        let response: ShipmentResponse = match response.json() {
+            Ok(response) => response,
+            Err(e) => return Err(format!("Unexpected response from {}: {}", self.name, e))
+        };
+
+        let label = match response.labels.iter().find(|label| label.label_type == "Label") {
+            Some(label) => label,
+            None => return Err(format!("{} did not return a label", self.name))
+        };
+
+        let label_pdf = match base64::decode(&label.content) {
+            Ok(label_pdf) => label_pdf,
+            Err(e) => return Err(format!("{} returned an invalid label: {}", self.name, e))
+        };
+
+        Ok(CreatedShipment {
+            tracking_number:    response.barcode,
+            tracking_url:       response.tracking_url,
+            label_pdf
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use crate::types::order::Address;
+
+    fn shipment() -> ShipmentRequest {
+        ShipmentRequest {
+            reference:      "10042".to_string(),
+            recipient_name: "Jan Jansen".to_string(),
+            company:        String::new(),
+            email:          "jan@example.com".to_string(),
+            phone:          String::new(),
+            address:        Address {
+                address_type:           "SHIPMENT".to_string(),
+                full_name:              "Jan Jansen".to_string(),
+                company:                String::new(),
+                email:                  String::new(),
+                phone:                  String::new(),
+                address_line_1:         "Damrak 1".to_string(),
+                address_line_2:         String::new(),
+                street:                 "Damrak".to_string(),
+                house_number:           "1".to_string(),
+                house_number_addition:  String::new(),
+                zip_code:               "1012 LG".to_string(),
+                city:                   "Amsterdam".to_string(),
+                country:                "NL".to_string(),
+                needs_review:           false
+            },
+            weight_kg:      1.25
+        }
+    }
+
+    /// A carrier talking to the mock server, each test under its own path so they can run in parallel
+    fn carrier(path: &str) -> ParcelApiCarrier {
+        ParcelApiCarrier::new("PostNL", &format!("{}/{}/", mockito::server_url(), path), "secret", "ABCD")
+    }
+
+    #[test]
+    fn returns_the_barcode_and_decoded_label() {
+        let server = mock("POST", "/created/shipments")
+            .match_header("apikey", "secret")
+            .match_body(Matcher::PartialJsonString(r#"{"customerCode": "ABCD", "reference": "10042", "weight": 1250, "receiver": {"postalCode": "1012 LG", "countryCode": "NL"}}"#.to_string()))
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{"barcode": "3SABCD123456789", "trackingUrl": "https://example.com/3SABCD123456789", "labels": [{{"content": "{}", "labelType": "Label"}}]}}"#, base64::encode(b"%PDF-1.4")))
+            .create();
+
+        let created = carrier("created").create_shipment(&shipment()).unwrap();
+        server.assert();
+
+        assert_eq!(created.tracking_number, "3SABCD123456789");
+        assert_eq!(created.tracking_url.as_deref(), Some("https://example.com/3SABCD123456789"));
+        assert_eq!(created.label_pdf, b"%PDF-1.4");
+    }
+
+    #[test]
+    fn rejected_shipments_are_errors() {
+        let _server = mock("POST", "/rejected/shipments")
+            .with_status(400)
+            .with_body("Invalid postal code")
+            .create();
+
+        let error = carrier("rejected").create_shipment(&shipment()).err().unwrap();
+        assert!(error.contains("400"));
+        assert!(error.contains("Invalid postal code"));
+    }
+
+    #[test]
+    fn responses_without_a_label_are_errors() {
+        let _server = mock("POST", "/unlabeled/shipments")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"barcode": "3SABCD123456789", "labels": [{"content": "", "labelType": "Return"}]}"#)
+            .create();
+
+        let error = carrier("unlabeled").create_shipment(&shipment()).err().unwrap();
+        assert_eq!(error, "PostNL did not return a label");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,255 @@
+use serde::Serialize;
+use mysql::{Params, Row, Transaction, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+
+use crate::database::Database;
+use crate::types::money::Money;
+use crate::types::wix::BuyerInfo;
+use crate::types::order::{Order, ORDER_COLUMNS};
+
+/// A customer of a store, aggregated over their orders
+#[derive(Serialize)]
+pub struct Customer {
+    customer_id:        String,
+    instance_id:        String,
+
+    /// Wix customer ID, empty if Wix did not provide one
+    wix_customer_id:    String,
+    identity_type:      String,
+    email:              String,
+    name:               String,
+    phone:              String,
+
+    /// Date of the first order, epoch seconds
+    first_order_date:   i64,
+
+    /// Date of the latest order, epoch seconds
+    last_order_date:    i64,
+    order_count:        i64,
+
+    /// Total of the customer's orders less their refunds, per currency
+    lifetime_value:     Vec<Money>
+}
+
+/**
+Get the key a customer is identified by within an instance.
+This is the Wix customer ID, or the lowercased email address if Wix did not provide an ID
+*/
+fn customer_key(buyer_info: &BuyerInfo) -> String {
+    if buyer_info.id.is_empty() {
+        buyer_info.email.to_lowercase()
+    } else {
+        buyer_info.id.clone()
+    }
+}
+
+/**
+Create or update the customer who placed an order.
+Call this in the transaction storing the order, so the customer only counts orders which were actually stored
+
+## Params
+    **tx** The transaction storing the order
+    **instance_id** The instance the order belongs to
+    **buyer_info** Buyer information of the order
+    **order_date** Date of the order, epoch seconds
+    **total** Total price of the order less the refunds stored with it, in the order's currency
+
+## Returns
+    **Ok**: The customer ID
+    **Err**: A summary of what went wrong
+*/
+pub fn upsert_customer(tx: &mut Transaction, instance_id: &str, buyer_info: &BuyerInfo, order_date: i64, total: &Money) -> Result<String, String> {
+    let key = customer_key(buyer_info);
+    let name = format!("{} {}", buyer_info.first_name, buyer_info.last_name);
+
+    let result = tx.exec::<Row, &str, Params>("SELECT customer_id FROM customers WHERE instance_id = :instance_id AND customer_key = :customer_key", params! {
+        "instance_id" => instance_id,
+        "customer_key" => key.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let rows = result.unwrap();
+    if let Some(row) = rows.first() {
+        let customer_id: String = row.get("customer_id").unwrap();
+
+        //Contact details are taken from the most recent order, orders aren't necessarily imported in chronological order
+        let result = tx.exec::<usize, &str, Params>("UPDATE customers SET \
+            email = IF(:order_date >= last_order_date, :email, email), \
+            name = IF(:order_date >= last_order_date, :name, name), \
+            phone = IF(:order_date >= last_order_date, :phone, phone), \
+            first_order_date = LEAST(first_order_date, :order_date), \
+            last_order_date = GREATEST(last_order_date, :order_date), \
+            order_count = order_count + 1 \
+            WHERE customer_id = :customer_id", params! {
+            "email" => buyer_info.email.clone(),
+            "name" => name,
+            "phone" => buyer_info.phone.clone(),
+            "order_date" => order_date,
+            "customer_id" => customer_id.clone()
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        add_lifetime_value(tx, &customer_id, total)?;
+        return Ok(customer_id);
+    }
+
+    let customer_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let result = tx.exec::<usize, &str, Params>("INSERT INTO customers \
+        (customer_id, instance_id, customer_key, wix_customer_id, identity_type, email, name, phone, first_order_date, last_order_date, order_count) \
+        VALUES (:customer_id, :instance_id, :customer_key, :wix_customer_id, :identity_type, :email, :name, :phone, :order_date, :order_date, 1)", params! {
+        "customer_id" => customer_id.clone(),
+        "instance_id" => instance_id,
+        "customer_key" => key,
+        "wix_customer_id" => buyer_info.id.clone(),
+        "identity_type" => buyer_info.identity_type.to_string(),
+        "email" => buyer_info.email.clone(),
+        "name" => name,
+        "phone" => buyer_info.phone.clone(),
+        "order_date" => order_date
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    add_lifetime_value(tx, &customer_id, total)?;
+    Ok(customer_id)
+}
+
+/// Lifetime values are kept per currency, amounts in different currencies can't be added up
+fn add_lifetime_value(tx: &mut Transaction, customer_id: &str, total: &Money) -> Result<(), String> {
+    let result = tx.exec_drop("INSERT INTO customer_lifetime_values (customer_id, currency, lifetime_value) VALUES (:customer_id, :currency, :total) \
+        ON DUPLICATE KEY UPDATE lifetime_value = lifetime_value + VALUES(lifetime_value)", params! {
+        "customer_id" => customer_id,
+        "currency" => total.currency.clone(),
+        "total" => total.amount
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Subtract refunds made after an order was stored from the lifetime value of the order's customer.
+Call this in the transaction storing the refunds
+
+## Params
+    **tx** The transaction storing the refunds
+    **order_id** The order the refunds were made for
+    **refunded** Sum of the refunds, in the order's currency
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn subtract_refunds(tx: &mut Transaction, order_id: &str, refunded: &Money) -> Result<(), String> {
+    let result = tx.exec_drop("UPDATE customer_lifetime_values v INNER JOIN orders o ON o.customer_id = v.customer_id \
+        SET v.lifetime_value = v.lifetime_value - :refunded WHERE o.order_id = :order_id AND v.currency = :currency", params! {
+        "refunded" => refunded.amount,
+        "order_id" => order_id,
+        "currency" => refunded.currency.clone()
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the customers of an instance, most recent buyers first
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the customers for
+    **limit** Maximum number of customers to return
+    **offset** Number of customers to skip
+
+## Returns
+    **Ok**: The customers
+    **Err**: A summary of what went wrong
+*/
+pub fn get_customers(database: Database, instance_id: String, limit: u64, offset: u64) -> Result<Vec<Customer>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT customer_id, instance_id, wix_customer_id, identity_type, email, name, phone, first_order_date, last_order_date, order_count \
+        FROM customers WHERE instance_id = :instance_id ORDER BY last_order_date DESC LIMIT :limit OFFSET :offset", params! {
+        "instance_id" => instance_id.clone(),
+        "limit" => limit,
+        "offset" => offset
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut customers: Vec<Customer> = result.unwrap().into_iter().map(|row| Customer {
+        customer_id:        row.get("customer_id").unwrap(),
+        instance_id:        row.get("instance_id").unwrap(),
+        wix_customer_id:    row.get("wix_customer_id").unwrap(),
+        identity_type:      row.get("identity_type").unwrap(),
+        email:              row.get("email").unwrap(),
+        name:               row.get("name").unwrap(),
+        phone:              row.get("phone").unwrap(),
+        first_order_date:   row.get("first_order_date").unwrap(),
+        last_order_date:    row.get("last_order_date").unwrap(),
+        order_count:        row.get("order_count").unwrap(),
+        lifetime_value:     Vec::new()
+    }).collect();
+
+    let result = conn.exec::<Row, &str, Params>("SELECT v.customer_id, v.currency, v.lifetime_value FROM customer_lifetime_values v \
+        INNER JOIN (SELECT customer_id FROM customers WHERE instance_id = :instance_id ORDER BY last_order_date DESC LIMIT :limit OFFSET :offset) c ON c.customer_id = v.customer_id \
+        ORDER BY v.currency ASC", params! {
+        "instance_id" => instance_id,
+        "limit" => limit,
+        "offset" => offset
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    for row in result.unwrap() {
+        let customer_id: String = row.get("customer_id").unwrap();
+        if let Some(customer) = customers.iter_mut().find(|customer| customer.customer_id == customer_id) {
+            let currency: String = row.get("currency").unwrap();
+            customer.lifetime_value.push(Money::new(row.get("lifetime_value").unwrap(), &currency));
+        }
+    }
+
+    Ok(customers)
+}
+
+/**
+Get the orders placed by a customer, most recent first
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the customer belongs to
+    **customer_id** The customer to get the orders for
+
+## Returns
+    **Ok**: The orders
+    **Err**: A summary of what went wrong
+*/
+pub fn get_customer_orders(database: Database, instance_id: String, customer_id: String) -> Result<Vec<Order>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(format!("SELECT {} FROM orders o WHERE o.customer_id = :customer_id AND o.instance_id = :instance_id ORDER BY o.order_date DESC", ORDER_COLUMNS), params! {
+        "customer_id" => customer_id,
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().iter().map(Order::from_row).collect())
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub mod packing_slip;
+pub mod pick_list;
+
+/// HTML to PDF converter, installed in the Docker image
+const WKHTMLTOPDF: &str = "wkhtmltopdf";
+
+/**
+Convert an HTML document to PDF
+
+## Params
+    **html** The HTML document. External resources such as logos are fetched by the converter
+
+## Returns
+    **Ok**: The PDF document
+    **Err**: A summary of what went wrong
+*/
+pub fn html_to_pdf(html: &str) -> Result<Vec<u8>, String> {
+    let child = Command::new(WKHTMLTOPDF)
+        .args(&["--quiet", "--encoding", "utf-8", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => return Err(format!("Unable to start {}: {}", WKHTMLTOPDF, e))
+    };
+
+    //Write the input from another thread, the converter may fill the stdout pipe before it has read all input
+    let mut stdin = child.stdin.take().unwrap();
+    let input = html.as_bytes().to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(e) => return Err(e.to_string())
+    };
+
+    if let Ok(Err(e)) = writer.join() {
+        return Err(format!("Unable to write to {}: {}", WKHTMLTOPDF, e));
+    }
+
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", WKHTMLTOPDF, String::from_utf8_lossy(&output.stderr)));
+    }
+
+    Ok(output.stdout)
+}
\ No newline at end of file
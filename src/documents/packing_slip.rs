@@ -0,0 +1,151 @@
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use tera::{Tera, Context};
+
+use crate::database::Database;
+use crate::documents::html_to_pdf;
+use crate::orders::get_order_details;
+use crate::types::order::OrderDetails;
+
+/// Default packing slip template, used when an instance hasn't configured its own
+const DEFAULT_TEMPLATE: &str = "documents/packing_slip.html";
+
+/// Packing slip settings of an instance
+#[derive(Serialize, Deserialize)]
+pub struct PackingSlipSettings {
+    pub instance_id:    String,
+
+    /// URL of the logo printed on the slip
+    pub logo_url:       Option<String>,
+
+    /// Tera template replacing the default template. It receives the same context, see render_packing_slips
+    pub template:       Option<String>
+}
+
+/**
+Get the packing slip settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the settings for
+
+## Returns
+    **Ok**: The settings. An instance without settings gets the defaults
+    **Err**: A summary of what went wrong
+*/
+pub fn get_settings(database: Database, instance_id: String) -> Result<PackingSlipSettings, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT logo_url, template FROM packing_slip_settings WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(match result.unwrap().first() {
+        Some(row) => PackingSlipSettings {
+            instance_id,
+            logo_url: row.get("logo_url").unwrap(),
+            template: row.get("template").unwrap()
+        },
+        None => PackingSlipSettings { instance_id, logo_url: None, template: None }
+    })
+}
+
+/**
+Create or replace the packing slip settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **settings** The new settings
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn set_settings(database: Database, settings: PackingSlipSettings) -> Result<(), String> {
+    if let Some(template) = &settings.template {
+        validate_template(template)?;
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO packing_slip_settings (instance_id, logo_url, template) VALUES (:instance_id, :logo_url, :template) \
+        ON DUPLICATE KEY UPDATE logo_url = :logo_url, template = :template", params! {
+        "instance_id" => settings.instance_id,
+        "logo_url" => settings.logo_url,
+        "template" => settings.template
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Check a packing slip template for syntax errors, so they are caught when it is saved rather than when the warehouse tries to print
+
+## Params
+    **template** The Tera template
+
+## Returns
+    **Ok**: The template is valid
+    **Err**: A description of the syntax error
+*/
+pub fn validate_template(template: &str) -> Result<(), String> {
+    match Tera::default().add_raw_template("packing_slip", template) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Invalid template: {}", e))
+    }
+}
+
+/**
+Render the packing slips for one or more orders into a single PDF, one order per page.
+Digital and custom amount items are left out, orders consisting of only such items are skipped.
+
+The template receives `logo_url` and `slips`, a list of orders as described by OrderDetails.
+
+## Params
+    **database** Instance of a Database object
+    **tera** The Tera instance holding the default template
+    **instance_id** The instance the orders belong to
+    **order_ids** The orders to render, in print order
+
+## Returns
+    **Ok**: The PDF document
+    **Err**: A summary of what went wrong
+*/
+pub fn render_packing_slips(database: Database, tera: &Tera, instance_id: String, order_ids: &[String]) -> Result<Vec<u8>, String> {
+    let mut slips: Vec<OrderDetails> = Vec::new();
+    for order_id in order_ids {
+        match get_order_details(database.clone(), order_id)? {
+            //Only the items which are packed are listed, orders with nothing to pack get no slip
+            Some(mut details) if details.order.instance_id == instance_id => {
+                details.items.retain(|item| item.requires_shipping());
+                if !details.items.is_empty() {
+                    slips.push(details);
+                }
+            },
+            _ => return Err(format!("No order with ID {} in instance {}", order_id, instance_id))
+        }
+    }
+
+    let settings = get_settings(database, instance_id)?;
+
+    let mut ctx = Context::new();
+    ctx.insert("logo_url", &settings.logo_url);
+    ctx.insert("slips", &slips);
+
+    //Names, notes and addresses are entered by buyers, so a custom template is escaped like the default one
+    let rendered = match &settings.template {
+        Some(template) => Tera::one_off(template, &ctx, true),
+        None => tera.render(DEFAULT_TEMPLATE, &ctx)
+    };
+
+    match rendered {
+        Ok(html) => html_to_pdf(&html),
+        Err(e) => Err(format!("Unable to render packing slip: {}", e))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use tera::{Tera, Context};
+
+use crate::database::Database;
+use crate::export::Locale;
+use crate::order_filter::OrderFilter;
+
+/// Pick list template
+const TEMPLATE: &str = "documents/pick_list.html";
+
+/// Storage location of a SKU in the warehouse
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinLocation {
+    pub sku:            String,
+
+    /// Location code, e.g. 'A-03-2'. Pick lists sorted by location follow the alphanumeric order of these codes
+    pub bin_location:   String
+}
+
+/// Order in which the lines of a pick list are sorted
+#[derive(Clone, Copy, PartialEq)]
+pub enum PickListSort {
+    /// By bin location, SKUs without a location last
+    BinLocation,
+    Sku
+}
+
+/// An order a picked unit belongs to
+#[derive(Serialize)]
+pub struct PickListOrder {
+    pub order_id:       String,
+    pub order_number:   i64,
+    pub quantity:       i64
+}
+
+/// All units of a SKU and option combination to pick
+#[derive(Serialize)]
+pub struct PickListLine {
+    pub sku:            String,
+    pub name:           String,
+
+    /// The selected options, e.g. 'Color: Red, Size: M'
+    pub options:        String,
+    pub bin_location:   Option<String>,
+    pub quantity:       i64,
+    pub orders:         Vec<PickListOrder>
+}
+
+/// A consolidated pick list for a wave of orders
+#[derive(Serialize)]
+pub struct PickList {
+    /// Epoch seconds
+    pub created_at:     i64,
+    pub order_count:    usize,
+    pub lines:          Vec<PickListLine>
+}
+
+/**
+Get the bin locations of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the locations for
+
+## Returns
+    **Ok**: The locations, by SKU
+    **Err**: A summary of what went wrong
+*/
+pub fn get_bin_locations(database: Database, instance_id: String) -> Result<Vec<BinLocation>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT sku, bin_location FROM bin_locations WHERE instance_id = :instance_id ORDER BY sku ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| BinLocation {
+            sku:            row.get("sku").unwrap(),
+            bin_location:   row.get("bin_location").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Replace the bin locations of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to set the locations for
+    **locations** The new locations. SKUs which are not listed lose their location
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A SKU is listed twice, or a summary of what went wrong
+*/
+pub fn set_bin_locations(database: Database, instance_id: String, locations: Vec<BinLocation>) -> Result<(), String> {
+    let mut skus: Vec<&str> = locations.iter().map(|location| location.sku.as_str()).collect();
+    skus.sort_unstable();
+    if let Some(duplicate) = skus.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("SKU '{}' is listed more than once", duplicate[0]));
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let result = tx.exec_drop("DELETE FROM bin_locations WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    for location in locations {
+        let result = tx.exec_drop("INSERT INTO bin_locations (instance_id, sku, bin_location) VALUES (:instance_id, :sku, :bin_location)", params! {
+            "instance_id" => instance_id.clone(),
+            "sku" => location.sku,
+            "bin_location" => location.bin_location
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Build a consolidated pick list for the filtered orders.
+Only paid orders which are not fulfilled or canceled are picked, the filter cannot override this.
+Units are grouped by SKU and option combination, digital and custom amount items are left out.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to pick
+    **sort** Order of the lines
+
+## Returns
+    **Ok**: The pick list
+    **Err**: A summary of what went wrong
+*/
+pub fn build_pick_list(database: Database, filter: &OrderFilter, sort: PickListSort) -> Result<PickList, String> {
+    let (condition, mut params) = filter.to_sql();
+    params.push(("instance_id".to_string(), mysql::Value::from(filter.instance_id.clone())));
+
+    let query = format!("SELECT o.order_id, o.wix_order_id, i.sku, i.name, i.quantity, b.bin_location, \
+        (SELECT GROUP_CONCAT(CONCAT(io.`option`, ': ', io.selection) ORDER BY io.`option` SEPARATOR ', ') FROM order_item_options io WHERE io.order_item_id = i.order_item_id) AS options \
+        FROM orders o INNER JOIN order_items i ON i.order_id = o.order_id \
+        LEFT JOIN bin_locations b ON b.instance_id = :instance_id AND b.sku = i.sku AND i.sku <> '' \
+        WHERE {} AND o.payment_status = 'Paid' AND o.fulfillment_status NOT IN ('Fulfilled', 'Canceled') \
+        AND COALESCE(i.item_type, 'UNSPECIFIED') IN ('PHYSICAL', 'UNSPECIFIED') ORDER BY o.order_date ASC", condition);
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(query, Params::from(params));
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    //Items without a SKU are grouped by name
+    let mut lines: BTreeMap<(String, String, String), PickListLine> = BTreeMap::new();
+    let mut order_ids: Vec<String> = Vec::new();
+
+    for row in result.unwrap() {
+        let order_id: String = row.get("order_id").unwrap();
+        let sku: String = row.get("sku").unwrap();
+        let name: String = row.get("name").unwrap();
+        let options = row.get::<Option<String>, &str>("options").unwrap().unwrap_or_default();
+        let quantity: i64 = row.get("quantity").unwrap();
+
+        let key = (sku.clone(), if sku.is_empty() { name.clone() } else { String::new() }, options.clone());
+        let line = lines.entry(key).or_insert_with(|| PickListLine {
+            sku,
+            name,
+            options,
+            bin_location:   row.get("bin_location").unwrap(),
+            quantity:       0,
+            orders:         Vec::new()
+        });
+
+        line.quantity += quantity;
+        match line.orders.iter_mut().find(|order| order.order_id == order_id) {
+            Some(order) => order.quantity += quantity,
+            None => line.orders.push(PickListOrder { order_id: order_id.clone(), order_number: row.get("wix_order_id").unwrap(), quantity })
+        }
+
+        if !order_ids.contains(&order_id) {
+            order_ids.push(order_id);
+        }
+    }
+
+    let mut lines: Vec<PickListLine> = lines.into_values().collect();
+    if sort == PickListSort::BinLocation {
+        //Stable, so lines in the same location stay sorted by SKU
+        lines.sort_by(|a, b| match (&a.bin_location, &b.bin_location) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal
+        });
+    }
+
+    Ok(PickList {
+        created_at:     chrono::Utc::now().timestamp(),
+        order_count:    order_ids.len(),
+        lines
+    })
+}
+
+/**
+Write a pick list as CSV, one row per line
+
+## Params
+    **pick_list** The pick list
+    **locale** Determines the field delimiter
+
+## Returns
+    **Ok**: The file
+    **Err**: A summary of what went wrong
+*/
+pub fn write_csv(pick_list: &PickList, locale: Locale) -> Result<Vec<u8>, String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(locale.csv_delimiter).from_writer(Vec::new());
+    if let Err(e) = writer.write_record(&["Bin location", "SKU", "Name", "Options", "Quantity", "Orders"]) {
+        return Err(e.to_string());
+    }
+
+    for line in &pick_list.lines {
+        let orders: Vec<String> = line.orders.iter().map(|order| format!("#{} ({})", order.order_number, order.quantity)).collect();
+        let record = [
+            line.bin_location.clone().unwrap_or_default(),
+            line.sku.clone(),
+            line.name.clone(),
+            line.options.clone(),
+            line.quantity.to_string(),
+            orders.join(", ")
+        ];
+
+        if let Err(e) = writer.write_record(&record) {
+            return Err(e.to_string());
+        }
+    }
+
+    match writer.into_inner() {
+        Ok(file) => Ok(file),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Render a pick list as a printable HTML page.
+The template receives `pick_list`, as described by PickList.
+
+## Params
+    **tera** The Tera instance holding the template
+    **pick_list** The pick list
+
+## Returns
+    **Ok**: The HTML page
+    **Err**: A summary of what went wrong
+*/
+pub fn render_html(tera: &Tera, pick_list: &PickList) -> Result<String, String> {
+    let mut ctx = Context::new();
+    ctx.insert("pick_list", pick_list);
+
+    match tera.render(TEMPLATE, &ctx) {
+        Ok(html) => Ok(html),
+        Err(e) => Err(format!("Unable to render pick list: {}", e))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::accounting::get_account_mappings;
+
+#[get("/accounting/accounts")]
+pub async fn get_accounts(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || get_account_mappings(database, instance_id)).await;
+
+    match result {
+        Ok(mappings) => HttpResponse::Ok().json(mappings),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod get_accounts;
+pub mod post_accounts;
\ No newline at end of file
@@ -0,0 +1,23 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::accounting::{set_account_mappings, AccountMapping};
+
+#[post("/accounting/accounts")]
+pub async fn post_accounts(data: web::Data<AppData>, req: HttpRequest, body: web::Json<Vec<AccountMapping>>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let mappings = body.into_inner();
+    let result = web::block(move || set_account_mappings(database, instance_id, mappings)).await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
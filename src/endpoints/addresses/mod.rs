@@ -0,0 +1 @@
+pub mod get_review;
\ No newline at end of file
@@ -0,0 +1,20 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::customers::get_customer_orders as get_orders_for_customer;
+
+#[get("/customers/{customer_id}/orders")]
+pub async fn get_customer_orders(data: web::Data<AppData>, web::Path(customer_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+    let instance_id = match qstring.get("instanceId") {
+        Some(instance_id) => instance_id.to_string(),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || get_orders_for_customer(database, instance_id, customer_id)).await;
+
+    match result {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::customers::get_customers as get_instance_customers;
+
+#[get("/customers")]
+pub async fn get_customers(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let limit = match qstring.get("limit").unwrap_or("100").parse::<u64>() {
+        Ok(limit) => limit,
+        Err(_) => return HttpResponse::BadRequest().json("Parameter 'limit' must be a positive number")
+    };
+
+    let offset = match qstring.get("offset").unwrap_or("0").parse::<u64>() {
+        Ok(offset) => offset,
+        Err(_) => return HttpResponse::BadRequest().json("Parameter 'offset' must be a positive number")
+    };
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || get_instance_customers(database, instance_id, limit, offset)).await;
+
+    match result {
+        Ok(customers) => HttpResponse::Ok().json(customers),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
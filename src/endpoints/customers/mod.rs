@@ -0,0 +1,2 @@
+pub mod get_customers;
+pub mod get_customer_orders;
\ No newline at end of file
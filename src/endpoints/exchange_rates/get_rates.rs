@@ -0,0 +1,25 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::exchange_rates::get_rates as get_stored_rates;
+
+#[get("/exchange_rates")]
+pub async fn get_rates(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    for param in &["currency", "from", "to"] {
+        if qstring.get(param).is_none() {
+            return HttpResponse::BadRequest().json(format!("Missing required parameter '{}'", param));
+        }
+    }
+
+    let database = data.database.clone();
+    let currency = qstring.get("currency").unwrap().to_string();
+    let from = qstring.get("from").unwrap().to_string();
+    let to = qstring.get("to").unwrap().to_string();
+    let result = web::block(move || get_stored_rates(database, currency, from, to)).await;
+
+    match result {
+        Ok(rates) => HttpResponse::Ok().json(rates),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
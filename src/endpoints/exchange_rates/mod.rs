@@ -0,0 +1,2 @@
+pub mod get_rates;
+pub mod post_rates;
\ No newline at end of file
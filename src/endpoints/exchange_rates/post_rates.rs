@@ -0,0 +1,36 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::exchange_rates::{parse_csv, parse_ecb_xml, store_rates, convert_orders_for_rates};
+
+#[post("/exchange_rates")]
+pub async fn post_rates(data: web::Data<AppData>, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let rates = match qstring.get("format").unwrap_or("csv") {
+        "csv" => parse_csv(&body),
+        "ecb" => match std::str::from_utf8(&body) {
+            Ok(xml) => parse_ecb_xml(xml),
+            Err(_) => Err("The file is not valid UTF-8".to_string())
+        },
+        _ => return HttpResponse::BadRequest().json("Parameter 'format' must be one of 'csv', 'ecb'")
+    };
+
+    let rates = match rates {
+        Ok(rates) => rates,
+        Err(e) => return HttpResponse::BadRequest().json(e)
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || {
+        let stored = store_rates(database.clone(), &rates)?;
+
+        //Orders which couldn't be converted before may have a rate now, others may have a closer one
+        convert_orders_for_rates(database, &rates)?;
+        Ok::<usize, String>(stored)
+    }).await;
+
+    match result {
+        Ok(stored) => HttpResponse::Ok().json(stored),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::accounting::build_journal;
+use crate::export::Locale;
+use crate::export::journal::{write_csv, build_exact_xml, DEFAULT_EXACT_JOURNAL};
+use crate::endpoints::filter::order_filter;
+
+#[get("/export/journal")]
+pub async fn get_journal(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    if filter.from.is_none() || filter.to.is_none() {
+        return HttpResponse::BadRequest().json("A period is required, use the parameters 'from' and 'to'");
+    }
+
+    let locale = match qstring.get("locale") {
+        Some(tag) => match Locale::from_tag(tag) {
+            Some(locale) => locale,
+            None => return HttpResponse::BadRequest().json(format!("Unsupported locale '{}'", tag))
+        },
+        None => Locale::DEFAULT
+    };
+
+    let format = qstring.get("format").unwrap_or("csv").to_string();
+    if format != "csv" && format != "exact" {
+        return HttpResponse::BadRequest().json("Parameter 'format' must be one of 'csv', 'exact'");
+    }
+
+    let journal = qstring.get("journal").unwrap_or(DEFAULT_EXACT_JOURNAL).to_string();
+    let database = data.database.clone();
+    let result = web::block(move || build_journal(database, &filter)).await;
+
+    let entries = match result {
+        Ok(entries) => entries,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string())
+    };
+
+    if format == "exact" {
+        return HttpResponse::Ok()
+            .content_type("application/xml")
+            .header("Content-Disposition", "attachment; filename=\"journal.xml\"")
+            .body(build_exact_xml(&entries, &journal));
+    }
+
+    match write_csv(&entries, locale) {
+        Ok(file) => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .header("Content-Disposition", "attachment; filename=\"journal.csv\"")
+            .body(file),
+        Err(e) => HttpResponse::InternalServerError().body(e)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,70 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use futures::StreamExt;
+use crate::appdata::AppData;
+use crate::export::Locale;
+use crate::export::orders::{ExportKind, select_columns, write_csv, build_xlsx};
+use crate::endpoints::filter::order_filter;
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+#[get("/export/orders")]
+pub async fn get_orders(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    let kind = match qstring.get("type").unwrap_or("orders") {
+        "orders" => ExportKind::Orders,
+        "line_items" => ExportKind::LineItems,
+        _ => return HttpResponse::BadRequest().json("Parameter 'type' must be one of 'orders', 'line_items'")
+    };
+
+    let columns = match select_columns(kind, qstring.get("columns")) {
+        Ok(columns) => columns,
+        Err(e) => return HttpResponse::BadRequest().json(e)
+    };
+
+    let locale = match qstring.get("locale") {
+        Some(tag) => match Locale::from_tag(tag) {
+            Some(locale) => locale,
+            None => return HttpResponse::BadRequest().json(format!("Unsupported locale '{}'", tag))
+        },
+        None => Locale::DEFAULT
+    };
+
+    let file_name = if kind == ExportKind::Orders { "orders" } else { "line_items" };
+    let database = data.database.clone();
+
+    match qstring.get("format").unwrap_or("csv") {
+        "csv" => {
+            //The export runs on its own thread and streams the file to the client as it is written
+            let (sender, receiver) = futures::channel::mpsc::unbounded::<web::Bytes>();
+            std::thread::spawn(move || {
+                let result = write_csv(database, &filter, kind, &columns, locale, |chunk| sender.unbounded_send(web::Bytes::from(chunk)).is_ok());
+                if result.is_err() {
+                    eprintln!("CSV export for instance {} failed: {}", filter.instance_id, result.err().unwrap());
+                }
+            });
+
+            HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .header("Content-Disposition", format!("attachment; filename=\"{}.csv\"", file_name))
+                .streaming(receiver.map(Ok::<web::Bytes, actix_web::Error>))
+        },
+        "xlsx" => {
+            let result = web::block(move || build_xlsx(database, &filter, kind, &columns, locale)).await;
+
+            match result {
+                Ok(workbook) => HttpResponse::Ok()
+                    .content_type(XLSX_CONTENT_TYPE)
+                    .header("Content-Disposition", format!("attachment; filename=\"{}.xlsx\"", file_name))
+                    .body(workbook),
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+            }
+        },
+        _ => HttpResponse::BadRequest().json("Parameter 'format' must be one of 'csv', 'xlsx'")
+    }
+}
\ No newline at end of file
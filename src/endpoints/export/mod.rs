@@ -0,0 +1,2 @@
+pub mod get_orders;
+pub mod get_journal;
\ No newline at end of file
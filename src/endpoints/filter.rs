@@ -0,0 +1,35 @@
+use actix_web::{web, HttpResponse};
+use qstring::QString;
+use crate::appdata::AppData;
+use crate::order_filter::OrderFilter;
+use crate::saved_views::{apply_view, get_view};
+
+/**
+Build the order filter of a request. When the request names a saved view in 'view', by ID or name,
+the view's parameters are used for those the request does not give itself
+
+## Params
+    **data** The application data
+    **qstring** The query parameters of the request
+
+## Returns
+    **Ok**: The filter
+    **Err**: The response to send: the view does not exist or the parameters are invalid
+*/
+pub async fn order_filter(data: &web::Data<AppData>, qstring: &QString) -> Result<OrderFilter, HttpResponse> {
+    let (instance_id, view) = match (qstring.get("instanceId"), qstring.get("view")) {
+        (Some(instance_id), Some(view)) => (instance_id.to_string(), view.to_string()),
+        _ => return OrderFilter::from_query(qstring).map_err(|e| HttpResponse::BadRequest().json(e))
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || get_view(database, instance_id, view)).await;
+
+    let view = match result {
+        Ok(Some(view)) => view,
+        Ok(None) => return Err(HttpResponse::NotFound().json("No view with this ID or name")),
+        Err(e) => return Err(HttpResponse::InternalServerError().body(e.to_string()))
+    };
+
+    OrderFilter::from_query(&apply_view(qstring, &view)).map_err(|e| HttpResponse::BadRequest().json(e))
+}
\ No newline at end of file
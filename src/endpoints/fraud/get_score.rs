@@ -0,0 +1,15 @@
+use actix_web::{get, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::fraud::get_score as get_risk_score;
+
+#[get("/orders/{order_id}/risk")]
+pub async fn get_score(data: web::Data<AppData>, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || get_risk_score(database, order_id)).await;
+
+    match result {
+        Ok(Some(score)) => HttpResponse::Ok().json(score),
+        Ok(None) => HttpResponse::NotFound().json("The order was not scored"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,4 @@
+pub mod get_settings;
+pub mod post_settings;
+pub mod get_score;
+pub mod post_score;
\ No newline at end of file
@@ -0,0 +1,29 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::fraud::{get_settings, score_order};
+
+#[post("/orders/{order_id}/risk")]
+pub async fn post_score(data: web::Data<AppData>, req: HttpRequest, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || {
+        match get_settings(database.clone(), instance_id)? {
+            Some(settings) => score_order(database, &settings, order_id).map(Some),
+            None => Ok(None)
+        }
+    }).await;
+
+    match result {
+        Ok(Some(Some(score))) => HttpResponse::Ok().json(score),
+        Ok(Some(None)) => HttpResponse::NotFound().json("No order with this ID"),
+        Ok(None) => HttpResponse::NotFound().json("Fraud scoring is not configured for this instance"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod get_export;
+pub mod post_erase;
\ No newline at end of file
@@ -0,0 +1,39 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::auth::admin::require_admin;
+use crate::gdpr::{erase_data, audit_request, RequestType};
+
+#[post("/gdpr/erase")]
+pub async fn post_erase(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    //Personal data is only handed out or erased on request of an admin
+    let admin = match require_admin(&req).await {
+        Ok(admin) => admin,
+        Err(response) => return response
+    };
+
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let email_param = qstring.get("email");
+    if email_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'email'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let email = email_param.unwrap().to_string();
+    let result = web::block(move || {
+        let result = erase_data(database.clone(), instance_id.clone(), email.clone());
+        audit_request(database, instance_id, RequestType::Erasure, admin.user_id, &email, result.as_ref().err())?;
+        result
+    }).await;
+
+    match result {
+        Ok(erasure) => HttpResponse::Ok().json(erasure),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
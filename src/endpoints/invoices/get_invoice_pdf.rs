@@ -0,0 +1,18 @@
+use actix_web::{get, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::invoices::get_invoice_pdf as get_stored_pdf;
+
+#[get("/invoices/{invoice_id}/pdf")]
+pub async fn get_invoice_pdf(data: web::Data<AppData>, web::Path(invoice_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || get_stored_pdf(database, invoice_id)).await;
+
+    match result {
+        Ok(Some(pdf)) => HttpResponse::Ok()
+            .content_type("application/pdf")
+            .header("Content-Disposition", "inline; filename=\"invoice.pdf\"")
+            .body(pdf),
+        Ok(None) => HttpResponse::NotFound().json("No invoice with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
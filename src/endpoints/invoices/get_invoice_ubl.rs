@@ -0,0 +1,18 @@
+use actix_web::{get, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::export::ubl::build_ubl;
+
+#[get("/invoices/{invoice_id}/ubl")]
+pub async fn get_invoice_ubl(data: web::Data<AppData>, web::Path(invoice_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || build_ubl(database, invoice_id)).await;
+
+    match result {
+        Ok(Some((file_name, xml))) => HttpResponse::Ok()
+            .content_type("application/xml")
+            .header("Content-Disposition", format!("attachment; filename=\"{}\"", file_name))
+            .body(xml),
+        Ok(None) => HttpResponse::NotFound().json("No invoice with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::export::ubl::build_ubl;
+use crate::invoices::get_order_invoice;
+
+#[get("/orders/{order_id}/ubl")]
+pub async fn get_order_ubl(data: web::Data<AppData>, req: HttpRequest, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || {
+        match get_order_invoice(database.clone(), instance_id, order_id)? {
+            Some(invoice) => build_ubl(database, invoice.invoice_id),
+            None => Ok(None)
+        }
+    }).await;
+
+    match result {
+        Ok(Some((file_name, xml))) => HttpResponse::Ok()
+            .content_type("application/xml")
+            .header("Content-Disposition", format!("attachment; filename=\"{}\"", file_name))
+            .body(xml),
+        Ok(None) => HttpResponse::NotFound().json("This order has not been invoiced"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
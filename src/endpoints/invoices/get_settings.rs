@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::invoices::get_settings as get_invoice_settings;
+
+#[get("/invoices/settings")]
+pub async fn get_settings(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || get_invoice_settings(database, instance_id)).await;
+
+    match result {
+        Ok(Some(settings)) => HttpResponse::Ok().json(settings),
+        Ok(None) => HttpResponse::NotFound().json("Invoicing is not set up for this instance"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::export::ubl::build_ubl_archive;
+
+#[get("/invoices/ubl")]
+pub async fn get_ubl_archive(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let from = match qstring.get("from").map(|from| from.parse::<i64>()) {
+        Some(Ok(from)) => from,
+        Some(Err(_)) => return HttpResponse::BadRequest().json("Parameter 'from' must be epoch seconds"),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'from'")
+    };
+
+    let to = match qstring.get("to").map(|to| to.parse::<i64>()) {
+        Some(Ok(to)) => to,
+        Some(Err(_)) => return HttpResponse::BadRequest().json("Parameter 'to' must be epoch seconds"),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'to'")
+    };
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || build_ubl_archive(database, instance_id, from, to)).await;
+
+    match result {
+        Ok(archive) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .header("Content-Disposition", "attachment; filename=\"invoices.zip\"")
+            .body(archive),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+pub mod post_invoice;
+pub mod post_credit_note;
+pub mod get_invoices;
+pub mod get_invoice_pdf;
+pub mod get_settings;
+pub mod post_settings;
+pub mod get_invoice_ubl;
+pub mod get_order_ubl;
+pub mod get_ubl_archive;
\ No newline at end of file
@@ -0,0 +1,29 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::invoices::issue_credit_note;
+
+#[post("/orders/{order_id}/credit_notes")]
+pub async fn post_credit_note(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let refund_id_param = qstring.get("refundId");
+    if refund_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'refundId'");
+    }
+
+    let database = data.database.clone();
+    let tera = data.tera.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let refund_id = refund_id_param.unwrap().to_string();
+    let result = web::block(move || issue_credit_note(database, &tera, instance_id, order_id, refund_id)).await;
+
+    match result {
+        Ok(credit_note) => HttpResponse::Ok().json(credit_note),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string())
+    }
+}
\ No newline at end of file
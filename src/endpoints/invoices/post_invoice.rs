@@ -0,0 +1,23 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::invoices::issue_invoice;
+
+#[post("/orders/{order_id}/invoice")]
+pub async fn post_invoice(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let tera = data.tera.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || issue_invoice(database, &tera, instance_id, order_id)).await;
+
+    match result {
+        Ok(invoice) => HttpResponse::Ok().json(invoice),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string())
+    }
+}
\ No newline at end of file
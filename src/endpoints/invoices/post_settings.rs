@@ -0,0 +1,15 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::invoices::{set_settings, InvoiceSettings};
+
+#[post("/invoices/settings")]
+pub async fn post_settings(data: web::Data<AppData>, body: web::Json<InvoiceSettings>) -> HttpResponse {
+    let database = data.database.clone();
+    let settings = body.into_inner();
+    let result = web::block(move || set_settings(database, settings)).await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
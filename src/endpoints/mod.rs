@@ -1 +1,22 @@
-pub mod wix;
\ No newline at end of file
+pub mod wix;
+pub mod orders;
+pub mod customers;
+pub mod gdpr;
+pub mod retention;
+pub mod export;
+pub mod packing_slips;
+pub mod invoices;
+pub mod accounting;
+pub mod reports;
+pub mod exchange_rates;
+pub mod shipping;
+pub mod addresses;
+pub mod pickup;
+pub mod picking;
+pub mod workflow;
+pub mod tags;
+pub mod views;
+pub mod rules;
+pub mod fraud;
+pub mod outbound_webhooks;
+pub mod filter;
\ No newline at end of file
@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::wix::order_writeback::get_conflicts as get_unresolved_conflicts;
+
+#[get("/orders/conflicts")]
+pub async fn get_conflicts(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || get_unresolved_conflicts(database, instance_id)).await;
+
+    match result {
+        Ok(conflicts) => HttpResponse::Ok().json(conflicts),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::endpoints::filter::order_filter;
+use crate::orders::list_orders;
+
+#[get("/orders")]
+pub async fn get_orders(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    let limit = match qstring.get("limit").unwrap_or("100").parse::<u64>() {
+        Ok(limit) => limit,
+        Err(_) => return HttpResponse::BadRequest().json("Parameter 'limit' must be a positive number")
+    };
+
+    let offset = match qstring.get("offset").unwrap_or("0").parse::<u64>() {
+        Ok(offset) => offset,
+        Err(_) => return HttpResponse::BadRequest().json("Parameter 'offset' must be a positive number")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || list_orders(database, &filter, limit, offset)).await;
+
+    match result {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
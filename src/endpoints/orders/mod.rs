@@ -0,0 +1,5 @@
+pub mod post_mark_paid;
+pub mod post_comment;
+pub mod get_conflicts;
+pub mod post_resolve_conflict;
+pub mod get_orders;
\ No newline at end of file
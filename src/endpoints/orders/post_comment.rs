@@ -0,0 +1,32 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::wix::order_writeback::{submit_mutation, MutationType, MutationOutcome};
+
+#[derive(Deserialize)]
+pub struct CommentRequest {
+    message: String
+}
+
+#[post("/orders/{order_id}/comments")]
+pub async fn post_comment(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, body: web::Json<CommentRequest>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+    let instance_id = match qstring.get("instanceId") {
+        Some(instance_id) => instance_id.to_string(),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'")
+    };
+
+    if body.message.trim().is_empty() {
+        return HttpResponse::BadRequest().json("Parameter 'message' may not be empty");
+    }
+
+    let database = data.database.clone();
+    let message = body.into_inner().message;
+    let result = web::block(move || submit_mutation(database, instance_id, order_id, MutationType::MerchantComment, message)).await;
+
+    match result {
+        Ok(outcome @ MutationOutcome::Conflict { .. }) => HttpResponse::Conflict().json(outcome),
+        Ok(outcome) => HttpResponse::Ok().json(outcome),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
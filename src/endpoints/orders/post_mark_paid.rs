@@ -0,0 +1,21 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::wix::order_writeback::{submit_mutation, MutationType, MutationOutcome};
+
+#[post("/orders/{order_id}/mark_paid")]
+pub async fn post_mark_paid(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+    let instance_id = match qstring.get("instanceId") {
+        Some(instance_id) => instance_id.to_string(),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || submit_mutation(database, instance_id, order_id, MutationType::MarkPaid, String::new())).await;
+
+    match result {
+        Ok(outcome @ MutationOutcome::Conflict { .. }) => HttpResponse::Conflict().json(outcome),
+        Ok(outcome) => HttpResponse::Ok().json(outcome),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
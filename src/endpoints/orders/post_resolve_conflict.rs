@@ -0,0 +1,29 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::wix::order_writeback::{resolve_conflict, MutationOutcome};
+
+#[post("/orders/conflicts/{mutation_id}/resolve")]
+pub async fn post_resolve_conflict(data: web::Data<AppData>, web::Path(mutation_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+    let instance_id = match qstring.get("instanceId") {
+        Some(instance_id) => instance_id.to_string(),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'")
+    };
+
+    //'overwrite' writes our change to Wix anyway, 'discard' drops it in favour of the edit made in Wix
+    let overwrite = match qstring.get("action") {
+        Some("overwrite") => true,
+        Some("discard") => false,
+        Some(_) => return HttpResponse::BadRequest().json("Parameter 'action' must be one of 'overwrite', 'discard'"),
+        None => return HttpResponse::BadRequest().json("Missing required parameter 'action'")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || resolve_conflict(database, instance_id, mutation_id, overwrite)).await;
+
+    match result {
+        Ok(outcome @ MutationOutcome::Conflict { .. }) => HttpResponse::Conflict().json(outcome),
+        Ok(outcome) => HttpResponse::Ok().json(outcome),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+use actix_web::{delete, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::outbound_webhooks::delete_subscription as delete_webhook_subscription;
+
+#[delete("/webhooks/subscriptions/{subscription_id}")]
+pub async fn delete_subscription(data: web::Data<AppData>, req: HttpRequest, web::Path(subscription_id): web::Path<String>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || delete_webhook_subscription(database, instance_id, subscription_id)).await;
+
+    match result {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().json("No subscription with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
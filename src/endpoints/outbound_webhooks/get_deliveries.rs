@@ -0,0 +1,28 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::outbound_webhooks::get_deliveries as get_webhook_deliveries;
+
+#[get("/webhooks/deliveries")]
+pub async fn get_deliveries(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let limit = match qstring.get("limit").unwrap_or("100").parse::<u64>() {
+        Ok(limit) => limit,
+        Err(_) => return HttpResponse::BadRequest().json("Parameter 'limit' must be a positive number")
+    };
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let subscription_id = qstring.get("subscriptionId").map(|s| s.to_string());
+    let result = web::block(move || get_webhook_deliveries(database, instance_id, subscription_id, limit)).await;
+
+    match result {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
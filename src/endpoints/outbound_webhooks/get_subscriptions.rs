@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::outbound_webhooks::get_subscriptions as get_instance_subscriptions;
+
+#[get("/webhooks/subscriptions")]
+pub async fn get_subscriptions(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || get_instance_subscriptions(database, instance_id)).await;
+
+    match result {
+        Ok(subscriptions) => HttpResponse::Ok().json(subscriptions),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+pub mod get_subscriptions;
+pub mod post_subscription;
+pub mod delete_subscription;
+pub mod post_test;
+pub mod get_deliveries;
+pub mod post_redeliver;
\ No newline at end of file
@@ -0,0 +1,30 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::outbound_webhooks::{create_subscription, OrderEvent};
+
+#[derive(Deserialize)]
+pub struct SubscriptionRequest {
+    url:    String,
+    events: Vec<OrderEvent>
+}
+
+#[post("/webhooks/subscriptions")]
+pub async fn post_subscription(data: web::Data<AppData>, req: HttpRequest, body: web::Json<SubscriptionRequest>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let body = body.into_inner();
+    let result = web::block(move || create_subscription(database, instance_id, body.url, body.events)).await;
+
+    match result {
+        Ok(subscription) => HttpResponse::Ok().json(subscription),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
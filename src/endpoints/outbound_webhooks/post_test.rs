@@ -0,0 +1,23 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::outbound_webhooks::send_ping;
+
+#[post("/webhooks/subscriptions/{subscription_id}/test")]
+pub async fn post_test(data: web::Data<AppData>, req: HttpRequest, web::Path(subscription_id): web::Path<String>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || send_ping(database, instance_id, subscription_id)).await;
+
+    match result {
+        Ok(Some(delivery)) => HttpResponse::Ok().json(delivery),
+        Ok(None) => HttpResponse::NotFound().json("No subscription with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,26 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::documents::packing_slip::render_packing_slips;
+
+#[get("/orders/{order_id}/packing_slip")]
+pub async fn get_packing_slip(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let tera = data.tera.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || render_packing_slips(database, &tera, instance_id, &[order_id])).await;
+
+    match result {
+        Ok(pdf) => HttpResponse::Ok()
+            .content_type("application/pdf")
+            .header("Content-Disposition", "inline; filename=\"packing_slip.pdf\"")
+            .body(pdf),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
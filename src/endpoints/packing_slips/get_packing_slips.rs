@@ -0,0 +1,36 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::documents::packing_slip::render_packing_slips;
+
+#[get("/packing_slips")]
+pub async fn get_packing_slips(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let order_ids_param = qstring.get("orderIds");
+    if order_ids_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'orderIds'");
+    }
+
+    let order_ids: Vec<String> = order_ids_param.unwrap().split(',').map(|id| id.trim().to_string()).filter(|id| !id.is_empty()).collect();
+    if order_ids.is_empty() {
+        return HttpResponse::BadRequest().json("Parameter 'orderIds' must contain at least one order ID");
+    }
+
+    let database = data.database.clone();
+    let tera = data.tera.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || render_packing_slips(database, &tera, instance_id, &order_ids)).await;
+
+    match result {
+        Ok(pdf) => HttpResponse::Ok()
+            .content_type("application/pdf")
+            .header("Content-Disposition", "inline; filename=\"packing_slips.pdf\"")
+            .body(pdf),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
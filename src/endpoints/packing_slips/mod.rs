@@ -0,0 +1,4 @@
+pub mod get_packing_slip;
+pub mod get_packing_slips;
+pub mod get_settings;
+pub mod post_settings;
\ No newline at end of file
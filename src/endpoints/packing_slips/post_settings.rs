@@ -0,0 +1,21 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::documents::packing_slip::{set_settings, validate_template, PackingSlipSettings};
+
+#[post("/packing_slips/settings")]
+pub async fn post_settings(data: web::Data<AppData>, body: web::Json<PackingSlipSettings>) -> HttpResponse {
+    let settings = body.into_inner();
+    if let Some(template) = &settings.template {
+        if let Err(e) = validate_template(template) {
+            return HttpResponse::BadRequest().body(e);
+        }
+    }
+
+    let database = data.database.clone();
+    let result = web::block(move || set_settings(database, settings)).await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,70 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::documents::pick_list::{build_pick_list, render_html, write_csv, PickListSort};
+use crate::export::Locale;
+use crate::endpoints::filter::order_filter;
+
+#[get("/picking/list")]
+pub async fn get_pick_list(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let mut filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    //A wave consists of the paid orders which still have to be fulfilled, unless specific orders are picked
+    if filter.order_ids.is_none() {
+        if filter.payment_status.is_none() {
+            filter.payment_status = Some("Paid".to_string());
+        }
+
+        if filter.fulfillment_status.is_none() {
+            filter.fulfillment_status = Some("NotFulfilled".to_string());
+        }
+    }
+
+    let sort = match qstring.get("sort") {
+        None | Some("bin") => PickListSort::BinLocation,
+        Some("sku") => PickListSort::Sku,
+        Some(_) => return HttpResponse::BadRequest().json("Parameter 'sort' must be one of 'bin', 'sku'")
+    };
+
+    let locale = match qstring.get("locale") {
+        Some(tag) => match Locale::from_tag(tag) {
+            Some(locale) => locale,
+            None => return HttpResponse::BadRequest().json(format!("Unsupported locale '{}'", tag))
+        },
+        None => Locale::DEFAULT
+    };
+
+    let format = qstring.get("format").unwrap_or("json").to_string();
+    if format != "json" && format != "csv" && format != "html" {
+        return HttpResponse::BadRequest().json("Parameter 'format' must be one of 'json', 'csv', 'html'");
+    }
+
+    let database = data.database.clone();
+    let result = web::block(move || build_pick_list(database, &filter, sort)).await;
+
+    let pick_list = match result {
+        Ok(pick_list) => pick_list,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string())
+    };
+
+    let file = match format.as_str() {
+        "csv" => write_csv(&pick_list, locale),
+        "html" => render_html(&data.tera, &pick_list).map(String::into_bytes),
+        _ => return HttpResponse::Ok().json(pick_list)
+    };
+
+    match (file, format.as_str()) {
+        (Ok(file), "csv") => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .header("Content-Disposition", "attachment; filename=\"pick_list.csv\"")
+            .body(file),
+        (Ok(file), _) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(file),
+        (Err(e), _) => HttpResponse::InternalServerError().body(e)
+    }
+}
\ No newline at end of file
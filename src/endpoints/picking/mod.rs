@@ -0,0 +1,3 @@
+pub mod get_pick_list;
+pub mod get_bins;
+pub mod post_bins;
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod get_queue;
+pub mod post_picked_up;
\ No newline at end of file
@@ -0,0 +1,27 @@
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::pickup::mark_picked_up;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickedUpRequest {
+    handed_over_by: String
+}
+
+#[post("/orders/{order_id}/picked_up")]
+pub async fn post_picked_up(data: web::Data<AppData>, web::Path(order_id): web::Path<String>, body: web::Json<PickedUpRequest>) -> HttpResponse {
+    if body.handed_over_by.trim().is_empty() {
+        return HttpResponse::BadRequest().json("Parameter 'handedOverBy' may not be empty");
+    }
+
+    let database = data.database.clone();
+    let handed_over_by = body.into_inner().handed_over_by.trim().to_string();
+    let result = web::block(move || mark_picked_up(database, order_id, handed_over_by)).await;
+
+    match result {
+        Ok(Some(pickup)) => HttpResponse::Ok().json(pickup),
+        Ok(None) => HttpResponse::NotFound().json("No order with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
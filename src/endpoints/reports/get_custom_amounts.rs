@@ -0,0 +1,31 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::endpoints::filter::order_filter;
+use crate::product_analytics::custom_amount_sales;
+use crate::reports::Granularity;
+
+#[get("/reports/custom_amounts")]
+pub async fn get_custom_amounts(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    let granularity = match qstring.get("granularity") {
+        None => None,
+        Some("day") => Some(Granularity::Day),
+        Some("week") => Some(Granularity::Week),
+        Some("month") => Some(Granularity::Month),
+        Some(_) => return HttpResponse::BadRequest().json("Parameter 'granularity' must be one of 'day', 'week', 'month'")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || custom_amount_sales(database, &filter, granularity)).await;
+
+    match result {
+        Ok(sales) => HttpResponse::Ok().json(sales),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
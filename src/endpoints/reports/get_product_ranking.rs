@@ -0,0 +1,36 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::endpoints::filter::order_filter;
+use crate::product_analytics::{rank_products, Ranking};
+
+const DEFAULT_LIMIT: usize = 10;
+
+#[get("/reports/products/ranking")]
+pub async fn get_product_ranking(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    let ranking = match qstring.get("ranking").unwrap_or("top") {
+        "top" => Ranking::TopSellers,
+        "slow" => Ranking::SlowMovers,
+        _ => return HttpResponse::BadRequest().json("Parameter 'ranking' must be one of 'top', 'slow'")
+    };
+
+    let limit = match qstring.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_LIMIT,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return HttpResponse::BadRequest().json("Parameter 'limit' must be a positive number")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || rank_products(database, &filter, ranking, limit)).await;
+
+    match result {
+        Ok(products) => HttpResponse::Ok().json(products),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
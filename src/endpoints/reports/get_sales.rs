@@ -0,0 +1,38 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::endpoints::filter::order_filter;
+use crate::reports::{sales_report, Granularity, GroupBy};
+
+#[get("/reports/sales")]
+pub async fn get_sales(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    let granularity = match qstring.get("granularity").unwrap_or("day") {
+        "day" => Granularity::Day,
+        "week" => Granularity::Week,
+        "month" => Granularity::Month,
+        _ => return HttpResponse::BadRequest().json("Parameter 'granularity' must be one of 'day', 'week', 'month'")
+    };
+
+    let group_by = match qstring.get("groupBy") {
+        None => None,
+        Some("currency") => Some(GroupBy::Currency),
+        Some("payment_status") => Some(GroupBy::PaymentStatus),
+        Some("channel") => Some(GroupBy::Channel),
+        Some("product") => Some(GroupBy::Product),
+        Some(_) => return HttpResponse::BadRequest().json("Parameter 'groupBy' must be one of 'currency', 'payment_status', 'channel', 'product'")
+    };
+
+    let database = data.database.clone();
+    let result = web::block(move || sales_report(database, &filter, granularity, group_by)).await;
+
+    match result {
+        Ok(rows) => HttpResponse::Ok().json(rows),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
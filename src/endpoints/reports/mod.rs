@@ -0,0 +1,6 @@
+pub mod get_sales;
+pub mod get_settings;
+pub mod post_settings;
+pub mod get_products;
+pub mod get_product_ranking;
+pub mod get_custom_amounts;
\ No newline at end of file
@@ -0,0 +1,26 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::exchange_rates::{convert_orders, Conversion};
+use crate::reports::{set_settings, ReportSettings};
+
+#[post("/reports/settings")]
+pub async fn post_settings(data: web::Data<AppData>, body: web::Json<ReportSettings>) -> HttpResponse {
+    let database = data.database.clone();
+    let settings = body.into_inner();
+    if let Err(e) = settings.validate() {
+        return HttpResponse::BadRequest().json(e);
+    }
+
+    let result = web::block(move || {
+        let instance_id = settings.instance_id.clone();
+        set_settings(database.clone(), settings)?;
+
+        //The reporting currency or timezone may have changed, which changes the rate of every order
+        convert_orders(database, instance_id, Conversion::All)
+    }).await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
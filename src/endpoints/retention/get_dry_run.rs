@@ -0,0 +1,28 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::retention::{get_policy, apply_policy};
+
+#[get("/retention/dry_run")]
+pub async fn get_dry_run(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || {
+        match get_policy(database.clone(), instance_id)? {
+            Some(policy) => apply_policy(database, &policy, true).map(Some),
+            None => Ok(None)
+        }
+    }).await;
+
+    match result {
+        Ok(Some(report)) => HttpResponse::Ok().json(report),
+        Ok(None) => HttpResponse::NotFound().json("No retention policy is configured for this instance"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
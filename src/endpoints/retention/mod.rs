@@ -0,0 +1,3 @@
+pub mod get_policy;
+pub mod post_policy;
+pub mod get_dry_run;
\ No newline at end of file
@@ -0,0 +1,21 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::retention::{set_policy, RetentionPolicy};
+
+#[post("/retention")]
+pub async fn post_policy(data: web::Data<AppData>, body: web::Json<RetentionPolicy>) -> HttpResponse {
+    let policy = body.into_inner();
+
+    let periods = [policy.anonymize_after_days, policy.delete_webhook_deliveries_after_days];
+    if periods.iter().any(|days| days.map(|days| days < 1).unwrap_or(false)) {
+        return HttpResponse::BadRequest().json("Retention periods must be at least 1 day");
+    }
+
+    let database = data.database.clone();
+    let result = web::block(move || set_policy(database, policy)).await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
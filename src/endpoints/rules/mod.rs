@@ -0,0 +1,5 @@
+pub mod get_rules;
+pub mod post_rules;
+pub mod get_log;
+pub mod post_evaluate;
+pub mod get_notifications;
\ No newline at end of file
@@ -0,0 +1,15 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::rules::{evaluate_order, Trigger};
+
+#[post("/orders/{order_id}/rules/evaluate")]
+pub async fn post_evaluate(data: web::Data<AppData>, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || evaluate_order(database, order_id, Trigger::Manual)).await;
+
+    match result {
+        Ok(Some(evaluations)) => HttpResponse::Ok().json(evaluations),
+        Ok(None) => HttpResponse::NotFound().json("No order with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+use actix_web::{get, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::shipping::get_label as get_shipment_label;
+
+#[get("/shipments/{shipment_id}/label")]
+pub async fn get_label(data: web::Data<AppData>, web::Path(shipment_id): web::Path<String>) -> HttpResponse {
+    let file_name = format!("label-{}.pdf", shipment_id);
+    let database = data.database.clone();
+    let result = web::block(move || get_shipment_label(database, shipment_id)).await;
+
+    match result {
+        Ok(Some(label)) => HttpResponse::Ok()
+            .content_type("application/pdf")
+            .header("Content-Disposition", format!("inline; filename=\"{}\"", file_name))
+            .body(label),
+        Ok(None) => HttpResponse::NotFound().json("No shipment with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
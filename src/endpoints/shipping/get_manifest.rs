@@ -0,0 +1,31 @@
+use actix_web::{get, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::endpoints::filter::order_filter;
+use crate::shipping::build_manifest;
+
+#[get("/shipping/manifest")]
+pub async fn get_manifest(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let mut filter = match order_filter(&data, &qstring).await {
+        Ok(filter) => filter,
+        Err(response) => return response
+    };
+
+    //Only orders which still have to be shipped, unless asked otherwise
+    if filter.fulfillment_status.is_none() {
+        filter.fulfillment_status = Some("NotFulfilled".to_string());
+    }
+
+    if filter.delivery_method.is_none() {
+        filter.delivery_method = Some("SHIP".to_string());
+    }
+
+    let database = data.database.clone();
+    let result = web::block(move || build_manifest(database, &filter)).await;
+
+    match result {
+        Ok(manifest) => HttpResponse::Ok().json(manifest),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
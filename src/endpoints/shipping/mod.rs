@@ -0,0 +1,5 @@
+pub mod get_manifest;
+pub mod get_settings;
+pub mod post_settings;
+pub mod post_shipment;
+pub mod get_label;
\ No newline at end of file
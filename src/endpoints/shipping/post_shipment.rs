@@ -0,0 +1,15 @@
+use actix_web::{post, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::shipping::create_shipment;
+
+#[post("/orders/{order_id}/shipments")]
+pub async fn post_shipment(data: web::Data<AppData>, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || create_shipment(database, order_id)).await;
+
+    match result {
+        Ok(Some(shipment)) => HttpResponse::Ok().json(shipment),
+        Ok(None) => HttpResponse::NotFound().json("No order with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
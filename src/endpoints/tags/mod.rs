@@ -0,0 +1,3 @@
+pub mod get_tags;
+pub mod post_add;
+pub mod post_remove;
\ No newline at end of file
@@ -0,0 +1,36 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::tags::add_tags;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddTagsRequest {
+    order_ids:  Vec<String>,
+    tags:       Vec<String>,
+    added_by:   String
+}
+
+#[post("/tags/add")]
+pub async fn post_add(data: web::Data<AppData>, req: HttpRequest, body: web::Json<AddTagsRequest>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    if body.added_by.trim().is_empty() {
+        return HttpResponse::BadRequest().json("Parameter 'addedBy' may not be empty");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let body = body.into_inner();
+    let result = web::block(move || add_tags(database, instance_id, &body.order_ids, &body.tags, body.added_by.trim())).await;
+
+    match result {
+        Ok(added) => HttpResponse::Ok().json(added),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
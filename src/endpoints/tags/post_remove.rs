@@ -0,0 +1,31 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::tags::remove_tags;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveTagsRequest {
+    order_ids:  Vec<String>,
+    tags:       Vec<String>
+}
+
+#[post("/tags/remove")]
+pub async fn post_remove(data: web::Data<AppData>, req: HttpRequest, body: web::Json<RemoveTagsRequest>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let body = body.into_inner();
+    let result = web::block(move || remove_tags(database, instance_id, &body.order_ids, &body.tags)).await;
+
+    match result {
+        Ok(removed) => HttpResponse::Ok().json(removed),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+use actix_web::{delete, web, HttpResponse, HttpRequest};
+use crate::appdata::AppData;
+use crate::saved_views::delete_view as delete_saved_view;
+
+#[delete("/views/{view_id}")]
+pub async fn delete_view(data: web::Data<AppData>, req: HttpRequest, web::Path(view_id): web::Path<String>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let result = web::block(move || delete_saved_view(database, instance_id, view_id)).await;
+
+    match result {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().json("No view with this ID"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod get_views;
+pub mod post_view;
+pub mod delete_view;
\ No newline at end of file
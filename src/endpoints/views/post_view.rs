@@ -0,0 +1,30 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::saved_views::save_view;
+
+#[derive(Deserialize)]
+pub struct ViewRequest {
+    name:   String,
+    query:  String
+}
+
+#[post("/views")]
+pub async fn post_view(data: web::Data<AppData>, req: HttpRequest, body: web::Json<ViewRequest>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let body = body.into_inner();
+    let result = web::block(move || save_view(database, instance_id, body.name, body.query)).await;
+
+    match result {
+        Ok(view) => HttpResponse::Ok().json(view),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
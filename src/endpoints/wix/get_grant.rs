@@ -4,6 +4,7 @@ use mysql::prelude::Queryable;
 use mysql::{Row, Params, params};
 use serde::{Serialize, Deserialize};
 use crate::environment::get_environment;
+use crate::retention::STATE_MAX_AGE_SECONDS;
 use tera::Context;
 
 const WIX_TOKEN_URI: &str = "https://www.wix.com/oauth/access";
@@ -42,10 +43,11 @@ pub async fn get_grant(data: web::Data<AppData>, req: HttpRequest) -> HttpRespon
         return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
     }
 
-    //Verify the 'state' param
+    //Verify the 'state' param, states older than STATE_MAX_AGE_SECONDS have expired even if the retention job hasn't deleted them yet
     let mut conn = data.database.pool.get_conn().unwrap();
-    let sql_result = conn.exec::<Row, &str, Params>("SELECT state FROM states WHERE state = :state", params! {
-        "state" => state_param.unwrap().clone()
+    let sql_result = conn.exec::<Row, &str, Params>("SELECT state FROM states WHERE state = :state AND created_at >= :cutoff", params! {
+        "state" => state_param.unwrap().clone(),
+        "cutoff" => chrono::Utc::now().timestamp() - STATE_MAX_AGE_SECONDS
     });
 
     if sql_result.is_err() {
@@ -23,8 +23,9 @@ pub async fn get_install(data: web::Data<AppData>, req: HttpRequest) -> HttpResp
 
     //Insert the state into the database
     let mut conn = data.database.pool.get_conn().unwrap();
-    conn.exec::<usize, &str, Params>("INSERT INTO states (state) VALUES (:state)", params!{
-        "state" => state_gen.clone()
+    conn.exec::<usize, &str, Params>("INSERT INTO states (state, created_at) VALUES (:state, :created_at)", params!{
+        "state" => state_gen.clone(),
+        "created_at" => chrono::Utc::now().timestamp()
     }).expect("Database error");
 
     //Build the redirect uri
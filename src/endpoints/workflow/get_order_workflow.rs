@@ -0,0 +1,14 @@
+use actix_web::{get, web, HttpResponse};
+use crate::appdata::AppData;
+use crate::workflow::get_order_workflow as get_workflow;
+
+#[get("/orders/{order_id}/workflow")]
+pub async fn get_order_workflow(data: web::Data<AppData>, web::Path(order_id): web::Path<String>) -> HttpResponse {
+    let database = data.database.clone();
+    let result = web::block(move || get_workflow(database, order_id)).await;
+
+    match result {
+        Ok(workflow) => HttpResponse::Ok().json(workflow),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,5 @@
+pub mod get_states;
+pub mod post_states;
+pub mod post_move;
+pub mod post_assign;
+pub mod get_order_workflow;
\ No newline at end of file
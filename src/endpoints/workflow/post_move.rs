@@ -0,0 +1,36 @@
+use actix_web::{post, web, HttpResponse, HttpRequest};
+use serde::Deserialize;
+use crate::appdata::AppData;
+use crate::workflow::move_orders;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveRequest {
+    order_ids:  Vec<String>,
+    state:      String,
+    changed_by: String
+}
+
+#[post("/workflow/move")]
+pub async fn post_move(data: web::Data<AppData>, req: HttpRequest, body: web::Json<MoveRequest>) -> HttpResponse {
+    let qstring = qstring::QString::from(req.query_string());
+
+    let instance_id_param = qstring.get("instanceId");
+    if instance_id_param.is_none() {
+        return HttpResponse::BadRequest().json("Missing required parameter 'instanceId'");
+    }
+
+    if body.changed_by.trim().is_empty() {
+        return HttpResponse::BadRequest().json("Parameter 'changedBy' may not be empty");
+    }
+
+    let database = data.database.clone();
+    let instance_id = instance_id_param.unwrap().to_string();
+    let body = body.into_inner();
+    let result = web::block(move || move_orders(database, instance_id, body.order_ids, body.state, body.changed_by)).await;
+
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string())
+    }
+}
\ No newline at end of file
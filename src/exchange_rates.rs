@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{NaiveDate, TimeZone};
+use serde::Serialize;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+use crate::reports::get_settings;
+
+/// Exchange rates are stored against this currency, as published by the ECB
+pub const BASE_CURRENCY: &str = "EUR";
+
+/// Rates older than this many days before an order are not used to convert it, the order is left unconverted instead
+pub const MAX_RATE_AGE_DAYS: i64 = 7;
+
+/// Which orders of an instance to (re)convert
+pub enum Conversion {
+    /// Every order, e.g. after the reporting currency or timezone changed
+    All,
+    /// Orders which have not been converted yet, e.g. after an import
+    Missing,
+    /// Orders which have not been converted yet, or were placed at or after a unix timestamp, e.g. after rates were loaded
+    Since(i64)
+}
+
+/// An exchange rate on a date: one unit of the base currency buys `rate` units of `currency`
+#[derive(Serialize)]
+pub struct ExchangeRate {
+    /// Date the rate applies to, e.g. '2021-03-01'
+    pub date:       String,
+    pub currency:   String,
+    pub rate:       f64
+}
+
+/// Rates by currency, then by date
+type RateTable = HashMap<String, BTreeMap<String, f64>>;
+
+/**
+Parse exchange rates from a CSV file with the columns 'date', 'currency' and 'rate'.
+Rates are against the base currency, dates formatted as YYYY-MM-DD.
+
+## Params
+    **data** The file
+
+## Returns
+    **Ok**: The rates
+    **Err**: A message describing the first invalid line
+*/
+pub fn parse_csv(data: &[u8]) -> Result<Vec<ExchangeRate>, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data);
+    let mut rates = Vec::new();
+
+    for (i, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => return Err(e.to_string())
+        };
+
+        //Line 1 is the header
+        let line = i + 2;
+        if record.len() < 3 {
+            return Err(format!("Line {} must have the columns 'date', 'currency' and 'rate'", line));
+        }
+
+        rates.push(parse_rate(&record[0], &record[1], &record[2]).map_err(|e| format!("Line {}: {}", line, e))?);
+    }
+
+    Ok(rates)
+}
+
+/**
+Parse exchange rates from an ECB euro foreign exchange reference rates file, daily or historic (eurofxref-hist.xml)
+
+## Params
+    **data** The file
+
+## Returns
+    **Ok**: The rates
+    **Err**: A message describing the invalid element
+*/
+pub fn parse_ecb_xml(data: &str) -> Result<Vec<ExchangeRate>, String> {
+    let mut rates = Vec::new();
+    let mut date: Option<String> = None;
+
+    //The rates are in nested Cube elements: <Cube time="2021-03-01"><Cube currency="USD" rate="1.2071"/>...</Cube>
+    for element in data.split('<').skip(1).filter(|element| element.starts_with("Cube")) {
+        let tag = match element.find('>') {
+            Some(end) => &element[..end],
+            None => return Err("Unterminated Cube element".to_string())
+        };
+
+        if let Some(time) = attribute(tag, "time") {
+            date = Some(time.to_string());
+        }
+
+        if let (Some(currency), Some(rate)) = (attribute(tag, "currency"), attribute(tag, "rate")) {
+            let date = match &date {
+                Some(date) => date,
+                None => return Err(format!("Rate for {} is not inside a dated Cube element", currency))
+            };
+
+            rates.push(parse_rate(date, currency, rate)?);
+        }
+    }
+
+    if rates.is_empty() {
+        return Err("The file does not contain any rates".to_string());
+    }
+
+    Ok(rates)
+}
+
+/**
+Store exchange rates, replacing existing rates for the same currency and date
+
+## Params
+    **database** Instance of a Database object
+    **rates** The rates to store
+
+## Returns
+    **Ok**: The number of rates stored
+    **Err**: A summary of what went wrong
+*/
+pub fn store_rates(database: Database, rates: &[ExchangeRate]) -> Result<usize, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    for rate in rates {
+        let result = tx.exec_drop("INSERT INTO exchange_rates (currency, rate_date, rate) VALUES (:currency, :rate_date, :rate) ON DUPLICATE KEY UPDATE rate = :rate", params! {
+            "currency" => rate.currency.clone(),
+            "rate_date" => rate.date.clone(),
+            "rate" => rate.rate
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(rates.len()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the stored exchange rates of a currency
+
+## Params
+    **database** Instance of a Database object
+    **currency** The currency
+    **from** First date, e.g. '2021-03-01'
+    **to** Last date
+
+## Returns
+    **Ok**: The rates, by date
+    **Err**: A summary of what went wrong
+*/
+pub fn get_rates(database: Database, currency: String, from: String, to: String) -> Result<Vec<ExchangeRate>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT currency, rate_date, rate FROM exchange_rates WHERE currency = :currency AND rate_date >= :from AND rate_date <= :to ORDER BY rate_date ASC", params! {
+        "currency" => currency,
+        "from" => from,
+        "to" => to
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| ExchangeRate {
+            date:       row.get("rate_date").unwrap(),
+            currency:   row.get("currency").unwrap(),
+            rate:       row.get("rate").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Convert the totals of an instance's orders to its reporting currency.
+Each order is converted at the most recent rate on or before its order date, in the instance's timezone, at most `MAX_RATE_AGE_DAYS` old.
+The rate and the date of the rate are stored on the order, the original amounts are left as they are. Orders for which no rate is known are left unconverted.
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to convert the orders of
+    **conversion** Which orders to convert
+
+## Returns
+    **Ok**: The number of orders of which the rate changed
+    **Err**: A summary of what went wrong
+*/
+pub fn convert_orders(database: Database, instance_id: String, conversion: Conversion) -> Result<usize, String> {
+    let settings = get_settings(database.clone(), instance_id.clone())?;
+    let timezone = settings.parse_timezone()?;
+    let rates = load_rates(database.clone())?;
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let query = "SELECT order_id, order_date, currency, reporting_currency, exchange_rate, exchange_rate_date FROM orders WHERE instance_id = :instance_id";
+    let result = match conversion {
+        Conversion::All => conn.exec::<Row, &str, Params>(query, params! {
+            "instance_id" => instance_id
+        }),
+        Conversion::Missing => conn.exec::<Row, String, Params>(format!("{} AND exchange_rate IS NULL", query), params! {
+            "instance_id" => instance_id
+        }),
+        Conversion::Since(since) => conn.exec::<Row, String, Params>(format!("{} AND (exchange_rate IS NULL OR order_date >= :since)", query), params! {
+            "instance_id" => instance_id,
+            "since" => since
+        })
+    };
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut converted = 0;
+    for row in result.unwrap() {
+        let order_id: String = row.get("order_id").unwrap();
+        let order_date: i64 = row.get("order_date").unwrap();
+        let currency: String = row.get("currency").unwrap();
+
+        let date = timezone.timestamp_opt(order_date, 0).unwrap().format("%Y-%m-%d").to_string();
+        let (exchange_rate_date, exchange_rate) = match conversion_rate(&rates, &currency, &settings.reporting_currency, &date) {
+            Some((rate_date, rate)) => (Some(rate_date), Some(rate)),
+            None => (None, None)
+        };
+
+        let reporting_currency = exchange_rate.map(|_| settings.reporting_currency.clone());
+        let unchanged = row.get::<Option<String>, &str>("reporting_currency").unwrap() == reporting_currency
+            && row.get::<Option<String>, &str>("exchange_rate_date").unwrap() == exchange_rate_date
+            && row.get::<Option<f64>, &str>("exchange_rate").unwrap() == exchange_rate;
+
+        if unchanged {
+            continue;
+        }
+
+        let result = conn.exec_drop("UPDATE orders SET reporting_currency = :reporting_currency, exchange_rate = :exchange_rate, exchange_rate_date = :exchange_rate_date WHERE order_id = :order_id", params! {
+            "reporting_currency" => reporting_currency,
+            "exchange_rate" => exchange_rate,
+            "exchange_rate_date" => exchange_rate_date,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        converted += 1;
+    }
+
+    Ok(converted)
+}
+
+/**
+Convert the orders of all instances which new rates may apply to: orders which have not been converted yet,
+and orders placed on or after the earliest new rate, which may have been converted at an older rate
+
+## Params
+    **database** Instance of a Database object
+    **rates** The rates which were loaded
+
+## Returns
+    **Ok**: The number of orders of which the rate changed
+    **Err**: A summary of what went wrong
+*/
+pub fn convert_orders_for_rates(database: Database, rates: &[ExchangeRate]) -> Result<usize, String> {
+    //Dates are in each instance's timezone, start a day early to cover all of them
+    let since = match rates.iter().filter_map(|rate| NaiveDate::parse_from_str(&rate.date, "%Y-%m-%d").ok()).min() {
+        Some(date) => chrono::Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).timestamp() - 24 * 60 * 60,
+        None => return Ok(0)
+    };
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<String, &str, Params>("SELECT DISTINCT instance_id FROM orders WHERE exchange_rate IS NULL OR order_date >= :since", params! {
+        "since" => since
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut converted = 0;
+    for instance_id in result.unwrap() {
+        converted += convert_orders(database.clone(), instance_id, Conversion::Since(since))?;
+    }
+
+    Ok(converted)
+}
+
+fn load_rates(database: Database) -> Result<RateTable, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.query::<Row, &str>("SELECT currency, rate_date, rate FROM exchange_rates");
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut rates: RateTable = HashMap::new();
+    for row in result.unwrap() {
+        rates.entry(row.get("currency").unwrap()).or_default().insert(row.get("rate_date").unwrap(), row.get("rate").unwrap());
+    }
+
+    Ok(rates)
+}
+
+/// Get the factor converting an amount in `from` to `to` on a date, crossing via the base currency, with the date of the oldest rate used
+fn conversion_rate(rates: &RateTable, from: &str, to: &str, date: &str) -> Option<(String, f64)> {
+    if from == to {
+        return Some((date.to_string(), 1.0));
+    }
+
+    let (to_date, to_rate) = rate_on(rates, to, date)?;
+    let (from_date, from_rate) = rate_on(rates, from, date)?;
+    Some((to_date.min(from_date), to_rate / from_rate))
+}
+
+/// The most recent rate on or before a date, with its date. No rates are published on weekends and holidays
+fn rate_on(rates: &RateTable, currency: &str, date: &str) -> Option<(String, f64)> {
+    if currency == BASE_CURRENCY {
+        return Some((date.to_string(), 1.0));
+    }
+
+    let (rate_date, rate) = rates.get(currency)?.range(..=date.to_string()).next_back()?;
+    let age = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()? - NaiveDate::parse_from_str(rate_date, "%Y-%m-%d").ok()?;
+    if age.num_days() > MAX_RATE_AGE_DAYS {
+        return None;
+    }
+
+    Some((rate_date.clone(), *rate))
+}
+
+fn parse_rate(date: &str, currency: &str, rate: &str) -> Result<ExchangeRate, String> {
+    if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+        return Err(format!("Invalid date '{}', expected YYYY-MM-DD", date));
+    }
+
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(format!("Invalid currency code '{}'", currency));
+    }
+
+    match rate.parse::<f64>() {
+        Ok(rate) if rate > 0.0 => Ok(ExchangeRate { date: date.to_string(), currency: currency.to_string(), rate }),
+        _ => Err(format!("Invalid rate '{}' for {}", rate, currency))
+    }
+}
+
+/// Get the value of an attribute in the contents of an XML tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=", name))? + name.len() + 2;
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+    value.find(quote).map(|end| &value[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> RateTable {
+        let mut rates: RateTable = HashMap::new();
+        rates.entry("USD".to_string()).or_default().insert("2021-03-01".to_string(), 1.2);
+        rates.entry("GBP".to_string()).or_default().insert("2021-03-03".to_string(), 0.8);
+        rates
+    }
+
+    #[test]
+    fn converts_at_the_most_recent_rate() {
+        assert_eq!(conversion_rate(&rates(), "EUR", "USD", "2021-03-02"), Some(("2021-03-01".to_string(), 1.2)));
+        assert_eq!(conversion_rate(&rates(), "USD", "EUR", "2021-03-01"), Some(("2021-03-01".to_string(), 1.0 / 1.2)));
+        assert_eq!(conversion_rate(&rates(), "USD", "USD", "2021-02-01"), Some(("2021-02-01".to_string(), 1.0)));
+        assert_eq!(conversion_rate(&rates(), "EUR", "USD", "2021-02-28"), None);
+    }
+
+    #[test]
+    fn crossing_rates_use_the_oldest_date() {
+        assert_eq!(conversion_rate(&rates(), "USD", "GBP", "2021-03-04"), Some(("2021-03-01".to_string(), 0.8 / 1.2)));
+    }
+
+    #[test]
+    fn stale_rates_are_not_used() {
+        assert!(conversion_rate(&rates(), "EUR", "USD", "2021-03-08").is_some());
+        assert_eq!(conversion_rate(&rates(), "EUR", "USD", "2021-03-09"), None);
+        assert_eq!(conversion_rate(&rates(), "GBP", "USD", "2021-03-09"), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+use chrono::{Datelike, TimeZone};
+
+use crate::accounting::JournalEntry;
+use crate::export::Locale;
+
+/// Journal code used in Exact Online when none is given, the default memorial journal
+pub const DEFAULT_EXACT_JOURNAL: &str = "70";
+
+/**
+Write journal entries as a generic CSV file, one row per posting
+
+## Params
+    **entries** The journal entries, see accounting::build_journal
+    **locale** Number and date formatting
+
+## Returns
+    **Ok**: The file
+    **Err**: A summary of what went wrong
+*/
+pub fn write_csv(entries: &[JournalEntry], locale: Locale) -> Result<Vec<u8>, String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(locale.csv_delimiter).from_writer(Vec::new());
+    if let Err(e) = writer.write_record(&["Entry", "Date", "Order number", "Description", "Account", "Line description", "Currency", "Debit", "Credit"]) {
+        return Err(e.to_string());
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        for line in &entry.lines {
+            let record = [
+                (i + 1).to_string(),
+                locale.format_date(entry.date),
+                entry.order_number.to_string(),
+                entry.description.clone(),
+                line.account.clone(),
+                line.description.clone(),
+                entry.currency.clone(),
+                locale.format_decimal(line.debit),
+                locale.format_decimal(line.credit)
+            ];
+
+            if let Err(e) = writer.write_record(&record) {
+                return Err(e.to_string());
+            }
+        }
+    }
+
+    match writer.into_inner() {
+        Ok(file) => Ok(file),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Write journal entries as an Exact Online XML import file (eExact GLTransactions).
+Each entry becomes a general journal transaction, amounts are positive for debit and negative for credit.
+
+## Params
+    **entries** The journal entries, see accounting::build_journal
+    **journal** The Exact Online journal code to book on
+
+## Returns
+    The XML document
+*/
+pub fn build_exact_xml(entries: &[JournalEntry], journal: &str) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<eExact xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:noNamespaceSchemaLocation=\"eExact-Schema.xsd\">\n");
+    xml.push_str("<GLTransactions>\n");
+
+    for entry in entries {
+        let date = chrono::Utc.timestamp_opt(entry.date, 0).unwrap();
+
+        xml.push_str("<GLTransaction>\n");
+        xml.push_str(&format!("<Journal code=\"{}\" type=\"M\"/>\n", escape(journal)));
+
+        for (i, line) in entry.lines.iter().enumerate() {
+            xml.push_str(&format!("<GLTransactionLine type=\"40\" line=\"{}\">\n", i + 1));
+            xml.push_str(&format!("<Date>{}</Date>\n", date.format("%Y-%m-%d")));
+            xml.push_str(&format!("<FinYear number=\"{}\"/>\n", date.year()));
+            xml.push_str(&format!("<FinPeriod number=\"{}\"/>\n", date.month()));
+            xml.push_str(&format!("<GLAccount code=\"{}\"/>\n", escape(&line.account)));
+            xml.push_str(&format!("<Description>{}: {}</Description>\n", escape(&entry.description), escape(&line.description)));
+            xml.push_str(&format!("<YourRef>{}</YourRef>\n", entry.order_number));
+            xml.push_str(&format!("<Amount><Currency code=\"{}\"/><Value>{:.2}</Value></Amount>\n", escape(&entry.currency), line.debit - line.credit));
+            xml.push_str("</GLTransactionLine>\n");
+        }
+
+        xml.push_str("</GLTransaction>\n");
+    }
+
+    xml.push_str("</GLTransactions>\n");
+    xml.push_str("</eExact>\n");
+    xml
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+use chrono::TimeZone;
+
+pub mod orders;
+pub mod ubl;
+pub mod journal;
+
+/// Number and date formatting conventions for exported files
+#[derive(Clone, Copy)]
+pub struct Locale {
+    pub decimal_separator:  char,
+
+    /// chrono format string for dates
+    pub date_format:        &'static str,
+
+    /// Field delimiter for CSV. Spreadsheet software expects ';' in locales using a decimal comma
+    pub csv_delimiter:      u8
+}
+
+impl Locale {
+    /// ISO 8601 dates and a decimal point
+    pub const DEFAULT: Locale = Locale { decimal_separator: '.', date_format: "%Y-%m-%d", csv_delimiter: b',' };
+
+    /**
+    Get the formatting conventions for a language tag
+
+    ## Params
+        **tag** A language tag, e.g. 'nl-NL'
+
+    ## Returns
+        The locale, or None if the tag is not supported
+    */
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        match tag {
+            "iso" => Some(Locale::DEFAULT),
+            "en-US" => Some(Locale { decimal_separator: '.', date_format: "%m/%d/%Y", csv_delimiter: b',' }),
+            "en-GB" => Some(Locale { decimal_separator: '.', date_format: "%d/%m/%Y", csv_delimiter: b',' }),
+            "nl-NL" | "nl-BE" => Some(Locale { decimal_separator: ',', date_format: "%d-%m-%Y", csv_delimiter: b';' }),
+            "de-DE" => Some(Locale { decimal_separator: ',', date_format: "%d.%m.%Y", csv_delimiter: b';' }),
+            "fr-FR" | "fr-BE" => Some(Locale { decimal_separator: ',', date_format: "%d/%m/%Y", csv_delimiter: b';' }),
+            _ => None
+        }
+    }
+
+    /// Format an amount with two decimals, either an f64 or an exact Decimal
+    pub fn format_decimal<T: std::fmt::Display>(&self, value: T) -> String {
+        let formatted = format!("{:.2}", value);
+        if self.decimal_separator == '.' {
+            formatted
+        } else {
+            formatted.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    /// Format an epoch timestamp as a date, in UTC
+    pub fn format_date(&self, epoch: i64) -> String {
+        chrono::Utc.timestamp_opt(epoch, 0).unwrap().format(self.date_format).to_string()
+    }
+}
\ No newline at end of file
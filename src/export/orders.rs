@@ -0,0 +1,288 @@
+use mysql::{Params, Row, Value};
+use mysql::prelude::Queryable;
+use simple_excel_writer::{Workbook, Row as SheetRow};
+
+use crate::database::Database;
+use crate::export::Locale;
+use crate::order_filter::OrderFilter;
+
+/// Number of rows written to the CSV buffer before it is handed to the sink
+const CSV_CHUNK_ROWS: usize = 500;
+
+/// Shape of the exported rows
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportKind {
+    /// One row per order
+    Orders,
+
+    /// One row per line item, with the order columns repeated
+    LineItems
+}
+
+#[derive(Clone, Copy)]
+enum ColumnKind {
+    Text,
+    Integer,
+    Decimal,
+    Date
+}
+
+/// A column which can be included in an export
+pub struct ExportColumn {
+    /// Name used to select the column in the 'columns' parameter
+    pub name:   &'static str,
+    header:     &'static str,
+    sql:        &'static str,
+    kind:       ColumnKind,
+
+    /// Only available when exporting line items
+    line_item:  bool
+}
+
+const fn column(name: &'static str, header: &'static str, sql: &'static str, kind: ColumnKind, line_item: bool) -> ExportColumn {
+    ExportColumn { name, header, sql, kind, line_item }
+}
+
+/// All exportable columns, in their default order
+pub const COLUMNS: &[ExportColumn] = &[
+    column("order_number",          "Order number",         "o.wix_order_id",       ColumnKind::Integer,    false),
+    column("order_date",            "Order date",           "o.order_date",         ColumnKind::Date,       false),
+    column("payment_status",        "Payment status",       "o.payment_status",     ColumnKind::Text,       false),
+    column("fulfillment_status",    "Fulfillment status",   "o.fulfillment_status", ColumnKind::Text,       false),
+    column("delivery_method",       "Delivery method",      "o.delivery_method",    ColumnKind::Text,       false),
+    column("tags",                  "Tags",                 "(SELECT GROUP_CONCAT(t.tag ORDER BY t.tag SEPARATOR ', ') FROM order_tags t WHERE t.order_id = o.order_id)",            ColumnKind::Text,   false),
+    column("buyer_name",            "Buyer name",           "o.buyer_name",         ColumnKind::Text,       false),
+    column("buyer_email",           "Buyer email",          "o.buyer_email",        ColumnKind::Text,       false),
+    column("buyer_phone",           "Buyer phone",          "o.buyer_phone",        ColumnKind::Text,       false),
+    column("billing_name",          "Billing name",         "(SELECT a.full_name FROM addresses a WHERE a.address_id = o.billing_address_id)",        ColumnKind::Text,   false),
+    column("billing_company",       "Billing company",      "(SELECT a.company FROM addresses a WHERE a.address_id = o.billing_address_id)",          ColumnKind::Text,   false),
+    column("shipping_name",         "Shipping name",        "(SELECT a.full_name FROM addresses a WHERE a.address_id = o.shipping_address_id)",       ColumnKind::Text,   false),
+    column("shipping_company",      "Shipping company",     "(SELECT a.company FROM addresses a WHERE a.address_id = o.shipping_address_id)",         ColumnKind::Text,   false),
+    column("shipping_email",        "Shipping email",       "(SELECT a.email FROM addresses a WHERE a.address_id = o.shipping_address_id)",           ColumnKind::Text,   false),
+    column("shipping_phone",        "Shipping phone",       "(SELECT a.phone FROM addresses a WHERE a.address_id = o.shipping_address_id)",           ColumnKind::Text,   false),
+    column("shipping_address",      "Shipping address",     "(SELECT a.address_line_1 FROM addresses a WHERE a.address_id = o.shipping_address_id)",  ColumnKind::Text,   false),
+    column("shipping_zip_code",     "Shipping postal code", "(SELECT a.zip_code FROM addresses a WHERE a.address_id = o.shipping_address_id)",        ColumnKind::Text,   false),
+    column("shipping_city",         "Shipping city",        "(SELECT a.city FROM addresses a WHERE a.address_id = o.shipping_address_id)",            ColumnKind::Text,   false),
+    column("shipping_country",      "Shipping country",     "(SELECT a.country FROM addresses a WHERE a.address_id = o.shipping_address_id)",         ColumnKind::Text,   false),
+    column("currency",              "Currency",             "o.currency",           ColumnKind::Text,       false),
+    column("quantity",              "Quantity",             "o.quantity",           ColumnKind::Integer,    false),
+    column("subtotal",              "Subtotal",             "o.subtotal",           ColumnKind::Decimal,    false),
+    column("tax",                   "Tax",                  "o.tax",                ColumnKind::Decimal,    false),
+    column("shipping",              "Shipping",             "o.shipping",           ColumnKind::Decimal,    false),
+    column("discount",              "Discount",             "o.discount",           ColumnKind::Decimal,    false),
+    column("total",                 "Total",                "o.total_price",        ColumnKind::Decimal,    false),
+    column("weight",                "Weight",               "o.weight",             ColumnKind::Decimal,    false),
+    column("weight_unit",           "Weight unit",          "o.weight_unit",        ColumnKind::Text,       false),
+    column("weight_kg",             "Weight (kg)",          "o.weight_kg",          ColumnKind::Decimal,    false),
+    column("item_name",             "Item name",            "i.name",               ColumnKind::Text,       true),
+    column("item_type",             "Item type",            "i.item_type",          ColumnKind::Text,       true),
+    column("item_sku",              "Item SKU",             "i.sku",                ColumnKind::Text,       true),
+    column("item_quantity",         "Item quantity",        "i.quantity",           ColumnKind::Integer,    true),
+    column("item_weight_kg",        "Item weight (kg)",     "i.weight_kg",          ColumnKind::Decimal,    true),
+    column("item_price",            "Item price",           "i.price",              ColumnKind::Decimal,    true),
+    column("item_total",            "Item total",           "i.total",              ColumnKind::Decimal,    true),
+];
+
+/// A value in an exported row
+enum Cell {
+    Text(String),
+    Integer(i64),
+    Decimal(f64)
+}
+
+/**
+Resolve the columns to export
+
+## Params
+    **kind** The kind of export
+    **names** Comma separated column names, or None for all columns available for this kind of export
+
+## Returns
+    **Ok**: The columns, in the requested order
+    **Err**: A message naming the unknown column
+*/
+pub fn select_columns(kind: ExportKind, names: Option<&str>) -> Result<Vec<&'static ExportColumn>, String> {
+    let names = match names {
+        Some(names) => names,
+        None => return Ok(COLUMNS.iter().filter(|c| kind == ExportKind::LineItems || !c.line_item).collect())
+    };
+
+    let mut columns = Vec::new();
+    for name in names.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        match COLUMNS.iter().find(|c| c.name == name) {
+            Some(c) if c.line_item && kind == ExportKind::Orders => return Err(format!("Column '{}' is only available when exporting line items", name)),
+            Some(c) => columns.push(c),
+            None => return Err(format!("Unknown column '{}'", name))
+        }
+    }
+
+    if columns.is_empty() {
+        return Err("At least one column must be selected".to_string());
+    }
+
+    Ok(columns)
+}
+
+/**
+Export orders as CSV.
+The file is handed to `sink` in chunks as it is produced, so large exports don't have to be held in memory.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to export
+    **kind** The kind of export
+    **columns** The columns to export, see select_columns
+    **locale** Number and date formatting
+    **sink** Receives the chunks of the file. Returns false if the receiver went away, which stops the export
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn write_csv<F: FnMut(Vec<u8>) -> bool>(database: Database, filter: &OrderFilter, kind: ExportKind, columns: &[&ExportColumn], locale: Locale, mut sink: F) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let (query, params) = build_query(filter, kind, columns);
+
+    let result = conn.exec_iter(query, params);
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut writer = new_csv_writer(locale);
+    if let Err(e) = writer.write_record(columns.iter().map(|c| c.header)) {
+        return Err(e.to_string());
+    }
+
+    let mut rows_in_chunk = 0;
+    for row in result.unwrap() {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => return Err(e.to_string())
+        };
+
+        let record = format_row(&row, columns, locale).into_iter().map(|cell| match cell {
+            Cell::Text(text) => text,
+            Cell::Integer(number) => number.to_string(),
+            Cell::Decimal(number) => locale.format_decimal(number)
+        });
+
+        if let Err(e) = writer.write_record(record) {
+            return Err(e.to_string());
+        }
+
+        rows_in_chunk += 1;
+        if rows_in_chunk == CSV_CHUNK_ROWS {
+            let chunk = match writer.into_inner() {
+                Ok(chunk) => chunk,
+                Err(e) => return Err(e.to_string())
+            };
+
+            if !sink(chunk) {
+                return Ok(());
+            }
+
+            writer = new_csv_writer(locale);
+            rows_in_chunk = 0;
+        }
+    }
+
+    match writer.into_inner() {
+        Ok(chunk) => {
+            sink(chunk);
+            Ok(())
+        },
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Export orders as an XLSX workbook.
+Amounts are written as numbers so they can be calculated with, dates are written as text in the locale's format.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to export
+    **kind** The kind of export
+    **columns** The columns to export, see select_columns
+    **locale** Date formatting
+
+## Returns
+    **Ok**: The workbook
+    **Err**: A summary of what went wrong
+*/
+pub fn build_xlsx(database: Database, filter: &OrderFilter, kind: ExportKind, columns: &[&ExportColumn], locale: Locale) -> Result<Vec<u8>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let (query, params) = build_query(filter, kind, columns);
+
+    let result = conn.exec::<Row, String, Params>(query, params);
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let rows = result.unwrap();
+    let mut workbook = Workbook::create_in_memory();
+    let mut sheet = workbook.create_sheet(if kind == ExportKind::Orders { "Orders" } else { "Line items" });
+
+    let written = workbook.write_sheet(&mut sheet, |sheet_writer| {
+        let mut header = SheetRow::new();
+        for c in columns {
+            header.add_cell(c.header);
+        }
+        sheet_writer.append_row(header)?;
+
+        for row in &rows {
+            let mut sheet_row = SheetRow::new();
+            for cell in format_row(row, columns, locale) {
+                match cell {
+                    Cell::Text(text) => sheet_row.add_cell(text),
+                    Cell::Integer(number) => sheet_row.add_cell(number as f64),
+                    Cell::Decimal(number) => sheet_row.add_cell(number)
+                }
+            }
+            sheet_writer.append_row(sheet_row)?;
+        }
+
+        Ok(())
+    });
+
+    if let Err(e) = written {
+        return Err(e.to_string());
+    }
+
+    match workbook.close() {
+        Ok(Some(bytes)) => Ok(bytes),
+        Ok(None) => Err("The workbook was not created in memory".to_string()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn build_query(filter: &OrderFilter, kind: ExportKind, columns: &[&ExportColumn]) -> (String, Params) {
+    let (condition, params) = filter.to_sql();
+    let selected: Vec<String> = columns.iter().enumerate().map(|(i, c)| format!("{} AS c{}", c.sql, i)).collect();
+
+    let query = match kind {
+        ExportKind::Orders => format!("SELECT {} FROM orders o WHERE {} ORDER BY o.order_date ASC", selected.join(", "), condition),
+        ExportKind::LineItems => format!("SELECT {} FROM orders o INNER JOIN order_items i ON i.order_id = o.order_id WHERE {} ORDER BY o.order_date ASC, i.order_item_id ASC", selected.join(", "), condition)
+    };
+
+    (query, Params::from(params))
+}
+
+fn format_row(row: &Row, columns: &[&ExportColumn], locale: Locale) -> Vec<Cell> {
+    columns.iter().enumerate().map(|(i, c)| {
+        if let Some(Value::NULL) = row.as_ref(i) {
+            return Cell::Text(String::new());
+        }
+
+        match c.kind {
+            ColumnKind::Text => Cell::Text(row.get::<String, usize>(i).unwrap_or_default()),
+            ColumnKind::Integer => Cell::Integer(row.get::<i64, usize>(i).unwrap_or_default()),
+            ColumnKind::Decimal => Cell::Decimal(row.get::<f64, usize>(i).unwrap_or_default()),
+            ColumnKind::Date => Cell::Text(locale.format_date(row.get::<i64, usize>(i).unwrap_or_default()))
+        }
+    }).collect()
+}
+
+fn new_csv_writer(locale: Locale) -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new().delimiter(locale.csv_delimiter).from_writer(Vec::new())
+}
\ No newline at end of file
@@ -0,0 +1,741 @@
+use std::io::Write;
+
+use chrono::TimeZone;
+use rust_decimal::Decimal;
+
+use crate::database::Database;
+use crate::invoices::{get_invoice, get_invoices_issued, get_settings, tax_rate, Invoice, InvoiceSettings, InvoiceType, SHIPPING_TAX_GROUP};
+use crate::orders::get_order_details;
+use crate::types::money::Money;
+use crate::types::order::OrderDetails;
+
+/// Peppol BIS Billing 3.0 specification identifier. Receivers check the document against this specification, OrderSync only follows its structure and calculation rules
+const CUSTOMIZATION_ID: &str = "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0";
+
+/// Peppol BIS Billing 3.0 business process
+const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+/// UNCL1001 document type codes
+const INVOICE_TYPE_CODE: &str = "380";
+const CREDIT_NOTE_TYPE_CODE: &str = "381";
+
+/// UNCL4461 payment means code for a SEPA credit transfer
+const SEPA_CREDIT_TRANSFER: &str = "58";
+
+/// UN/ECE rec 20 unit code for 'one', used for all quantities
+const UNIT_CODE: &str = "C62";
+
+/// Maximum number of decimals of an item price. Prices needing more are given for the whole quantity of a line
+const PRICE_DECIMALS: u32 = 4;
+
+/// A line of the e-invoice, in the document's currency and excluding tax
+struct UblLine {
+    name:       String,
+    sku:        String,
+    quantity:   i64,
+
+    /// Rounded to the currency's minor units
+    net:        Money,
+    rate:       Decimal
+}
+
+/**
+Build the UBL 2.1 document for an invoice or credit note, following the structure of Peppol BIS Billing 3.0.
+The document is not validated against the UBL 2.1 schema or the Peppol schematron before it is returned
+
+## Params
+    **database** Instance of a Database object
+    **invoice_id** The invoice or credit note
+
+## Returns
+    **Ok**: The file name and XML document, or None if the invoice doesn't exist
+    **Err**: A summary of what went wrong
+*/
+pub fn build_ubl(database: Database, invoice_id: String) -> Result<Option<(String, String)>, String> {
+    let invoice = match get_invoice(database.clone(), invoice_id)? {
+        Some(invoice) => invoice,
+        None => return Ok(None)
+    };
+
+    build_ubl_for(database, &invoice).map(Some)
+}
+
+/**
+Build the UBL 2.1 documents for all invoices and credit notes issued within a period, as a zip archive
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to export the invoices of
+    **from** Start of the period, epoch seconds
+    **to** End of the period (exclusive), epoch seconds
+
+## Returns
+    **Ok**: The zip archive
+    **Err**: A summary of what went wrong
+*/
+pub fn build_ubl_archive(database: Database, instance_id: String, from: i64, to: i64) -> Result<Vec<u8>, String> {
+    let invoices = get_invoices_issued(database.clone(), instance_id, from, to)?;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for invoice in &invoices {
+        let (file_name, xml) = build_ubl_for(database.clone(), invoice)?;
+
+        if let Err(e) = zip.start_file(file_name, zip::write::FileOptions::default()) {
+            return Err(e.to_string());
+        }
+
+        if let Err(e) = zip.write_all(xml.as_bytes()) {
+            return Err(e.to_string());
+        }
+    }
+
+    match zip.finish() {
+        Ok(cursor) => Ok(cursor.into_inner()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn build_ubl_for(database: Database, invoice: &Invoice) -> Result<(String, String), String> {
+    let details = match get_order_details(database.clone(), &invoice.order_id)? {
+        Some(details) => details,
+        None => return Err(format!("The order of invoice {} no longer exists", invoice.invoice_number))
+    };
+
+    let settings = match get_settings(database.clone(), invoice.instance_id.clone())? {
+        Some(settings) => settings,
+        None => return Err(format!("Invoicing is not set up for instance {}", invoice.instance_id))
+    };
+
+    let credited_invoice_number = match &invoice.credited_invoice_id {
+        Some(credited_invoice_id) => get_invoice(database, credited_invoice_id.clone())?.map(|credited| credited.invoice_number),
+        None => None
+    };
+
+    let xml = render_ubl(invoice, &details, &settings, credited_invoice_number)?;
+    Ok((format!("{}.xml", invoice.invoice_number), xml))
+}
+
+fn render_ubl(invoice: &Invoice, details: &OrderDetails, seller: &InvoiceSettings, credited_invoice_number: Option<String>) -> Result<String, String> {
+    let credit_note = invoice.invoice_type == InvoiceType::CreditNote;
+    let (root, line_element, quantity_element) = if credit_note {
+        ("CreditNote", "CreditNoteLine", "CreditedQuantity")
+    } else {
+        ("Invoice", "InvoiceLine", "InvoicedQuantity")
+    };
+
+    //Credit notes are stored with negative amounts, UBL expresses them as positive amounts on a credit note
+    let sign = if credit_note { Decimal::NEGATIVE_ONE } else { Decimal::ONE };
+    let currency = &invoice.currency;
+    let lines = ubl_lines(invoice, details, sign);
+    let endpoint_scheme = vat_scheme(&seller.company_country)?;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<{} xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:{}-2\" \
+        xmlns:cac=\"urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2\" \
+        xmlns:cbc=\"urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2\">\n", root, root));
+
+    xml.push_str(&element("cbc:CustomizationID", CUSTOMIZATION_ID));
+    xml.push_str(&element("cbc:ProfileID", PROFILE_ID));
+    xml.push_str(&element("cbc:ID", &invoice.invoice_number));
+    xml.push_str(&element("cbc:IssueDate", &format_date(invoice.issued_at)));
+
+    if credit_note {
+        xml.push_str(&element("cbc:CreditNoteTypeCode", CREDIT_NOTE_TYPE_CODE));
+    } else {
+        xml.push_str(&element("cbc:InvoiceTypeCode", INVOICE_TYPE_CODE));
+    }
+
+    xml.push_str(&element("cbc:DocumentCurrencyCode", currency));
+    xml.push_str(&element("cbc:BuyerReference", &details.order.wix_order_id.to_string()));
+
+    if let Some(credited_invoice_number) = credited_invoice_number {
+        xml.push_str("<cac:BillingReference><cac:InvoiceDocumentReference>");
+        xml.push_str(&element("cbc:ID", &credited_invoice_number));
+        xml.push_str("</cac:InvoiceDocumentReference></cac:BillingReference>\n");
+    }
+
+    //Seller
+    xml.push_str("<cac:AccountingSupplierParty><cac:Party>\n");
+    xml.push_str(&format!("<cbc:EndpointID schemeID=\"{}\">{}</cbc:EndpointID>\n", endpoint_scheme, escape(&seller.vat_number)));
+    xml.push_str(&postal_address(&seller.company_street, "", &seller.company_zip_code, &seller.company_city, &seller.company_country));
+    xml.push_str("<cac:PartyTaxScheme>");
+    xml.push_str(&element("cbc:CompanyID", &seller.vat_number));
+    xml.push_str("<cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme></cac:PartyTaxScheme>\n");
+    xml.push_str("<cac:PartyLegalEntity>");
+    xml.push_str(&element("cbc:RegistrationName", &seller.company_name));
+    xml.push_str(&element("cbc:CompanyID", &seller.coc_number));
+    xml.push_str("</cac:PartyLegalEntity>\n");
+    xml.push_str("</cac:Party></cac:AccountingSupplierParty>\n");
+
+    //Buyer
+    xml.push_str("<cac:AccountingCustomerParty><cac:Party>\n");
+    xml.push_str(&format!("<cbc:EndpointID schemeID=\"EM\">{}</cbc:EndpointID>\n", escape(&details.buyer_email)));
+    match &details.billing_address {
+        Some(address) => xml.push_str(&postal_address(&address.address_line_1, &address.address_line_2, &address.zip_code, &address.city, &address.country)),
+        None => xml.push_str(&postal_address("", "", "", "", &seller.company_country))
+    }
+    xml.push_str("<cac:PartyLegalEntity>");
+    let buyer_name = match &details.billing_address {
+        Some(address) if !address.company.is_empty() => &address.company,
+        Some(address) => address.addressee(&details.buyer_name),
+        None => &details.buyer_name
+    };
+    xml.push_str(&element("cbc:RegistrationName", buyer_name));
+    xml.push_str("</cac:PartyLegalEntity>\n");
+    xml.push_str("</cac:Party></cac:AccountingCustomerParty>\n");
+
+    if !seller.iban.is_empty() {
+        xml.push_str("<cac:PaymentMeans>");
+        xml.push_str(&element("cbc:PaymentMeansCode", SEPA_CREDIT_TRANSFER));
+        xml.push_str("<cac:PayeeFinancialAccount>");
+        xml.push_str(&element("cbc:ID", &seller.iban.replace(' ', "")));
+        xml.push_str("</cac:PayeeFinancialAccount></cac:PaymentMeans>\n");
+    }
+
+    //Tax per category and rate
+    let mut subtotals: Vec<(Decimal, Decimal, Decimal)> = Vec::new();
+    for line in &invoice.tax_lines {
+        match subtotals.iter_mut().find(|(rate, _, _)| *rate == line.rate) {
+            Some(subtotal) => {
+                subtotal.1 += line.net * sign;
+                subtotal.2 += line.tax * sign;
+            },
+            None => subtotals.push((line.rate, line.net * sign, line.tax * sign))
+        }
+    }
+
+    xml.push_str("<cac:TaxTotal>");
+    xml.push_str(&amount("cbc:TaxAmount", invoice.tax_total * sign, currency));
+    for (rate, net, tax) in &subtotals {
+        xml.push_str("<cac:TaxSubtotal>");
+        xml.push_str(&amount("cbc:TaxableAmount", *net, currency));
+        xml.push_str(&amount("cbc:TaxAmount", *tax, currency));
+        xml.push_str(&tax_category("cac:TaxCategory", *rate));
+        xml.push_str("</cac:TaxSubtotal>\n");
+    }
+    xml.push_str("</cac:TaxTotal>\n");
+
+    //Orders are paid in the shop, so the full amount is prepaid. The lines are rounded before they're summed (BR-CO-10)
+    let nets: Vec<Money> = lines.iter().map(|line| line.net.clone()).collect();
+    let line_total = Money::sum(&nets, currency)?;
+    xml.push_str("<cac:LegalMonetaryTotal>");
+    xml.push_str(&amount("cbc:LineExtensionAmount", line_total.amount, currency));
+    xml.push_str(&amount("cbc:TaxExclusiveAmount", invoice.net_total * sign, currency));
+    xml.push_str(&amount("cbc:TaxInclusiveAmount", invoice.gross_total * sign, currency));
+    xml.push_str(&amount("cbc:PrepaidAmount", invoice.gross_total * sign, currency));
+    xml.push_str(&amount("cbc:PayableAmount", Decimal::ZERO, currency));
+    xml.push_str("</cac:LegalMonetaryTotal>\n");
+
+    for (i, line) in lines.iter().enumerate() {
+        xml.push_str(&format!("<cac:{}>", line_element));
+        xml.push_str(&element("cbc:ID", &(i + 1).to_string()));
+        xml.push_str(&format!("<cbc:{} unitCode=\"{}\">{}</cbc:{}>", quantity_element, UNIT_CODE, line.quantity, quantity_element));
+        xml.push_str(&amount("cbc:LineExtensionAmount", line.net.amount, currency));
+        xml.push_str("<cac:Item>");
+        xml.push_str(&element("cbc:Name", &line.name));
+        if !line.sku.is_empty() {
+            xml.push_str("<cac:SellersItemIdentification>");
+            xml.push_str(&element("cbc:ID", &line.sku));
+            xml.push_str("</cac:SellersItemIdentification>");
+        }
+        xml.push_str(&tax_category("cac:ClassifiedTaxCategory", line.rate));
+        xml.push_str("</cac:Item>");
+        xml.push_str("<cac:Price>");
+        match unit_price(line)? {
+            Some(price) => xml.push_str(&price_amount(price, currency)),
+            None => {
+                xml.push_str(&price_amount(line.net.amount, currency));
+                xml.push_str(&format!("<cbc:BaseQuantity unitCode=\"{}\">{}</cbc:BaseQuantity>", UNIT_CODE, line.quantity));
+            }
+        }
+        xml.push_str("</cac:Price>");
+        xml.push_str(&format!("</cac:{}>\n", line_element));
+    }
+
+    xml.push_str(&format!("</{}>\n", root));
+    Ok(xml)
+}
+
+/**
+Get the price of a single item of a line, such that quantity × price equals the line amount (PEPPOL-EN16931-R120)
+
+## Returns
+    **Ok**: The price, or None if the amount doesn't divide exactly by the quantity, e.g. 10.00 for 3 items.
+        The price is then given for the whole quantity, with the quantity as base quantity
+    **Err**: The amount overflows
+*/
+fn unit_price(line: &UblLine) -> Result<Option<Decimal>, String> {
+    let price = match line.net.amount.checked_div(Decimal::from(line.quantity)) {
+        Some(price) => Money::new(price.round_dp(PRICE_DECIMALS), &line.net.currency),
+        None => return Ok(None)
+    };
+
+    if price.times(line.quantity)? == line.net {
+        Ok(Some(price.amount))
+    } else {
+        Ok(None)
+    }
+}
+
+/**
+Get the lines of an e-invoice.
+Invoices list the order's line items and shipping. A credit note may cover a partial refund, which
+can't be attributed to line items, so it has a line per tax rate instead.
+*/
+fn ubl_lines(invoice: &Invoice, details: &OrderDetails, sign: Decimal) -> Vec<UblLine> {
+    if invoice.invoice_type == InvoiceType::CreditNote {
+        return invoice.tax_lines.iter().map(|line| UblLine {
+            name: format!("Refund order #{}", details.order.wix_order_id),
+            sku: String::new(),
+            quantity: 1,
+            net: Money::new(line.net * sign, &invoice.currency).round(),
+            rate: line.rate
+        }).collect();
+    }
+
+    //Each item is rounded the way it was rounded in the tax breakdown, so the lines add up to the invoice's net total
+    let mut lines: Vec<UblLine> = details.items.iter().map(|item| {
+        let net = if item.tax_included_in_price { item.total - item.tax } else { item.total };
+        UblLine {
+            name: item.name.clone(),
+            sku: item.sku.clone(),
+            quantity: item.quantity,
+            net: Money::new(net, &invoice.currency).round(),
+            rate: tax_rate_of(invoice, &item.tax_group_id, net, item.tax)
+        }
+    }).collect();
+
+    if let Some(shipping) = invoice.tax_lines.iter().find(|line| line.tax_group_id == SHIPPING_TAX_GROUP) {
+        lines.push(UblLine {
+            name: "Shipping".to_string(),
+            sku: String::new(),
+            quantity: 1,
+            net: Money::new(shipping.net, &invoice.currency).round(),
+            rate: shipping.rate
+        });
+    }
+
+    lines
+}
+
+/// Find the rate a line item was grouped under in the invoice's tax breakdown
+fn tax_rate_of(invoice: &Invoice, tax_group_id: &str, net: Decimal, tax: Decimal) -> Decimal {
+    let rate = tax_rate(net, tax);
+    invoice.tax_lines.iter()
+        .find(|line| line.tax_group_id == tax_group_id && line.rate == rate)
+        .map(|line| line.rate)
+        .unwrap_or(rate)
+}
+
+fn postal_address(street: &str, additional_street: &str, zip_code: &str, city: &str, country: &str) -> String {
+    let mut xml = String::from("<cac:PostalAddress>");
+    if !street.is_empty() {
+        xml.push_str(&element("cbc:StreetName", street));
+    }
+    if !additional_street.is_empty() {
+        xml.push_str(&element("cbc:AdditionalStreetName", additional_street));
+    }
+    if !city.is_empty() {
+        xml.push_str(&element("cbc:CityName", city));
+    }
+    if !zip_code.is_empty() {
+        xml.push_str(&element("cbc:PostalZone", zip_code));
+    }
+    xml.push_str("<cac:Country>");
+    xml.push_str(&element("cbc:IdentificationCode", country));
+    xml.push_str("</cac:Country></cac:PostalAddress>\n");
+    xml
+}
+
+/// Standard rated (S) or zero rated (Z) VAT category
+fn tax_category(element_name: &str, rate: Decimal) -> String {
+    format!("<{}><cbc:ID>{}</cbc:ID><cbc:Percent>{:.2}</cbc:Percent><cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme></{}>",
+        element_name, if rate > Decimal::ZERO { "S" } else { "Z" }, rate, element_name)
+}
+
+/**
+Get the Peppol electronic address scheme for the VAT number of the seller's country
+
+## Params
+    **country** ISO 3166 country code of the seller
+
+## Returns
+    **Ok**: The electronic address scheme ID
+    **Err**: If sellers of the country are not addressed by their VAT number, or the country is not supported
+*/
+fn vat_scheme(country: &str) -> Result<&'static str, String> {
+    match country {
+        "AT" => Ok("9914"),
+        "BE" => Ok("9925"),
+        "BG" => Ok("9926"),
+        "CY" => Ok("9928"),
+        "CZ" => Ok("9929"),
+        "DE" => Ok("9930"),
+        "EE" => Ok("9931"),
+        "ES" => Ok("9920"),
+        "FI" => Ok("0213"),
+        "FR" => Ok("9957"),
+        "GB" => Ok("9932"),
+        "GR" => Ok("9933"),
+        "HR" => Ok("9934"),
+        "HU" => Ok("9910"),
+        "IE" => Ok("9935"),
+        "IT" => Ok("0211"),
+        "LT" => Ok("9937"),
+        "LU" => Ok("9938"),
+        "LV" => Ok("9939"),
+        "MT" => Ok("9943"),
+        "NL" => Ok("9944"),
+        "PL" => Ok("9945"),
+        "PT" => Ok("9946"),
+        "RO" => Ok("9947"),
+        "SI" => Ok("9949"),
+        "SK" => Ok("9950"),
+
+        //Peppol addresses Danish and Swedish companies by their company registration number, which OrderSync doesn't store
+        "DK" | "SE" => Err(format!("E-invoices for sellers in '{}' need the company registration number as electronic address, which is not supported", country)),
+        _ => Err(format!("E-invoices are not supported for sellers in '{}'", country))
+    }
+}
+
+fn element(name: &str, value: &str) -> String {
+    format!("<{}>{}</{}>", name, escape(value), name)
+}
+
+fn amount(name: &str, value: Decimal, currency: &str) -> String {
+    let mut value = Money::new(value, currency).round().amount;
+
+    //Avoid '-0.00'
+    if value.is_zero() {
+        value.set_sign_positive(true);
+    }
+
+    format!("<{} currencyID=\"{}\">{}</{}>", name, escape(currency), value, name)
+}
+
+/// Item prices aren't limited to the currency's minor units, but are written with at least that many decimals
+fn price_amount(value: Decimal, currency: &str) -> String {
+    let mut value = value.normalize();
+    if value.scale() < Money::minor_units(currency) {
+        value.rescale(Money::minor_units(currency));
+    }
+
+    format!("<cbc:PriceAmount currencyID=\"{}\">{}</cbc:PriceAmount>", escape(currency), value)
+}
+
+fn format_date(epoch: i64) -> String {
+    chrono::Utc.timestamp_opt(epoch, 0).unwrap().format("%Y-%m-%d").to_string()
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::invoices::{tax_breakdown, TaxLine};
+    use crate::types::order::{Address, Order, OrderItem};
+
+    /// Children of the document root in the order the UBL 2.1 Invoice and CreditNote schemas require them, as far as they're used
+    const ROOT_SEQUENCE: &[&str] = &["CustomizationID", "ProfileID", "ID", "IssueDate", "InvoiceTypeCode", "CreditNoteTypeCode", "DocumentCurrencyCode", "BuyerReference",
+        "BillingReference", "AccountingSupplierParty", "AccountingCustomerParty", "PaymentMeans", "TaxTotal", "LegalMonetaryTotal", "InvoiceLine", "CreditNoteLine"];
+    const PARTY_SEQUENCE: &[&str] = &["EndpointID", "PostalAddress", "PartyTaxScheme", "PartyLegalEntity"];
+    const ADDRESS_SEQUENCE: &[&str] = &["StreetName", "AdditionalStreetName", "CityName", "PostalZone", "Country"];
+    const TOTAL_SEQUENCE: &[&str] = &["LineExtensionAmount", "TaxExclusiveAmount", "TaxInclusiveAmount", "PrepaidAmount", "PayableAmount"];
+    const LINE_SEQUENCE: &[&str] = &["ID", "InvoicedQuantity", "CreditedQuantity", "LineExtensionAmount", "Item", "Price"];
+    const ITEM_SEQUENCE: &[&str] = &["Name", "SellersItemIdentification", "ClassifiedTaxCategory"];
+    const PRICE_SEQUENCE: &[&str] = &["PriceAmount", "BaseQuantity"];
+    const CATEGORY_SEQUENCE: &[&str] = &["ID", "Percent", "TaxScheme"];
+
+    const CBC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+    const CAC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn item(name: &str, quantity: i64, total: &str, tax: &str, tax_included_in_price: bool) -> OrderItem {
+        OrderItem {
+            order_item_id:          name.to_string(),
+            product_id:             None,
+            variant_id:             None,
+            item_type:              "PHYSICAL".to_string(),
+            name:                   name.to_string(),
+            sku:                    format!("SKU-{}", name),
+            quantity,
+            weight:                 0.0,
+            weight_kg:              None,
+            price:                  decimal(total) / Decimal::from(quantity),
+            total:                  decimal(total),
+            tax:                    decimal(tax),
+            tax_group_id:           if tax == "0.00" { "zero".to_string() } else { "standard".to_string() },
+            tax_included_in_price,
+            discount:               Decimal::ZERO,
+            options:                Vec::new()
+        }
+    }
+
+    /// An order with prices which don't divide by their quantity, prices including tax and shipping
+    fn order_details() -> OrderDetails {
+        let items = vec![
+            item("Mug", 3, "10.00", "2.10", false),
+            item("Poster", 7, "12.10", "2.10", true),
+            item("Book", 2, "19.98", "0.00", false),
+            item("Sticker & pin", 4, "1.00", "0.21", false)
+        ];
+
+        OrderDetails {
+            order: Order {
+                order_id:           "order".to_string(),
+                instance_id:        "instance".to_string(),
+                wix_order_id:       10042,
+                order_date:         1614556800,
+                currency:           "EUR".to_string(),
+                payment_status:     "Paid".to_string(),
+                payment_method:     "CreditCard".to_string(),
+                channel:            "Web".to_string(),
+                fulfillment_status: "NotFulfilled".to_string(),
+                delivery_method:    "SHIP".to_string(),
+                requires_shipping:  true,
+                total_price:        decimal("49.62"),
+                subtotal:           decimal("43.08"),
+                tax:                decimal("5.45"),
+                shipping:           decimal("4.95"),
+                discount:           Decimal::ZERO,
+                quantity:           13,
+                weight:             0.0,
+                weight_unit:        "KG".to_string(),
+                weight_kg:          None
+            },
+            buyer_name:         "Jan de Vries".to_string(),
+            buyer_email:        "jan@example.com".to_string(),
+            buyer_phone:        String::new(),
+            buyer_note:         String::new(),
+            billing_address:    Some(Address {
+                address_type:           "BILLING".to_string(),
+                full_name:              "Jan de Vries".to_string(),
+                company:                String::new(),
+                email:                  String::new(),
+                phone:                  String::new(),
+                address_line_1:         "Dorpsstraat 1".to_string(),
+                address_line_2:         String::new(),
+                street:                 "Dorpsstraat".to_string(),
+                house_number:           "1".to_string(),
+                house_number_addition:  String::new(),
+                zip_code:               "1234 AB".to_string(),
+                city:                   "Utrecht".to_string(),
+                country:                "NL".to_string(),
+                needs_review:           false
+            }),
+            shipping_address:   None,
+            items
+        }
+    }
+
+    fn seller(country: &str) -> InvoiceSettings {
+        InvoiceSettings {
+            instance_id:        "instance".to_string(),
+            prefix:             "INV-".to_string(),
+            company_name:       "Winkel B.V.".to_string(),
+            company_street:     "Kerkstraat 2".to_string(),
+            company_zip_code:   "1000 AA".to_string(),
+            company_city:       "Amsterdam".to_string(),
+            company_country:    country.to_string(),
+            vat_number:         "NL123456789B01".to_string(),
+            coc_number:         "12345678".to_string(),
+            iban:               "NL91 ABNA 0417 1643 00".to_string()
+        }
+    }
+
+    fn invoice(details: &OrderDetails, invoice_type: InvoiceType, tax_lines: Vec<TaxLine>) -> Invoice {
+        let net_total: Decimal = tax_lines.iter().map(|line| line.net).sum();
+        let tax_total: Decimal = tax_lines.iter().map(|line| line.tax).sum();
+
+        Invoice {
+            invoice_id:             "invoice".to_string(),
+            instance_id:            details.order.instance_id.clone(),
+            order_id:               details.order.order_id.clone(),
+            invoice_number:         if invoice_type == InvoiceType::Invoice { "INV-000001".to_string() } else { "INV-000002".to_string() },
+            invoice_type,
+            credited_invoice_id:    None,
+            refund_id:              None,
+            issued_at:              1614643200,
+            currency:               details.order.currency.clone(),
+            net_total,
+            tax_total,
+            gross_total:            net_total + tax_total,
+            tax_lines
+        }
+    }
+
+    /// A credit note for a partial refund, with the tax lines of the invoice scaled down
+    fn credit_note(details: &OrderDetails) -> Invoice {
+        let tax_lines = tax_breakdown(details).into_iter().map(|line| TaxLine {
+            net: -Money::new(line.net / Decimal::from(3), "EUR").round().amount,
+            tax: -Money::new(line.tax / Decimal::from(3), "EUR").round().amount,
+            ..line
+        }).collect();
+
+        invoice(details, InvoiceType::CreditNote, tax_lines)
+    }
+
+    fn children<'a, 'input>(node: roxmltree::Node<'a, 'input>) -> Vec<roxmltree::Node<'a, 'input>> {
+        node.children().filter(|child| child.is_element()).collect()
+    }
+
+    fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> roxmltree::Node<'a, 'input> {
+        children(node).into_iter().find(|child| child.tag_name().name() == name).unwrap_or_else(|| panic!("{} has no {}", node.tag_name().name(), name))
+    }
+
+    fn value(node: roxmltree::Node, name: &str) -> Decimal {
+        decimal(child(node, name).text().unwrap())
+    }
+
+    /// Check the element children of a node appear in schema order and use the CBC or CAC namespace
+    fn assert_sequence(node: roxmltree::Node, sequence: &[&str]) {
+        let mut previous = 0;
+        for child in children(node) {
+            let name = child.tag_name().name();
+            let position = sequence.iter().position(|expected| *expected == name).unwrap_or_else(|| panic!("Unexpected element {} in {}", name, node.tag_name().name()));
+            assert!(position >= previous, "{} is out of order in {}", name, node.tag_name().name());
+            assert!(child.tag_name().namespace() == Some(CBC) || child.tag_name().namespace() == Some(CAC), "{} has no UBL namespace", name);
+            previous = position;
+        }
+    }
+
+    /// Check the element order and the Peppol BIS Billing 3.0 calculation rules of a document. This covers what OrderSync computes,
+    /// it does not replace validation against the UBL 2.1 schema and the Peppol schematron
+    fn validate(xml: &str, root: &str) {
+        let document = roxmltree::Document::parse(xml).unwrap();
+        let root_element = document.root_element();
+        assert_eq!(root_element.tag_name().name(), root);
+        assert_eq!(root_element.tag_name().namespace(), Some(format!("urn:oasis:names:specification:ubl:schema:xsd:{}-2", root).as_str()));
+        assert_sequence(root_element, ROOT_SEQUENCE);
+
+        for party in &["AccountingSupplierParty", "AccountingCustomerParty"] {
+            let party = child(child(root_element, party), "Party");
+            assert_sequence(party, PARTY_SEQUENCE);
+            assert_sequence(child(party, "PostalAddress"), ADDRESS_SEQUENCE);
+            assert!(child(party, "EndpointID").attribute("schemeID").is_some());
+        }
+
+        //Every amount is in the document currency, with at most two decimals except prices
+        for amount in document.descendants().filter(|node| node.attribute("currencyID").is_some()) {
+            assert_eq!(amount.attribute("currencyID"), Some("EUR"));
+            if amount.tag_name().name() != "PriceAmount" {
+                assert!(value(amount.parent().unwrap(), amount.tag_name().name()).scale() <= 2, "{} has more than two decimals", amount.tag_name().name());
+            }
+        }
+
+        let lines: Vec<roxmltree::Node> = children(root_element).into_iter().filter(|node| node.tag_name().name().ends_with("Line")).collect();
+        assert!(!lines.is_empty());
+
+        let mut line_total = Decimal::ZERO;
+        let mut taxable_per_rate: Vec<(Decimal, Decimal)> = Vec::new();
+        for line in &lines {
+            assert_sequence(*line, LINE_SEQUENCE);
+            assert_sequence(child(*line, "Item"), ITEM_SEQUENCE);
+            assert_sequence(child(child(*line, "Item"), "ClassifiedTaxCategory"), CATEGORY_SEQUENCE);
+
+            let price = child(*line, "Price");
+            assert_sequence(price, PRICE_SEQUENCE);
+
+            //PEPPOL-EN16931-R120: quantity × price / base quantity = line amount
+            let quantity = decimal(children(*line)[1].text().unwrap());
+            let base_quantity = children(price).into_iter().find(|node| node.tag_name().name() == "BaseQuantity").map(|node| decimal(node.text().unwrap())).unwrap_or(Decimal::ONE);
+            let net = value(*line, "LineExtensionAmount");
+            assert_eq!(quantity * value(price, "PriceAmount") / base_quantity, net, "Line {}", value(*line, "ID"));
+
+            let rate = value(child(child(*line, "Item"), "ClassifiedTaxCategory"), "Percent");
+            match taxable_per_rate.iter_mut().find(|(line_rate, _)| *line_rate == rate) {
+                Some((_, taxable)) => *taxable += net,
+                None => taxable_per_rate.push((rate, net))
+            }
+
+            line_total += net;
+        }
+
+        let tax_total = child(root_element, "TaxTotal");
+        let totals = child(root_element, "LegalMonetaryTotal");
+        assert_sequence(totals, TOTAL_SEQUENCE);
+
+        //BR-CO-10 and BR-CO-13: the document totals are the sum of the rounded line amounts
+        assert_eq!(value(totals, "LineExtensionAmount"), line_total);
+        assert_eq!(value(totals, "TaxExclusiveAmount"), line_total);
+
+        //BR-CO-15 and BR-CO-16
+        assert_eq!(value(totals, "TaxInclusiveAmount"), value(totals, "TaxExclusiveAmount") + value(tax_total, "TaxAmount"));
+        assert_eq!(value(totals, "PayableAmount"), value(totals, "TaxInclusiveAmount") - value(totals, "PrepaidAmount"));
+
+        //BR-CO-14 and BR-S-08: the subtotals add up to the tax amount, and each is taxed over the lines at its rate
+        let subtotals: Vec<roxmltree::Node> = children(tax_total).into_iter().filter(|node| node.tag_name().name() == "TaxSubtotal").collect();
+        assert_eq!(subtotals.iter().map(|subtotal| value(*subtotal, "TaxAmount")).sum::<Decimal>(), value(tax_total, "TaxAmount"));
+        assert_eq!(subtotals.len(), taxable_per_rate.len());
+        for subtotal in subtotals {
+            let rate = value(child(subtotal, "TaxCategory"), "Percent");
+            let taxable = taxable_per_rate.iter().find(|(line_rate, _)| *line_rate == rate).map(|(_, taxable)| *taxable);
+            assert_eq!(Some(value(subtotal, "TaxableAmount")), taxable, "Taxable amount at {}%", rate);
+        }
+    }
+
+    #[test]
+    fn invoice_follows_peppol_structure_and_calculations() {
+        let details = order_details();
+        let invoice = invoice(&details, InvoiceType::Invoice, tax_breakdown(&details));
+        let xml = render_ubl(&invoice, &details, &seller("NL"), None).unwrap();
+
+        validate(&xml, "Invoice");
+        assert!(xml.contains("<cbc:Name>Sticker &amp; pin</cbc:Name>"));
+    }
+
+    #[test]
+    fn credit_note_follows_peppol_structure_and_calculations() {
+        let details = order_details();
+        let xml = render_ubl(&credit_note(&details), &details, &seller("BE"), Some("INV-000001".to_string())).unwrap();
+
+        validate(&xml, "CreditNote");
+        assert!(!xml.contains(">-"), "Credit note amounts must be positive");
+    }
+
+    #[test]
+    fn prices_which_do_not_divide_use_a_base_quantity() {
+        let details = order_details();
+        let invoice = invoice(&details, InvoiceType::Invoice, tax_breakdown(&details));
+        let xml = render_ubl(&invoice, &details, &seller("NL"), None).unwrap();
+
+        //10.00 for 3 mugs, 9.99 each for the books and 0.25 for the stickers
+        assert!(xml.contains("<cbc:PriceAmount currencyID=\"EUR\">10.00</cbc:PriceAmount><cbc:BaseQuantity unitCode=\"C62\">3</cbc:BaseQuantity>"));
+        assert!(xml.contains("<cbc:PriceAmount currencyID=\"EUR\">9.99</cbc:PriceAmount></cac:Price>"));
+        assert!(xml.contains("<cbc:PriceAmount currencyID=\"EUR\">0.25</cbc:PriceAmount></cac:Price>"));
+    }
+
+    #[test]
+    fn unknown_seller_country_is_rejected() {
+        let details = order_details();
+        let invoice = invoice(&details, InvoiceType::Invoice, tax_breakdown(&details));
+
+        assert!(render_ubl(&invoice, &details, &seller("XX"), None).is_err());
+    }
+
+    #[test]
+    fn every_eu_seller_country_has_a_scheme_or_an_explicit_error() {
+        let countries = ["AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK"];
+        for country in &countries {
+            match vat_scheme(country) {
+                Ok(scheme) => assert_eq!(scheme.len(), 4, "Scheme of {}", country),
+                Err(e) => assert!(e.contains("company registration number"), "Error for {}: {}", country, e)
+            }
+        }
+
+        assert_eq!(vat_scheme("NL"), Ok("9944"));
+        assert!(vat_scheme("DK").is_err());
+    }
+}
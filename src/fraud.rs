@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::database::Database;
+use crate::gdpr::ERASED;
+use crate::orders::get_order_details;
+use crate::types::money::Money;
+use crate::types::order::OrderDetails;
+
+const SECONDS_PER_HOUR: i64 = 60 * 60;
+
+/// Recorded as the author of the tags and workflow changes made when an order is put on hold
+const CHANGED_BY: &str = "Fraud scoring";
+
+/// Fraud risk settings of an instance. Orders are only scored when the instance has settings.
+/// A signal with a weight of 0 is not checked
+#[derive(Serialize, Deserialize)]
+pub struct RiskSettings {
+    pub instance_id:                String,
+
+    /// Points for an order shipped to another country than its billing address
+    pub country_mismatch_weight:    i64,
+
+    /// Points for the first order of a customer with a total of at least new_customer_min_total
+    pub new_customer_weight:        i64,
+
+    /// In the instance's reporting currency. Orders which have not been converted to it are not checked, see crate::exchange_rates
+    pub new_customer_min_total:     Decimal,
+
+    /// Points when more than velocity_max_orders orders were placed with the buyer's email address within velocity_window_hours
+    pub velocity_weight:            i64,
+    pub velocity_max_orders:        i64,
+    pub velocity_window_hours:      i64,
+
+    /// Points for an order shipped to an addressee whose name has nothing in common with the buyer's
+    pub name_mismatch_weight:       i64,
+
+    /// Orders scoring at least this many points are put on hold
+    pub hold_threshold:             i64,
+
+    /// Workflow state orders on hold are moved to, see crate::workflow
+    pub hold_state:                 Option<String>,
+
+    /// Tag added to orders on hold, e.g. 'fraud check'
+    pub hold_tag:                   Option<String>
+}
+
+/// The fraud risk score of an order
+#[derive(Serialize)]
+pub struct RiskScore {
+    pub order_id:       String,
+
+    /// Sum of the points of the signals
+    pub score:          i64,
+
+    /// The signals which contributed to the score
+    pub signals:        Vec<RiskSignal>,
+
+    /// Whether the score reached the hold threshold
+    pub on_hold:        bool,
+
+    /// Epoch seconds
+    pub scored_at:      i64
+}
+
+/// A reason an order scored points
+#[derive(Serialize, Deserialize)]
+pub struct RiskSignal {
+    /// 'COUNTRY_MISMATCH', 'NEW_CUSTOMER_HIGH_VALUE', 'ORDER_VELOCITY' or 'NAME_MISMATCH'
+    pub signal:         String,
+    pub points:         i64,
+
+    /// Explanation for staff, erased when the order is anonymized
+    pub explanation:    String
+}
+
+/**
+Get the fraud risk settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the settings for
+
+## Returns
+    **Ok**: The settings, or None if the instance has none
+    **Err**: A summary of what went wrong
+*/
+pub fn get_settings(database: Database, instance_id: String) -> Result<Option<RiskSettings>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT instance_id, country_mismatch_weight, new_customer_weight, new_customer_min_total, velocity_weight, velocity_max_orders, \
+        velocity_window_hours, name_mismatch_weight, hold_threshold, hold_state, hold_tag FROM risk_settings WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(row) => Ok(row.map(|row| RiskSettings {
+            instance_id:                row.get("instance_id").unwrap(),
+            country_mismatch_weight:    row.get("country_mismatch_weight").unwrap(),
+            new_customer_weight:        row.get("new_customer_weight").unwrap(),
+            new_customer_min_total:     row.get("new_customer_min_total").unwrap(),
+            velocity_weight:            row.get("velocity_weight").unwrap(),
+            velocity_max_orders:        row.get("velocity_max_orders").unwrap(),
+            velocity_window_hours:      row.get("velocity_window_hours").unwrap(),
+            name_mismatch_weight:       row.get("name_mismatch_weight").unwrap(),
+            hold_threshold:             row.get("hold_threshold").unwrap(),
+            hold_state:                 row.get("hold_state").unwrap(),
+            hold_tag:                   row.get("hold_tag").unwrap()
+        })),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Create or replace the fraud risk settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **settings** The new settings
+
+## Returns
+    **Ok**: Nothing
+    **Err**: The settings are invalid, or a summary of what went wrong
+*/
+pub fn set_settings(database: Database, settings: RiskSettings) -> Result<(), String> {
+    let weights = [settings.country_mismatch_weight, settings.new_customer_weight, settings.velocity_weight, settings.name_mismatch_weight];
+    if weights.iter().any(|weight| *weight < 0) {
+        return Err("Weights may not be negative".to_string());
+    }
+
+    if settings.velocity_max_orders < 1 || settings.velocity_window_hours < 1 {
+        return Err("The order velocity needs at least 1 order within at least 1 hour".to_string());
+    }
+
+    if settings.hold_threshold < 1 {
+        return Err("The hold threshold must be at least 1 point".to_string());
+    }
+
+    if let Some(state) = &settings.hold_state {
+        let states = crate::workflow::get_states(database.clone(), settings.instance_id.clone())?;
+        if !states.iter().any(|known| known.key == *state) {
+            return Err(format!("Unknown state '{}'", state));
+        }
+    }
+
+    if let Some(tag) = &settings.hold_tag {
+        crate::tags::validate_tag(tag.trim())?;
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO risk_settings (instance_id, country_mismatch_weight, new_customer_weight, new_customer_min_total, velocity_weight, \
+        velocity_max_orders, velocity_window_hours, name_mismatch_weight, hold_threshold, hold_state, hold_tag) \
+        VALUES (:instance_id, :country_mismatch_weight, :new_customer_weight, :new_customer_min_total, :velocity_weight, \
+        :velocity_max_orders, :velocity_window_hours, :name_mismatch_weight, :hold_threshold, :hold_state, :hold_tag) \
+        ON DUPLICATE KEY UPDATE country_mismatch_weight = :country_mismatch_weight, new_customer_weight = :new_customer_weight, new_customer_min_total = :new_customer_min_total, \
+        velocity_weight = :velocity_weight, velocity_max_orders = :velocity_max_orders, velocity_window_hours = :velocity_window_hours, \
+        name_mismatch_weight = :name_mismatch_weight, hold_threshold = :hold_threshold, hold_state = :hold_state, hold_tag = :hold_tag", params! {
+        "instance_id" => settings.instance_id,
+        "country_mismatch_weight" => settings.country_mismatch_weight,
+        "new_customer_weight" => settings.new_customer_weight,
+        "new_customer_min_total" => settings.new_customer_min_total,
+        "velocity_weight" => settings.velocity_weight,
+        "velocity_max_orders" => settings.velocity_max_orders,
+        "velocity_window_hours" => settings.velocity_window_hours,
+        "name_mismatch_weight" => settings.name_mismatch_weight,
+        "hold_threshold" => settings.hold_threshold,
+        "hold_state" => settings.hold_state,
+        "hold_tag" => settings.hold_tag.map(|tag| tag.trim().to_string())
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the stored fraud risk score of an order
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+
+## Returns
+    **Ok**: The score, or None if the order was not scored
+    **Err**: A summary of what went wrong
+*/
+pub fn get_score(database: Database, order_id: String) -> Result<Option<RiskScore>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT order_id, score, signals, on_hold, scored_at FROM order_risk_scores WHERE order_id = :order_id", params! {
+        "order_id" => order_id
+    });
+
+    match result {
+        Ok(row) => Ok(row.map(|row| RiskScore {
+            order_id:   row.get("order_id").unwrap(),
+            score:      row.get("score").unwrap(),
+            signals:    serde_json::from_str(&row.get::<String, &str>("signals").unwrap()).unwrap_or_default(),
+            on_hold:    row.get("on_hold").unwrap(),
+            scored_at:  row.get("scored_at").unwrap()
+        })),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Score an order for fraud risk and store the score. An order reaching the hold threshold is tagged and moved to the hold state,
+an order which was scored before is not put on hold again.
+
+## Params
+    **database** Instance of a Database object
+    **settings** The risk settings of the order's instance
+    **order_id** The OrderSync ID of the order
+
+## Returns
+    **Ok**: The score, or None if the instance has no such order
+    **Err**: A summary of what went wrong
+*/
+pub fn score_order(database: Database, settings: &RiskSettings, order_id: String) -> Result<Option<RiskScore>, String> {
+    let details = match get_order_details(database.clone(), &order_id)? {
+        Some(details) if details.order.instance_id == settings.instance_id => details,
+        _ => return Ok(None)
+    };
+
+    let previous = get_score(database.clone(), order_id.clone())?;
+    let signals = collect_signals(&database, settings, &details)?;
+    let score: i64 = signals.iter().map(|signal| signal.points).sum();
+    let on_hold = reaches_hold_threshold(settings, score);
+
+    let risk_score = RiskScore {
+        order_id,
+        score,
+        signals,
+        on_hold,
+        scored_at:  chrono::Utc::now().timestamp()
+    };
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("INSERT INTO order_risk_scores (order_id, score, signals, on_hold, scored_at) VALUES (:order_id, :score, :signals, :on_hold, :scored_at) \
+        ON DUPLICATE KEY UPDATE score = :score, signals = :signals, on_hold = :on_hold, scored_at = :scored_at", params! {
+        "order_id" => risk_score.order_id.clone(),
+        "score" => risk_score.score,
+        "signals" => serde_json::to_string(&risk_score.signals).unwrap(),
+        "on_hold" => risk_score.on_hold,
+        "scored_at" => risk_score.scored_at
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    //Staff may have released the order since it was first put on hold
+    let was_on_hold = previous.map(|previous| previous.on_hold).unwrap_or(false);
+    if on_hold && !was_on_hold {
+        put_on_hold(&database, settings, &risk_score.order_id)?;
+    }
+
+    Ok(Some(risk_score))
+}
+
+fn collect_signals(database: &Database, settings: &RiskSettings, details: &OrderDetails) -> Result<Vec<RiskSignal>, String> {
+    let order = &details.order;
+    let mut signals = Vec::new();
+
+    //Pickup orders are collected at the store, the pickup address is not the buyer's
+    let shipping_address = details.shipping_address.as_ref().filter(|address| !address.is_pickup());
+
+    if settings.country_mismatch_weight > 0 {
+        if let (Some(billing), Some(shipping)) = (&details.billing_address, shipping_address) {
+            if !billing.country.is_empty() && !shipping.country.is_empty() && billing.country != shipping.country {
+                signals.push(RiskSignal {
+                    signal:         "COUNTRY_MISMATCH".to_string(),
+                    points:         settings.country_mismatch_weight,
+                    explanation:    format!("Billed in {}, shipped to {}", billing.country, shipping.country)
+                });
+            }
+        }
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+
+    //Orders in different currencies are compared against the minimum in the reporting currency
+    let converted_total = if settings.new_customer_weight > 0 {
+        let reporting_currency = crate::reports::get_settings(database.clone(), order.instance_id.clone())?.reporting_currency;
+        let result = conn.exec_first::<Row, &str, Params>("SELECT reporting_currency, exchange_rate FROM orders WHERE order_id = :order_id", params! {
+            "order_id" => order.order_id.clone()
+        });
+
+        match result {
+            Ok(row) => row.and_then(|row| crate::reports::exchange_rate(&row, &reporting_currency))
+                .map(|rate| convert_total(order.total_price, rate, &reporting_currency)),
+            Err(e) => return Err(e.to_string())
+        }
+    } else {
+        None
+    };
+
+    if let Some(total) = converted_total.filter(|total| total.amount >= settings.new_customer_min_total) {
+        let result = conn.exec_first::<i64, &str, Params>("SELECT COUNT(*) FROM orders p INNER JOIN orders o ON o.customer_id = p.customer_id \
+            WHERE o.order_id = :order_id AND p.order_date < o.order_date", params! {
+            "order_id" => order.order_id.clone()
+        });
+
+        match result {
+            Ok(previous_orders) => signals.extend(new_customer_signal(settings, &total, previous_orders.unwrap_or_default())),
+            Err(e) => return Err(e.to_string())
+        }
+    }
+
+    //Erased orders all share the same placeholder address, they are not the orders of a single buyer
+    if settings.velocity_weight > 0 && !details.buyer_email.is_empty() && details.buyer_email != ERASED {
+        let (window_start, window_end) = velocity_window(settings, order.order_date);
+        let result = conn.exec::<i64, &str, Params>("SELECT order_date FROM orders WHERE instance_id = :instance_id AND buyer_email = :buyer_email \
+            AND buyer_email <> :erased AND order_date > :window_start AND order_date <= :window_end", params! {
+            "instance_id" => order.instance_id.clone(),
+            "buyer_email" => details.buyer_email.clone(),
+            "erased" => ERASED,
+            "window_start" => window_start,
+            "window_end" => window_end
+        });
+
+        match result {
+            Ok(order_dates) => signals.extend(velocity_signal(settings, order.order_date, &order_dates)),
+            Err(e) => return Err(e.to_string())
+        }
+    }
+
+    if settings.name_mismatch_weight > 0 {
+        if let Some(shipping) = shipping_address {
+            if !shipping.full_name.is_empty() && !details.buyer_name.trim().is_empty() && !names_overlap(&shipping.full_name, &details.buyer_name) {
+                signals.push(RiskSignal {
+                    signal:         "NAME_MISMATCH".to_string(),
+                    points:         settings.name_mismatch_weight,
+                    explanation:    "The addressee's name has nothing in common with the buyer's name".to_string()
+                });
+            }
+        }
+    }
+
+    Ok(signals)
+}
+
+/// Convert an order total to the reporting currency, rounded to its minor units
+fn convert_total(total: Decimal, rate: Decimal, reporting_currency: &str) -> Money {
+    Money::new(total * rate, reporting_currency).round()
+}
+
+/// The signal for the first order of a customer with a total, in the reporting currency, of at least the minimum
+fn new_customer_signal(settings: &RiskSettings, total: &Money, previous_orders: i64) -> Option<RiskSignal> {
+    if previous_orders > 0 || total.amount < settings.new_customer_min_total {
+        return None;
+    }
+
+    Some(RiskSignal {
+        signal:         "NEW_CUSTOMER_HIGH_VALUE".to_string(),
+        points:         settings.new_customer_weight,
+        explanation:    format!("First order of the customer, with a total of {} {}", total.amount, total.currency)
+    })
+}
+
+/// The window of the velocity check for an order: after the start, up to and including the order itself. Epoch seconds
+fn velocity_window(settings: &RiskSettings, order_date: i64) -> (i64, i64) {
+    (order_date - settings.velocity_window_hours * SECONDS_PER_HOUR, order_date)
+}
+
+/// The signal for more than velocity_max_orders orders by the buyer within the window, counting the order itself
+fn velocity_signal(settings: &RiskSettings, order_date: i64, buyer_order_dates: &[i64]) -> Option<RiskSignal> {
+    let (window_start, window_end) = velocity_window(settings, order_date);
+    let count = buyer_order_dates.iter().filter(|date| **date > window_start && **date <= window_end).count() as i64;
+    if count <= settings.velocity_max_orders {
+        return None;
+    }
+
+    Some(RiskSignal {
+        signal:         "ORDER_VELOCITY".to_string(),
+        points:         settings.velocity_weight,
+        explanation:    format!("{} orders with the buyer's email address within {} hours", count, settings.velocity_window_hours)
+    })
+}
+
+/// Whether a score puts an order on hold
+fn reaches_hold_threshold(settings: &RiskSettings, score: i64) -> bool {
+    score >= settings.hold_threshold
+}
+
+/// Whether two names share a word, so 'J. Jansen' and 'Jan Jansen' overlap but 'Jan Jansen' and 'Piet de Vries' do not
+fn names_overlap(a: &str, b: &str) -> bool {
+    let words = |name: &str| -> HashSet<String> {
+        name.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() > 1)
+            .map(|word| word.to_lowercase())
+            .collect()
+    };
+
+    !words(a).is_disjoint(&words(b))
+}
+
+fn put_on_hold(database: &Database, settings: &RiskSettings, order_id: &str) -> Result<(), String> {
+    if let Some(tag) = &settings.hold_tag {
+        crate::tags::add_tags(database.clone(), settings.instance_id.clone(), &[order_id.to_string()], std::slice::from_ref(tag), CHANGED_BY)?;
+    }
+
+    if let Some(state) = &settings.hold_state {
+        let result = crate::workflow::move_orders(database.clone(), settings.instance_id.clone(), vec![order_id.to_string()], state.clone(), CHANGED_BY.to_string())?;
+        if let Some(rejected) = result.rejected.into_iter().next() {
+            return Err(format!("Unable to put order {} on hold: {}", order_id, rejected.reason));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn settings() -> RiskSettings {
+        RiskSettings {
+            instance_id:                "instance".to_string(),
+            country_mismatch_weight:    20,
+            new_customer_weight:        30,
+            new_customer_min_total:     Decimal::from(100),
+            velocity_weight:            40,
+            velocity_max_orders:        2,
+            velocity_window_hours:      24,
+            name_mismatch_weight:       10,
+            hold_threshold:             50,
+            hold_state:                 None,
+            hold_tag:                   None
+        }
+    }
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn names_overlap_on_a_shared_word() {
+        assert!(names_overlap("J. Jansen", "Jan Jansen"));
+        assert!(names_overlap("JAN JANSEN", "jan de vries"));
+        assert!(names_overlap("Anne-Marie de Vries", "Anne Smit"));
+        assert!(!names_overlap("Jan Jansen", "Piet de Vries"));
+
+        //Initials and single letters don't count as a shared word
+        assert!(!names_overlap("J. Jansen", "J. de Vries"));
+        assert!(!names_overlap("", "Jan Jansen"));
+    }
+
+    #[test]
+    fn velocity_counts_orders_after_the_window_start_up_to_the_order() {
+        let settings = settings();
+        let order_date = 1_000_000;
+        let (window_start, window_end) = velocity_window(&settings, order_date);
+        assert_eq!(window_start, order_date - 24 * SECONDS_PER_HOUR);
+        assert_eq!(window_end, order_date);
+
+        //The order itself and two more within the window: 3 > 2
+        assert!(velocity_signal(&settings, order_date, &[order_date, window_start + 1, order_date - 60]).is_some());
+
+        //An order exactly at the window start is outside it, as is an order placed after this one
+        assert!(velocity_signal(&settings, order_date, &[order_date, window_start, order_date - 60]).is_none());
+        assert!(velocity_signal(&settings, order_date, &[order_date, order_date + 1, order_date - 60]).is_none());
+
+        let signal = velocity_signal(&settings, order_date, &[order_date, order_date, order_date]).unwrap();
+        assert_eq!(signal.points, 40);
+        assert_eq!(signal.explanation, "3 orders with the buyer's email address within 24 hours");
+    }
+
+    #[test]
+    fn new_customer_minimum_is_compared_after_conversion() {
+        let settings = settings();
+
+        //USD 120.00 at 0.9 is EUR 108.00, at 0.8 it's EUR 96.00
+        let total = convert_total(decimal("120.00"), decimal("0.9"), "EUR");
+        assert_eq!(total, Money::new(decimal("108.00"), "EUR"));
+        assert!(new_customer_signal(&settings, &total, 0).is_some());
+        assert!(new_customer_signal(&settings, &convert_total(decimal("120.00"), decimal("0.8"), "EUR"), 0).is_none());
+
+        //Rounded to the reporting currency before comparing: 99.995 is 100.00
+        let total = convert_total(decimal("99.995"), Decimal::ONE, "EUR");
+        assert_eq!(total.amount, decimal("100.00"));
+        assert_eq!(new_customer_signal(&settings, &total, 0).unwrap().points, 30);
+
+        //Only the first order of a customer
+        assert!(new_customer_signal(&settings, &Money::new(decimal("500"), "EUR"), 1).is_none());
+
+        //JPY has no minor units
+        assert_eq!(convert_total(decimal("10.00"), decimal("160.456"), "JPY").amount, decimal("1605"));
+    }
+
+    #[test]
+    fn orders_are_held_from_the_threshold() {
+        let settings = settings();
+        assert!(!reaches_hold_threshold(&settings, 49));
+        assert!(reaches_hold_threshold(&settings, 50));
+        assert!(reaches_hold_threshold(&settings, 70));
+        assert!(!reaches_hold_threshold(&settings, 0));
+    }
+}
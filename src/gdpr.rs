@@ -0,0 +1,613 @@
+use serde::Serialize;
+use mysql::{Params, Row, Transaction, Value, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::database::Database;
+use crate::fraud::RiskSignal;
+use crate::rules::ActionOutcome;
+
+/// Replacement value for erased personal data
+pub const ERASED: &str = "ERASED";
+
+/// Everything OrderSync holds about a data subject
+#[derive(Serialize)]
+pub struct DataExport {
+    instance_id:        String,
+    email:              String,
+    exported_at:        i64,
+    customers:          Vec<serde_json::Value>,
+
+    /// Order totals of the customers less refunds, per currency
+    lifetime_values:    Vec<serde_json::Value>,
+    orders:             Vec<ExportedOrder>
+}
+
+/// An order with everything linked to it
+#[derive(Serialize)]
+pub struct ExportedOrder {
+    order:              serde_json::Value,
+    billing_address:    Option<serde_json::Value>,
+    shipping_address:   Option<serde_json::Value>,
+    items:              Vec<serde_json::Value>,
+    comments:           Vec<serde_json::Value>,
+
+    /// Invoices and credit notes issued for the order, without their PDF
+    invoices:           Vec<serde_json::Value>,
+
+    /// Handover of a pickup order, with the staff member who handed it over
+    pickup:             Option<serde_json::Value>,
+
+    /// Shipments registered with a carrier, without their label
+    shipments:          Vec<serde_json::Value>,
+
+    /// Fulfillment workflow state and assignee of the order
+    workflow:           Option<serde_json::Value>,
+
+    /// Workflow transitions, oldest first, with the staff members who made them
+    workflow_history:   Vec<serde_json::Value>,
+
+    /// Tags on the order, with who added them
+    tags:               Vec<serde_json::Value>,
+
+    /// Fraud risk score of the order, with the signals it is based on
+    risk_score:         Option<serde_json::Value>,
+
+    /// Outbound webhook deliveries of the order, with the order as it was sent
+    webhook_deliveries: Vec<serde_json::Value>
+}
+
+/// Summary of an erasure
+#[derive(Serialize)]
+pub struct ErasureResult {
+    request_id:         String,
+    orders_erased:      usize,
+    addresses_erased:   usize,
+    customers_erased:   usize
+}
+
+/**
+Export all data stored about a person
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the person ordered from
+    **email** The person's email address
+
+## Returns
+    **Ok**: The data export
+    **Err**: A summary of what went wrong
+*/
+pub fn export_data(database: Database, instance_id: String, email: String) -> Result<DataExport, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+
+    let customers = select_json(&mut conn, "SELECT * FROM customers WHERE instance_id = :instance_id AND LOWER(email) = LOWER(:email)", params! {
+        "instance_id" => instance_id.clone(),
+        "email" => email.clone()
+    })?;
+
+    let lifetime_values = select_json(&mut conn, "SELECT v.* FROM customer_lifetime_values v INNER JOIN customers c ON c.customer_id = v.customer_id \
+        WHERE c.instance_id = :instance_id AND LOWER(c.email) = LOWER(:email)", params! {
+        "instance_id" => instance_id.clone(),
+        "email" => email.clone()
+    })?;
+
+    let order_rows = conn.exec::<Row, &str, Params>("SELECT * FROM orders WHERE instance_id = :instance_id AND LOWER(buyer_email) = LOWER(:email) ORDER BY order_date ASC", params! {
+        "instance_id" => instance_id.clone(),
+        "email" => email.clone()
+    });
+
+    if order_rows.is_err() {
+        return Err(order_rows.err().unwrap().to_string());
+    }
+
+    let mut orders: Vec<ExportedOrder> = Vec::new();
+    for row in order_rows.unwrap() {
+        let order_id: String = row.get("order_id").unwrap();
+        let billing_address_id: Option<String> = row.get("billing_address_id").unwrap();
+        let shipping_address_id: Option<String> = row.get("shipping_address_id").unwrap();
+
+        let billing_address = match billing_address_id {
+            Some(address_id) => select_address(&mut conn, address_id)?,
+            None => None
+        };
+
+        let shipping_address = match shipping_address_id {
+            Some(address_id) => select_address(&mut conn, address_id)?,
+            None => None
+        };
+
+        let items = select_json(&mut conn, "SELECT * FROM order_items WHERE order_id = :order_id", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        //Merchant comments can contain personal data as well
+        let comments = select_json(&mut conn, "SELECT * FROM order_mutations WHERE order_id = :order_id AND mutation_type = 'MERCHANT_COMMENT'", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        let invoices = select_json(&mut conn, "SELECT invoice_id, invoice_number, invoice_type, credited_invoice_id, refund_id, issued_at, currency, net_total, tax_total, gross_total \
+            FROM invoices WHERE order_id = :order_id ORDER BY sequence_number ASC", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        let pickup = select_json(&mut conn, "SELECT * FROM order_pickups WHERE order_id = :order_id", params! {
+            "order_id" => order_id.clone()
+        })?.into_iter().next();
+
+        let shipments = select_json(&mut conn, "SELECT shipment_id, carrier, status, tracking_number, tracking_url, error, created_at \
+            FROM shipments WHERE order_id = :order_id ORDER BY created_at ASC", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        let workflow = select_json(&mut conn, "SELECT * FROM order_workflow WHERE order_id = :order_id", params! {
+            "order_id" => order_id.clone()
+        })?.into_iter().next();
+
+        let workflow_history = select_json(&mut conn, "SELECT * FROM order_workflow_history WHERE order_id = :order_id ORDER BY changed_at ASC", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        let tags = select_json(&mut conn, "SELECT * FROM order_tags WHERE order_id = :order_id ORDER BY tag ASC", params! {
+            "order_id" => order_id.clone()
+        })?;
+
+        let risk_score = select_json(&mut conn, "SELECT * FROM order_risk_scores WHERE order_id = :order_id", params! {
+            "order_id" => order_id.clone()
+        })?.into_iter().next();
+
+        let webhook_deliveries = select_json(&mut conn, "SELECT * FROM webhook_deliveries WHERE order_id = :order_id ORDER BY created_at ASC", params! {
+            "order_id" => order_id
+        })?;
+
+        orders.push(ExportedOrder {
+            order: row_to_json(&row),
+            billing_address,
+            shipping_address,
+            items,
+            comments,
+            invoices,
+            pickup,
+            shipments,
+            workflow,
+            workflow_history,
+            tags,
+            risk_score,
+            webhook_deliveries
+        });
+    }
+
+    Ok(DataExport {
+        instance_id,
+        email,
+        exported_at: chrono::Utc::now().timestamp(),
+        customers,
+        lifetime_values,
+        orders
+    })
+}
+
+/**
+Anonymize all personal data stored about a person.
+Financial data (totals, tax, line item prices) and the address country are kept, as they are needed for bookkeeping.
+Issued invoices and credit notes are kept unchanged, they have to be retained for bookkeeping as well.
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the person ordered from
+    **email** The person's email address
+
+## Returns
+    **Ok**: A summary of what was erased
+    **Err**: A summary of what went wrong
+*/
+pub fn erase_data(database: Database, instance_id: String, email: String) -> Result<ErasureResult, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+
+    let order_rows = conn.exec::<Row, &str, Params>("SELECT order_id, billing_address_id, shipping_address_id FROM orders WHERE instance_id = :instance_id AND LOWER(buyer_email) = LOWER(:email)", params! {
+        "instance_id" => instance_id.clone(),
+        "email" => email.clone()
+    });
+
+    if order_rows.is_err() {
+        return Err(order_rows.err().unwrap().to_string());
+    }
+
+    let (order_ids, address_ids) = order_and_address_ids(order_rows.unwrap());
+
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    anonymize_orders(&mut tx, &order_ids, &address_ids)?;
+
+    //The customer key may be the email address, replace it with the customer ID so it is no longer personal data
+    let result = tx.exec_drop("UPDATE customers SET email = :erased, name = :erased, phone = :erased, wix_customer_id = :erased, customer_key = customer_id \
+        WHERE instance_id = :instance_id AND LOWER(email) = LOWER(:email)", params! {
+        "erased" => ERASED,
+        "instance_id" => instance_id.clone(),
+        "email" => email
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let customers_erased = tx.affected_rows() as usize;
+
+    //Keep a record that the request was carried out, without storing who it was for
+    let request_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let result = tx.exec_drop("INSERT INTO gdpr_erasures (request_id, instance_id, erased_at, orders_erased, addresses_erased, customers_erased) \
+        VALUES (:request_id, :instance_id, :erased_at, :orders_erased, :addresses_erased, :customers_erased)", params! {
+        "request_id" => request_id.clone(),
+        "instance_id" => instance_id,
+        "erased_at" => chrono::Utc::now().timestamp(),
+        "orders_erased" => order_ids.len(),
+        "addresses_erased" => address_ids.len(),
+        "customers_erased" => customers_erased
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    if let Err(e) = tx.commit() {
+        return Err(e.to_string());
+    }
+
+    Ok(ErasureResult {
+        request_id,
+        orders_erased: order_ids.len(),
+        addresses_erased: address_ids.len(),
+        customers_erased
+    })
+}
+
+/// Kind of GDPR request, as recorded in the audit trail
+#[derive(Clone, Copy)]
+pub enum RequestType {
+    Export,
+    Erasure
+}
+
+impl RequestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestType::Export => "EXPORT",
+            RequestType::Erasure => "ERASURE"
+        }
+    }
+}
+
+/**
+Record an export or erasure request in the audit trail, whether it succeeded or not.
+The email address is stored as a SHA-256 hash of its lowercase form, so the trail holds no personal data after an erasure
+while the requests for a person can still be found
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the request was made for
+    **request_type** What was requested
+    **requested_by** User ID of the admin who made the request
+    **email** The email address of the data subject
+    **error** Why the request failed, None if it succeeded
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn audit_request(database: Database, instance_id: String, request_type: RequestType, requested_by: String, email: &str, error: Option<&String>) -> Result<(), String> {
+    let audit_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let email_hash: String = Sha256::digest(email.trim().to_lowercase().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("INSERT INTO gdpr_audit_log (audit_id, instance_id, request_type, email_hash, requested_by, requested_at, succeeded, error) \
+        VALUES (:audit_id, :instance_id, :request_type, :email_hash, :requested_by, :requested_at, :succeeded, :error)", params! {
+        "audit_id" => audit_id,
+        "instance_id" => instance_id,
+        "request_type" => request_type.as_str(),
+        "email_hash" => email_hash,
+        "requested_by" => requested_by,
+        "requested_at" => chrono::Utc::now().timestamp(),
+        "succeeded" => error.is_none(),
+        "error" => error
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Anonymize the personal data on orders and their addresses, leaving financial data intact
+
+## Params
+    **tx** The transaction to run the updates in
+    **order_ids** The orders to anonymize
+    **address_ids** The billing and shipping addresses of these orders
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn anonymize_orders(tx: &mut Transaction, order_ids: &[String], address_ids: &[String]) -> Result<(), String> {
+    for address_id in address_ids {
+        let result = tx.exec_drop("UPDATE addresses SET city = :erased, zip_code = :erased, address_line_1 = :erased, address_line_2 = :erased, street = :erased, house_number = '', house_number_addition = '', \
+            full_name = :erased, company = :erased, email = :erased, phone = :erased WHERE address_id = :address_id", params! {
+            "erased" => ERASED,
+            "address_id" => address_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    for order_id in order_ids {
+        let result = tx.exec_drop("UPDATE orders SET buyer_email = :erased, buyer_name = :erased, buyer_phone = :erased, buyer_note = :erased WHERE order_id = :order_id", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        let result = tx.exec_drop("UPDATE order_mutations SET payload = :erased WHERE order_id = :order_id AND mutation_type = 'MERCHANT_COMMENT'", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        //Shipping labels are printed with the recipient's name and address
+        let result = tx.exec_drop("UPDATE shipments SET label = NULL WHERE order_id = :order_id", params! {
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        //Pickups and the workflow history record names typed in by staff
+        let result = tx.exec_drop("UPDATE order_pickups SET handed_over_by = :erased WHERE order_id = :order_id", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        let result = tx.exec_drop("UPDATE order_workflow_history SET assignee = IF(assignee IS NULL, NULL, :erased), changed_by = :erased WHERE order_id = :order_id", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        //Rule notifications and the outcomes of rule actions can contain the order's details as rendered for staff
+        let result = tx.exec_drop("UPDATE rule_notifications SET message = :erased WHERE order_id = :order_id", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        let result = tx.exec::<(String, String), &str, Params>("SELECT evaluation_id, actions FROM rule_evaluations WHERE order_id = :order_id", params! {
+            "order_id" => order_id
+        });
+
+        let evaluations = match result {
+            Ok(evaluations) => evaluations,
+            Err(e) => return Err(e.to_string())
+        };
+
+        for (evaluation_id, actions) in evaluations {
+            let result = tx.exec_drop("UPDATE rule_evaluations SET actions = :actions WHERE evaluation_id = :evaluation_id", params! {
+                "actions" => erase_action_outcomes(&actions)?,
+                "evaluation_id" => evaluation_id
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+        }
+
+        let result = tx.exec_first::<String, &str, Params>("SELECT signals FROM order_risk_scores WHERE order_id = :order_id", params! {
+            "order_id" => order_id
+        });
+
+        let signals = match result {
+            Ok(signals) => signals,
+            Err(e) => return Err(e.to_string())
+        };
+
+        if let Some(signals) = signals {
+            let result = tx.exec_drop("UPDATE order_risk_scores SET signals = :signals WHERE order_id = :order_id", params! {
+                "signals" => erase_risk_signals(&signals)?,
+                "order_id" => order_id
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+        }
+
+        //Outbound webhook deliveries hold the order as it was sent, pending ones are no longer sent
+        let result = tx.exec_drop("UPDATE webhook_deliveries SET payload = :erased, status = IF(status = 'PENDING', 'FAILED', status), next_attempt_at = NULL \
+            WHERE order_id = :order_id", params! {
+            "erased" => ERASED,
+            "order_id" => order_id
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/**
+Erase the errors of rule actions, which can echo the order's details. The descriptions of the actions come from the rule and are kept
+
+## Params
+    **actions** The JSON array of ActionOutcome stored with a rule evaluation
+
+## Returns
+    **Ok**: The JSON array with the errors erased
+    **Err**: If the actions could not be parsed
+*/
+pub fn erase_action_outcomes(actions: &str) -> Result<String, String> {
+    let mut outcomes: Vec<ActionOutcome> = match serde_json::from_str(actions) {
+        Ok(outcomes) => outcomes,
+        Err(e) => return Err(format!("Unable to parse the rule actions: {}", e))
+    };
+
+    for outcome in outcomes.iter_mut() {
+        if outcome.error.is_some() {
+            outcome.error = Some(ERASED.to_string());
+        }
+    }
+
+    Ok(serde_json::to_string(&outcomes).unwrap())
+}
+
+/**
+Erase the explanations of risk signals, keeping the signals and their points so the score still adds up
+
+## Params
+    **signals** The JSON array of RiskSignal stored with a risk score
+
+## Returns
+    **Ok**: The JSON array with the explanations erased
+    **Err**: If the signals could not be parsed
+*/
+pub fn erase_risk_signals(signals: &str) -> Result<String, String> {
+    let mut signals: Vec<RiskSignal> = match serde_json::from_str(signals) {
+        Ok(signals) => signals,
+        Err(e) => return Err(format!("Unable to parse the risk signals: {}", e))
+    };
+
+    for signal in signals.iter_mut() {
+        signal.explanation = ERASED.to_string();
+    }
+
+    Ok(serde_json::to_string(&signals).unwrap())
+}
+
+/**
+Split rows with the columns order_id, billing_address_id and shipping_address_id into
+the order IDs and the distinct address IDs, as needed by anonymize_orders
+*/
+pub fn order_and_address_ids(rows: Vec<Row>) -> (Vec<String>, Vec<String>) {
+    let mut order_ids: Vec<String> = Vec::new();
+    let mut address_ids: Vec<String> = Vec::new();
+    for row in rows {
+        order_ids.push(row.get("order_id").unwrap());
+
+        if let Some(address_id) = row.get::<Option<String>, &str>("billing_address_id").unwrap() {
+            address_ids.push(address_id);
+        }
+
+        if let Some(address_id) = row.get::<Option<String>, &str>("shipping_address_id").unwrap() {
+            address_ids.push(address_id);
+        }
+    }
+
+    address_ids.sort();
+    address_ids.dedup();
+
+    (order_ids, address_ids)
+}
+
+fn select_address(conn: &mut mysql::PooledConn, address_id: String) -> Result<Option<serde_json::Value>, String> {
+    let addresses = select_json(conn, "SELECT * FROM addresses WHERE address_id = :address_id", params! {
+        "address_id" => address_id
+    })?;
+
+    Ok(addresses.into_iter().next())
+}
+
+fn select_json(conn: &mut mysql::PooledConn, query: &str, params: Params) -> Result<Vec<serde_json::Value>, String> {
+    let result = conn.exec::<Row, &str, Params>(query, params);
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().iter().map(row_to_json).collect())
+}
+
+/// Convert a row to a JSON object, so the export contains every column without listing them here
+fn row_to_json(row: &Row) -> serde_json::Value {
+    let mut object = serde_json::Map::new();
+    for (i, column) in row.columns_ref().iter().enumerate() {
+        let value = match row.as_ref(i) {
+            None | Some(Value::NULL) => serde_json::Value::Null,
+            Some(Value::Bytes(bytes)) => serde_json::Value::String(String::from_utf8_lossy(bytes).to_string()),
+            Some(Value::Int(i)) => serde_json::json!(i),
+            Some(Value::UInt(u)) => serde_json::json!(u),
+            Some(Value::Float(f)) => serde_json::json!(f),
+            Some(Value::Double(d)) => serde_json::json!(d),
+            Some(value) => serde_json::Value::String(value.as_sql(true).trim_matches('\'').to_string())
+        };
+
+        object.insert(column.name_str().to_string(), value);
+    }
+
+    serde_json::Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUYER: [&str; 2] = ["Jan Jansen", "jan@example.com"];
+
+    #[test]
+    fn action_errors_are_erased() {
+        let actions = serde_json::to_string(&vec![
+            ActionOutcome { action: "tag 'customs'".to_string(), error: None },
+            ActionOutcome { action: "call webhook https://example.com/hook".to_string(), error: Some("Rejected order of Jan Jansen <jan@example.com>".to_string()) }
+        ]).unwrap();
+
+        let erased = erase_action_outcomes(&actions).unwrap();
+        assert!(BUYER.iter().all(|data| !erased.contains(data)));
+
+        let outcomes: Vec<ActionOutcome> = serde_json::from_str(&erased).unwrap();
+        assert_eq!(outcomes[0].action, "tag 'customs'");
+        assert_eq!(outcomes[0].error, None);
+        assert_eq!(outcomes[1].error.as_deref(), Some(ERASED));
+    }
+
+    #[test]
+    fn risk_explanations_are_erased() {
+        let signals = serde_json::to_string(&vec![
+            RiskSignal { signal: "NAME_MISMATCH".to_string(), points: 20, explanation: "Ordered by Jan Jansen, shipped to P. de Vries".to_string() },
+            RiskSignal { signal: "ORDER_VELOCITY".to_string(), points: 30, explanation: "4 orders by jan@example.com within 24 hours".to_string() }
+        ]).unwrap();
+
+        let erased = erase_risk_signals(&signals).unwrap();
+        assert!(BUYER.iter().all(|data| !erased.contains(data)));
+
+        let signals: Vec<RiskSignal> = serde_json::from_str(&erased).unwrap();
+        assert_eq!(signals.iter().map(|signal| signal.points).sum::<i64>(), 50);
+        assert!(signals.iter().all(|signal| signal.explanation == ERASED));
+    }
+
+    #[test]
+    fn unparseable_json_is_an_error() {
+        assert!(erase_action_outcomes("not json").is_err());
+        assert!(erase_risk_signals("not json").is_err());
+    }
+}
@@ -0,0 +1,626 @@
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, Transaction, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+use rust_decimal::{Decimal, RoundingStrategy};
+use tera::{Tera, Context};
+
+use crate::database::Database;
+use crate::documents::html_to_pdf;
+use crate::orders::get_order_details;
+use crate::types::money::Money;
+use crate::types::order::OrderDetails;
+
+const INVOICE_TEMPLATE: &str = "documents/invoice.html";
+
+/// Payment statuses of orders which have been paid, and can therefore be invoiced
+const INVOICEABLE_PAYMENT_STATUSES: &[&str] = &["Paid", "PartiallyRefunded", "FullyRefunded"];
+
+/// Tax group ID used for the shipping costs in a tax breakdown
+pub const SHIPPING_TAX_GROUP: &str = "shipping";
+
+/// Invoice settings of an instance. The seller details are printed on every invoice
+#[derive(Serialize, Deserialize)]
+pub struct InvoiceSettings {
+    pub instance_id:        String,
+
+    /// Prepended to the sequence number, e.g. 'INV-'
+    pub prefix:             String,
+    pub company_name:       String,
+
+    /// Street and house number
+    pub company_street:     String,
+    pub company_zip_code:   String,
+    pub company_city:       String,
+
+    /// Country code (2 letters)
+    pub company_country:    String,
+    pub vat_number:         String,
+
+    /// Chamber of commerce (KvK) registration number
+    pub coc_number:         String,
+    pub iban:               String
+}
+
+/// Whether a document is an invoice or a credit note
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum InvoiceType {
+    Invoice,
+    CreditNote
+}
+
+impl InvoiceType {
+    fn from_str(value: &str) -> InvoiceType {
+        match value {
+            "CREDIT_NOTE" => InvoiceType::CreditNote,
+            _ => InvoiceType::Invoice
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceType::Invoice => "INVOICE",
+            InvoiceType::CreditNote => "CREDIT_NOTE"
+        }
+    }
+}
+
+/// Tax charged at a single rate within a tax group
+#[derive(Serialize, Clone)]
+pub struct TaxLine {
+    pub tax_group_id:   String,
+
+    /// Tax rate in percent, e.g. '21' or '5.5'
+    pub rate:           Decimal,
+
+    /// Amount the tax was charged over, after discounts
+    pub net:            Decimal,
+    pub tax:            Decimal
+}
+
+/// An issued invoice or credit note
+#[derive(Serialize)]
+pub struct Invoice {
+    pub invoice_id:             String,
+    pub instance_id:            String,
+    pub order_id:               String,
+    pub invoice_number:         String,
+    pub invoice_type:           InvoiceType,
+
+    /// For credit notes, the invoice being credited
+    pub credited_invoice_id:    Option<String>,
+
+    /// For credit notes, the refund the credit note was issued for
+    pub refund_id:              Option<String>,
+    pub issued_at:              i64,
+    pub currency:               String,
+    pub net_total:              Decimal,
+    pub tax_total:              Decimal,
+    pub gross_total:            Decimal,
+    pub tax_lines:              Vec<TaxLine>
+}
+
+/**
+Get the invoice settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the settings for
+
+## Returns
+    **Ok**: The settings, or None if invoicing isn't set up for the instance
+    **Err**: A summary of what went wrong
+*/
+pub fn get_settings(database: Database, instance_id: String) -> Result<Option<InvoiceSettings>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT instance_id, prefix, company_name, company_street, company_zip_code, company_city, company_country, vat_number, coc_number, iban FROM invoice_settings WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().first().map(settings_from_row))
+}
+
+/**
+Create or update the invoice settings of an instance.
+The numbering sequence starts at 1 when the settings are first created, and can't be changed afterwards.
+
+## Params
+    **database** Instance of a Database object
+    **settings** The new settings
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn set_settings(database: Database, settings: InvoiceSettings) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO invoice_settings (instance_id, prefix, company_name, company_street, company_zip_code, company_city, company_country, vat_number, coc_number, iban, next_number) \
+        VALUES (:instance_id, :prefix, :company_name, :company_street, :company_zip_code, :company_city, :company_country, :vat_number, :coc_number, :iban, 1) \
+        ON DUPLICATE KEY UPDATE prefix = :prefix, company_name = :company_name, company_street = :company_street, company_zip_code = :company_zip_code, company_city = :company_city, company_country = :company_country, vat_number = :vat_number, coc_number = :coc_number, iban = :iban", params! {
+        "instance_id" => settings.instance_id,
+        "prefix" => settings.prefix,
+        "company_name" => settings.company_name,
+        "company_street" => settings.company_street,
+        "company_zip_code" => settings.company_zip_code,
+        "company_city" => settings.company_city,
+        "company_country" => settings.company_country,
+        "vat_number" => settings.vat_number,
+        "coc_number" => settings.coc_number,
+        "iban" => settings.iban
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Calculate the tax per tax group and rate for an order.
+The rate of a line item is derived from its tax and price, shipping is taxed with whatever
+order tax remains after the line items. Each item is rounded to the currency's minor units before it's added to its line.
+The total of an item is what Wix charged for it, after the item's discount, so the net amounts are net of discounts:
+the invoice shows what the buyer paid, and the journal books the discounts separately from the revenue before discounts.
+
+## Params
+    **details** The order
+
+## Returns
+    The tax lines, one per distinct tax group and rate
+*/
+pub fn tax_breakdown(details: &OrderDetails) -> Vec<TaxLine> {
+    let order = &details.order;
+    let mut lines: Vec<TaxLine> = Vec::new();
+
+    for item in &details.items {
+        let net = if item.tax_included_in_price { item.total - item.tax } else { item.total };
+        add_tax_line(&mut lines, &item.tax_group_id, order.money(net), order.money(item.tax));
+    }
+
+    if order.shipping > Decimal::ZERO {
+        let items_tax: Decimal = details.items.iter().map(|item| item.tax).sum();
+        add_tax_line(&mut lines, SHIPPING_TAX_GROUP, order.money(order.shipping), order.money(order.tax - items_tax));
+    }
+
+    lines
+}
+
+/**
+Derive a tax rate from an amount and the tax charged over it
+
+## Params
+    **net** The amount the tax was charged over
+    **tax** The tax
+
+## Returns
+    The rate in percent, with at most one decimal. 0 if the amount is 0
+*/
+pub fn tax_rate(net: Decimal, tax: Decimal) -> Decimal {
+    match (tax * Decimal::ONE_HUNDRED).checked_div(net) {
+        Some(rate) => rate.round_dp_with_strategy(1, RoundingStrategy::MidpointAwayFromZero).normalize(),
+        None => Decimal::ZERO
+    }
+}
+
+fn add_tax_line(lines: &mut Vec<TaxLine>, tax_group_id: &str, net: Money, tax: Money) {
+    let rate = tax_rate(net.amount, tax.amount);
+    let (net, tax) = (net.round().amount, tax.round().amount);
+
+    match lines.iter_mut().find(|line| line.tax_group_id == tax_group_id && line.rate == rate) {
+        Some(line) => {
+            line.net += net;
+            line.tax += tax;
+        },
+        None => lines.push(TaxLine {
+            tax_group_id: tax_group_id.to_string(),
+            rate,
+            net,
+            tax
+        })
+    }
+}
+
+/**
+Issue an invoice for a paid order
+
+## Params
+    **database** Instance of a Database object
+    **tera** The Tera instance holding the invoice template
+    **instance_id** The instance the order belongs to
+    **order_id** The order to invoice
+
+## Returns
+    **Ok**: The invoice
+    **Err**: A summary of what went wrong
+*/
+pub fn issue_invoice(database: Database, tera: &Tera, instance_id: String, order_id: String) -> Result<Invoice, String> {
+    let details = match get_order_details(database.clone(), &order_id)? {
+        Some(details) if details.order.instance_id == instance_id => details,
+        _ => return Err(format!("No order with ID {} in instance {}", order_id, instance_id))
+    };
+
+    if !INVOICEABLE_PAYMENT_STATUSES.contains(&details.order.payment_status.as_str()) {
+        return Err(format!("Order {} has not been paid", order_id));
+    }
+
+    let tax_lines = tax_breakdown(&details);
+    let mut invoice = new_invoice(&details, InvoiceType::Invoice, tax_lines)?;
+    store_invoice(database, tera, &mut invoice, &details, None)?;
+
+    Ok(invoice)
+}
+
+/**
+Issue a credit note for a refund of an invoiced order.
+The tax lines of the original invoice are scaled to the refunded amount.
+
+## Params
+    **database** Instance of a Database object
+    **tera** The Tera instance holding the invoice template
+    **instance_id** The instance the order belongs to
+    **order_id** The refunded order
+    **refund_id** The Wix refund to credit
+
+## Returns
+    **Ok**: The credit note
+    **Err**: A summary of what went wrong
+*/
+pub fn issue_credit_note(database: Database, tera: &Tera, instance_id: String, order_id: String, refund_id: String) -> Result<Invoice, String> {
+    let details = match get_order_details(database.clone(), &order_id)? {
+        Some(details) if details.order.instance_id == instance_id => details,
+        _ => return Err(format!("No order with ID {} in instance {}", order_id, instance_id))
+    };
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let refund = conn.exec_first::<Row, &str, Params>("SELECT amount FROM order_refunds WHERE refund_id = :refund_id AND order_id = :order_id", params! {
+        "refund_id" => refund_id.clone(),
+        "order_id" => order_id.clone()
+    });
+
+    let refund_amount: Decimal = match refund {
+        Ok(Some(row)) => row.get("amount").unwrap(),
+        Ok(None) => return Err(format!("No refund with ID {} for order {}", refund_id, order_id)),
+        Err(e) => return Err(e.to_string())
+    };
+
+    let original = match find_invoice_ids(database.clone(), "SELECT invoice_id FROM invoices WHERE order_id = :key AND invoice_type = 'INVOICE'", order_id.clone())?.pop() {
+        Some(invoice_id) => get_invoice(database.clone(), invoice_id)?.unwrap(),
+        None => return Err(format!("Order {} has not been invoiced", order_id))
+    };
+
+    //Scale the original tax lines to the refunded amount, and put any rounding difference on the largest line
+    let factor = refund_amount.checked_div(original.gross_total).unwrap_or_default();
+    let mut tax_lines: Vec<TaxLine> = original.tax_lines.iter().map(|line| TaxLine {
+        tax_group_id: line.tax_group_id.clone(),
+        rate: line.rate,
+        net: -details.order.money(line.net * factor).round().amount,
+        tax: -details.order.money(line.tax * factor).round().amount
+    }).collect();
+
+    let difference = details.order.money(-refund_amount - tax_lines.iter().map(|line| line.net + line.tax).sum::<Decimal>()).round().amount;
+    if !difference.is_zero() {
+        if let Some(largest) = tax_lines.iter_mut().max_by_key(|line| line.net.abs()) {
+            largest.net += difference;
+        }
+    }
+
+    let mut credit_note = new_invoice(&details, InvoiceType::CreditNote, tax_lines)?;
+    credit_note.credited_invoice_id = Some(original.invoice_id);
+    credit_note.refund_id = Some(refund_id);
+    store_invoice(database, tera, &mut credit_note, &details, Some(original.invoice_number))?;
+
+    Ok(credit_note)
+}
+
+/**
+Get an invoice or credit note
+
+## Params
+    **database** Instance of a Database object
+    **invoice_id** The invoice to get
+
+## Returns
+    **Ok**: The invoice, or None if it doesn't exist
+    **Err**: A summary of what went wrong
+*/
+pub fn get_invoice(database: Database, invoice_id: String) -> Result<Option<Invoice>, String> {
+    let mut invoices = query_invoices(database, "WHERE invoice_id = :invoice_id", params! {
+        "invoice_id" => invoice_id
+    })?;
+    Ok(invoices.pop())
+}
+
+/**
+Get all invoices and credit notes of an instance, in numbering order
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the invoices for
+
+## Returns
+    **Ok**: The invoices
+    **Err**: A summary of what went wrong
+*/
+pub fn get_invoices(database: Database, instance_id: String) -> Result<Vec<Invoice>, String> {
+    query_invoices(database, "WHERE instance_id = :instance_id ORDER BY sequence_number ASC", params! {
+        "instance_id" => instance_id
+    })
+}
+
+/**
+Get the invoice issued for an order, excluding its credit notes
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the order belongs to
+    **order_id** The order to get the invoice for
+
+## Returns
+    **Ok**: The invoice, or None if the order wasn't invoiced
+    **Err**: A summary of what went wrong
+*/
+pub fn get_order_invoice(database: Database, instance_id: String, order_id: String) -> Result<Option<Invoice>, String> {
+    let mut invoices = query_invoices(database, "WHERE instance_id = :instance_id AND order_id = :order_id AND invoice_type = 'INVOICE'", params! {
+        "instance_id" => instance_id,
+        "order_id" => order_id
+    })?;
+    Ok(invoices.pop())
+}
+
+/**
+Get the invoices and credit notes of an instance issued within a period, in numbering order
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the invoices for
+    **from** Start of the period, epoch seconds
+    **to** End of the period (exclusive), epoch seconds
+
+## Returns
+    **Ok**: The invoices
+    **Err**: A summary of what went wrong
+*/
+pub fn get_invoices_issued(database: Database, instance_id: String, from: i64, to: i64) -> Result<Vec<Invoice>, String> {
+    query_invoices(database, "WHERE instance_id = :instance_id AND issued_at >= :from AND issued_at < :to ORDER BY sequence_number ASC", params! {
+        "instance_id" => instance_id,
+        "from" => from,
+        "to" => to
+    })
+}
+
+/**
+Get the stored PDF of an invoice or credit note
+
+## Params
+    **database** Instance of a Database object
+    **invoice_id** The invoice to get the PDF for
+
+## Returns
+    **Ok**: The PDF, or None if the invoice doesn't exist
+    **Err**: A summary of what went wrong
+*/
+pub fn get_invoice_pdf(database: Database, invoice_id: String) -> Result<Option<Vec<u8>>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT pdf FROM invoices WHERE invoice_id = :invoice_id", params! {
+        "invoice_id" => invoice_id
+    });
+
+    match result {
+        Ok(row) => Ok(row.map(|row| row.get("pdf").unwrap())),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn new_invoice(details: &OrderDetails, invoice_type: InvoiceType, tax_lines: Vec<TaxLine>) -> Result<Invoice, String> {
+    let currency = &details.order.currency;
+    let nets: Vec<Money> = tax_lines.iter().map(|line| Money::new(line.net, currency)).collect();
+    let taxes: Vec<Money> = tax_lines.iter().map(|line| Money::new(line.tax, currency)).collect();
+    let net_total = Money::sum(&nets, currency)?;
+    let tax_total = Money::sum(&taxes, currency)?;
+    let gross_total = net_total.add(&tax_total)?;
+
+    Ok(Invoice {
+        invoice_id: rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect(),
+        instance_id: details.order.instance_id.clone(),
+        order_id: details.order.order_id.clone(),
+        invoice_number: String::new(),
+        invoice_type,
+        credited_invoice_id: None,
+        refund_id: None,
+        issued_at: chrono::Utc::now().timestamp(),
+        currency: details.order.currency.clone(),
+        net_total: net_total.amount,
+        tax_total: tax_total.amount,
+        gross_total: gross_total.amount,
+        tax_lines
+    })
+}
+
+/**
+Assign the next number in the instance's sequence to an invoice, render it and store it.
+The sequence row is locked for the duration of the transaction, and the number is only used
+if the invoice is stored, so the sequence has no gaps. The lock also serializes issuing, so the check
+that the order or refund wasn't invoiced yet is done after taking it.
+*/
+fn store_invoice(database: Database, tera: &Tera, invoice: &mut Invoice, details: &OrderDetails, credited_invoice_number: Option<String>) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let settings = tx.exec_first::<Row, &str, Params>("SELECT instance_id, prefix, company_name, company_street, company_zip_code, company_city, company_country, vat_number, coc_number, iban, next_number \
+        FROM invoice_settings WHERE instance_id = :instance_id FOR UPDATE", params! {
+        "instance_id" => invoice.instance_id.clone()
+    });
+
+    let (settings, sequence_number) = match settings {
+        Ok(Some(row)) => (settings_from_row(&row), row.get::<i64, &str>("next_number").unwrap()),
+        Ok(None) => return Err(format!("Invoicing is not set up for instance {}", invoice.instance_id)),
+        Err(e) => return Err(e.to_string())
+    };
+
+    //Another request may have issued the same invoice while we were waiting for the lock
+    let existing = match &invoice.refund_id {
+        Some(refund_id) => tx.exec_first::<String, &str, Params>("SELECT invoice_id FROM invoices WHERE refund_id = :refund_id", params! {
+            "refund_id" => refund_id.clone()
+        }),
+        None => tx.exec_first::<String, &str, Params>("SELECT invoice_id FROM invoices WHERE order_id = :order_id AND invoice_type = 'INVOICE'", params! {
+            "order_id" => invoice.order_id.clone()
+        })
+    };
+
+    match (existing, &invoice.refund_id) {
+        (Ok(None), _) => (),
+        (Ok(Some(_)), Some(refund_id)) => return Err(format!("A credit note has already been issued for refund {}", refund_id)),
+        (Ok(Some(_)), None) => return Err(format!("Order {} has already been invoiced", invoice.order_id)),
+        (Err(e), _) => return Err(e.to_string())
+    }
+
+    invoice.invoice_number = format!("{}{:06}", settings.prefix, sequence_number);
+
+    let mut ctx = Context::new();
+    ctx.insert("invoice", &invoice);
+    ctx.insert("credited_invoice_number", &credited_invoice_number);
+    ctx.insert("order", &details);
+    ctx.insert("seller", &settings);
+
+    let html = match tera.render(INVOICE_TEMPLATE, &ctx) {
+        Ok(html) => html,
+        Err(e) => return Err(format!("Unable to render invoice: {}", e))
+    };
+    let pdf = html_to_pdf(&html)?;
+
+    let result = tx.exec_drop("INSERT INTO invoices (invoice_id, instance_id, order_id, sequence_number, invoice_number, invoice_type, credited_invoice_id, refund_id, issued_at, currency, net_total, tax_total, gross_total, pdf) \
+        VALUES (:invoice_id, :instance_id, :order_id, :sequence_number, :invoice_number, :invoice_type, :credited_invoice_id, :refund_id, :issued_at, :currency, :net_total, :tax_total, :gross_total, :pdf)", params! {
+        "invoice_id" => invoice.invoice_id.clone(),
+        "instance_id" => invoice.instance_id.clone(),
+        "order_id" => invoice.order_id.clone(),
+        "sequence_number" => sequence_number,
+        "invoice_number" => invoice.invoice_number.clone(),
+        "invoice_type" => invoice.invoice_type.as_str(),
+        "credited_invoice_id" => invoice.credited_invoice_id.clone(),
+        "refund_id" => invoice.refund_id.clone(),
+        "issued_at" => invoice.issued_at,
+        "currency" => invoice.currency.clone(),
+        "net_total" => invoice.net_total,
+        "tax_total" => invoice.tax_total,
+        "gross_total" => invoice.gross_total,
+        "pdf" => pdf
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    insert_tax_lines(&mut tx, &invoice.invoice_id, &invoice.tax_lines)?;
+
+    let result = tx.exec_drop("UPDATE invoice_settings SET next_number = next_number + 1 WHERE instance_id = :instance_id", params! {
+        "instance_id" => invoice.instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn insert_tax_lines(tx: &mut Transaction, invoice_id: &str, tax_lines: &[TaxLine]) -> Result<(), String> {
+    for line in tax_lines {
+        let result = tx.exec_drop("INSERT INTO invoice_tax_lines (invoice_id, tax_group_id, rate, net, tax) VALUES (:invoice_id, :tax_group_id, :rate, :net, :tax)", params! {
+            "invoice_id" => invoice_id,
+            "tax_group_id" => line.tax_group_id.clone(),
+            "rate" => line.rate,
+            "net" => line.net,
+            "tax" => line.tax
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    Ok(())
+}
+
+fn find_invoice_ids(database: Database, query: &str, key: String) -> Result<Vec<String>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>(query, params! {
+        "key" => key
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| row.get("invoice_id").unwrap()).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn query_invoices(database: Database, condition: &str, params: Params) -> Result<Vec<Invoice>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(format!("SELECT invoice_id, instance_id, order_id, invoice_number, invoice_type, credited_invoice_id, refund_id, issued_at, currency, net_total, tax_total, gross_total \
+        FROM invoices {}", condition), params);
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut invoices = Vec::new();
+    for row in result.unwrap() {
+        let invoice_id: String = row.get("invoice_id").unwrap();
+
+        let tax_lines = conn.exec::<Row, &str, Params>("SELECT tax_group_id, rate, net, tax FROM invoice_tax_lines WHERE invoice_id = :invoice_id", params! {
+            "invoice_id" => invoice_id.clone()
+        });
+
+        if tax_lines.is_err() {
+            return Err(tax_lines.err().unwrap().to_string());
+        }
+
+        invoices.push(Invoice {
+            invoice_id,
+            instance_id:            row.get("instance_id").unwrap(),
+            order_id:               row.get("order_id").unwrap(),
+            invoice_number:         row.get("invoice_number").unwrap(),
+            invoice_type:           InvoiceType::from_str(&row.get::<String, &str>("invoice_type").unwrap()),
+            credited_invoice_id:    row.get("credited_invoice_id").unwrap(),
+            refund_id:              row.get("refund_id").unwrap(),
+            issued_at:              row.get("issued_at").unwrap(),
+            currency:               row.get("currency").unwrap(),
+            net_total:              row.get("net_total").unwrap(),
+            tax_total:              row.get("tax_total").unwrap(),
+            gross_total:            row.get("gross_total").unwrap(),
+            tax_lines:              tax_lines.unwrap().iter().map(|line| TaxLine {
+                tax_group_id:   line.get("tax_group_id").unwrap(),
+                rate:           line.get("rate").unwrap(),
+                net:            line.get("net").unwrap(),
+                tax:            line.get("tax").unwrap()
+            }).collect()
+        });
+    }
+
+    Ok(invoices)
+}
+
+fn settings_from_row(row: &Row) -> InvoiceSettings {
+    InvoiceSettings {
+        instance_id:        row.get("instance_id").unwrap(),
+        prefix:             row.get("prefix").unwrap(),
+        company_name:       row.get("company_name").unwrap(),
+        company_street:     row.get("company_street").unwrap(),
+        company_zip_code:   row.get("company_zip_code").unwrap(),
+        company_city:       row.get("company_city").unwrap(),
+        company_country:    row.get("company_country").unwrap(),
+        vat_number:         row.get("vat_number").unwrap(),
+        coc_number:         row.get("coc_number").unwrap(),
+        iban:               row.get("iban").unwrap()
+    }
+}
\ No newline at end of file
@@ -5,6 +5,29 @@ mod endpoints;
 mod types;
 mod auth;
 mod threads;
+mod wix;
+mod customers;
+mod gdpr;
+mod retention;
+mod order_filter;
+mod export;
+mod orders;
+mod documents;
+mod invoices;
+mod accounting;
+mod reports;
+mod product_analytics;
+mod exchange_rates;
+mod shipping;
+mod carriers;
+mod addresses;
+mod pickup;
+mod workflow;
+mod tags;
+mod saved_views;
+mod rules;
+mod fraud;
+mod outbound_webhooks;
 
 use actix_web::{HttpServer, App};
 use std::process::exit;
@@ -28,6 +51,12 @@ async fn main() -> std::io::Result<()> {
     //Create a database object
     let database = database::Database::new();
 
+    //Start enforcing data retention policies
+    threads::retention::start_retention_job(database.clone());
+
+    //Start sending outbound webhook deliveries
+    threads::webhook_delivery::start_delivery_job(database.clone());
+
     //Create a Tera instance
     let mut tera = Tera::new("templates/**/*").expect("Tera error!");
     tera.autoescape_on(vec![]);
@@ -54,6 +83,80 @@ async fn main() -> std::io::Result<()> {
             .service(endpoints::wix::get_grant::get_grant)
             .service(endpoints::wix::webhooks::post_order_created::post_order_created)
             //.service(endpoints::wix::webhooks::post_order_paid)
+            .service(endpoints::orders::post_mark_paid::post_mark_paid)
+            .service(endpoints::orders::post_comment::post_comment)
+            .service(endpoints::orders::get_conflicts::get_conflicts)
+            .service(endpoints::orders::post_resolve_conflict::post_resolve_conflict)
+            .service(endpoints::customers::get_customers::get_customers)
+            .service(endpoints::customers::get_customer_orders::get_customer_orders)
+            .service(endpoints::gdpr::get_export::get_export)
+            .service(endpoints::gdpr::post_erase::post_erase)
+            .service(endpoints::retention::get_policy::get_policy)
+            .service(endpoints::retention::post_policy::post_policy)
+            .service(endpoints::retention::get_dry_run::get_dry_run)
+            .service(endpoints::export::get_orders::get_orders)
+            .service(endpoints::packing_slips::get_packing_slip::get_packing_slip)
+            .service(endpoints::packing_slips::get_settings::get_settings)
+            .service(endpoints::packing_slips::post_settings::post_settings)
+            .service(endpoints::packing_slips::get_packing_slips::get_packing_slips)
+            .service(endpoints::invoices::post_invoice::post_invoice)
+            .service(endpoints::invoices::post_credit_note::post_credit_note)
+            .service(endpoints::invoices::get_settings::get_settings)
+            .service(endpoints::invoices::post_settings::post_settings)
+            .service(endpoints::invoices::get_invoices::get_invoices)
+            .service(endpoints::invoices::get_invoice_pdf::get_invoice_pdf)
+            .service(endpoints::invoices::get_ubl_archive::get_ubl_archive)
+            .service(endpoints::invoices::get_invoice_ubl::get_invoice_ubl)
+            .service(endpoints::invoices::get_order_ubl::get_order_ubl)
+            .service(endpoints::accounting::get_accounts::get_accounts)
+            .service(endpoints::accounting::post_accounts::post_accounts)
+            .service(endpoints::export::get_journal::get_journal)
+            .service(endpoints::reports::get_sales::get_sales)
+            .service(endpoints::reports::get_settings::get_settings)
+            .service(endpoints::reports::post_settings::post_settings)
+            .service(endpoints::reports::get_product_ranking::get_product_ranking)
+            .service(endpoints::reports::get_custom_amounts::get_custom_amounts)
+            .service(endpoints::reports::get_products::get_products)
+            .service(endpoints::exchange_rates::get_rates::get_rates)
+            .service(endpoints::exchange_rates::post_rates::post_rates)
+            .service(endpoints::shipping::get_manifest::get_manifest)
+            .service(endpoints::shipping::get_settings::get_settings)
+            .service(endpoints::shipping::post_settings::post_settings)
+            .service(endpoints::shipping::post_shipment::post_shipment)
+            .service(endpoints::shipping::get_label::get_label)
+            .service(endpoints::addresses::get_review::get_review)
+            .service(endpoints::pickup::get_queue::get_queue)
+            .service(endpoints::pickup::post_picked_up::post_picked_up)
+            .service(endpoints::picking::get_pick_list::get_pick_list)
+            .service(endpoints::picking::get_bins::get_bins)
+            .service(endpoints::picking::post_bins::post_bins)
+            .service(endpoints::workflow::get_states::get_states)
+            .service(endpoints::workflow::post_states::post_states)
+            .service(endpoints::workflow::post_move::post_move)
+            .service(endpoints::workflow::post_assign::post_assign)
+            .service(endpoints::workflow::get_order_workflow::get_order_workflow)
+            .service(endpoints::orders::get_orders::get_orders)
+            .service(endpoints::tags::get_tags::get_tags)
+            .service(endpoints::tags::post_add::post_add)
+            .service(endpoints::tags::post_remove::post_remove)
+            .service(endpoints::views::get_views::get_views)
+            .service(endpoints::views::post_view::post_view)
+            .service(endpoints::views::delete_view::delete_view)
+            .service(endpoints::rules::get_rules::get_rules)
+            .service(endpoints::rules::post_rules::post_rules)
+            .service(endpoints::rules::get_log::get_log)
+            .service(endpoints::rules::post_evaluate::post_evaluate)
+            .service(endpoints::rules::get_notifications::get_notifications)
+            .service(endpoints::fraud::get_settings::get_settings)
+            .service(endpoints::fraud::post_settings::post_settings)
+            .service(endpoints::fraud::get_score::get_score)
+            .service(endpoints::fraud::post_score::post_score)
+            .service(endpoints::outbound_webhooks::get_subscriptions::get_subscriptions)
+            .service(endpoints::outbound_webhooks::post_subscription::post_subscription)
+            .service(endpoints::outbound_webhooks::delete_subscription::delete_subscription)
+            .service(endpoints::outbound_webhooks::post_test::post_test)
+            .service(endpoints::outbound_webhooks::get_deliveries::get_deliveries)
+            .service(endpoints::outbound_webhooks::post_redeliver::post_redeliver)
 
             .data(actix_web::web::PayloadConfig::new(1 << 25))
     })
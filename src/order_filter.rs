@@ -0,0 +1,216 @@
+use mysql::Value;
+use qstring::QString;
+
+/// Selection of orders, shared by the endpoints which list, export or report on orders.
+/// Columns are prefixed with `o.`, so queries using it must alias the orders table as `o`
+#[derive(Clone)]
+pub struct OrderFilter {
+    pub instance_id:        String,
+
+    /// Only orders placed at or after this time, epoch seconds
+    pub from:               Option<i64>,
+
+    /// Only orders placed before this time, epoch seconds
+    pub to:                 Option<i64>,
+    pub payment_status:     Option<String>,
+    pub fulfillment_status: Option<String>,
+    pub currency:           Option<String>,
+
+    /// 'SHIP', 'PICKUP' or 'DIGITAL'
+    pub delivery_method:    Option<String>,
+
+    /// Only these orders, by OrderSync ID
+    pub order_ids:          Option<Vec<String>>,
+
+    /// Key of a workflow state, see crate::workflow
+    pub workflow_state:     Option<String>,
+
+    /// Staff member the orders are assigned to
+    pub assignee:           Option<String>,
+
+    /// Only orders which have all of these tags
+    pub tags:               Option<Vec<String>>,
+
+    /// Only orders placed at least this many days ago. Unlike 'to' this moves along with time, which saved views rely on
+    pub older_than_days:    Option<i64>,
+
+    /// Only orders placed less than this many days ago
+    pub newer_than_days:    Option<i64>,
+
+    /// Only orders with a fraud risk score of at least this many points, see crate::fraud
+    pub min_risk_score:     Option<i64>
+}
+
+impl OrderFilter {
+    /**
+    Read a filter from the query parameters of a request
+
+    ## Params
+        **qstring** The query parameters. 'instanceId' is required, 'from', 'to', 'paymentStatus', 'fulfillmentStatus', 'currency', 'deliveryMethod',
+            'orderIds' (comma separated), 'workflowState', 'assignee', 'tags' (comma separated), 'olderThanDays', 'newerThanDays'
+            and 'minRiskScore' are optional
+
+    ## Returns
+        **Ok**: The filter
+        **Err**: A message describing the invalid parameter
+    */
+    pub fn from_query(qstring: &QString) -> Result<OrderFilter, String> {
+        let instance_id = match qstring.get("instanceId") {
+            Some(instance_id) => instance_id.to_string(),
+            None => return Err("Missing required parameter 'instanceId'".to_string())
+        };
+
+        Ok(OrderFilter {
+            instance_id,
+            from:               parse_epoch(qstring, "from")?,
+            to:                 parse_epoch(qstring, "to")?,
+            payment_status:     qstring.get("paymentStatus").map(|s| s.to_string()),
+            fulfillment_status: qstring.get("fulfillmentStatus").map(|s| s.to_string()),
+            currency:           qstring.get("currency").map(|s| s.to_string()),
+            delivery_method:    qstring.get("deliveryMethod").map(|s| s.to_uppercase()),
+            order_ids:          parse_list(qstring, "orderIds")?,
+            workflow_state:     qstring.get("workflowState").map(|s| s.to_string()),
+            assignee:           qstring.get("assignee").map(|s| s.to_string()),
+            tags:               parse_list(qstring, "tags")?,
+            older_than_days:    parse_days(qstring, "olderThanDays")?,
+            newer_than_days:    parse_days(qstring, "newerThanDays")?,
+            min_risk_score:     match qstring.get("minRiskScore").map(|value| value.parse::<i64>()) {
+                None => None,
+                Some(Ok(score)) => Some(score),
+                Some(Err(_)) => return Err("Parameter 'minRiskScore' must be a number".to_string())
+            }
+        })
+    }
+
+    /**
+    Build the SQL condition for this filter
+
+    ## Returns
+        The condition, to be placed after WHERE, and the named parameters it uses
+    */
+    pub fn to_sql(&self) -> (String, Vec<(String, Value)>) {
+        let mut conditions: Vec<&str> = vec!["o.instance_id = :filter_instance_id"];
+        let mut params: Vec<(String, Value)> = vec![("filter_instance_id".to_string(), Value::from(self.instance_id.clone()))];
+
+        if let Some(from) = self.from {
+            conditions.push("o.order_date >= :filter_from");
+            params.push(("filter_from".to_string(), Value::from(from)));
+        }
+
+        if let Some(to) = self.to {
+            conditions.push("o.order_date < :filter_to");
+            params.push(("filter_to".to_string(), Value::from(to)));
+        }
+
+        if let Some(payment_status) = &self.payment_status {
+            conditions.push("o.payment_status = :filter_payment_status");
+            params.push(("filter_payment_status".to_string(), Value::from(payment_status.clone())));
+        }
+
+        if let Some(fulfillment_status) = &self.fulfillment_status {
+            conditions.push("o.fulfillment_status = :filter_fulfillment_status");
+            params.push(("filter_fulfillment_status".to_string(), Value::from(fulfillment_status.clone())));
+        }
+
+        if let Some(currency) = &self.currency {
+            conditions.push("o.currency = :filter_currency");
+            params.push(("filter_currency".to_string(), Value::from(currency.clone())));
+        }
+
+        if let Some(delivery_method) = &self.delivery_method {
+            conditions.push("o.delivery_method = :filter_delivery_method");
+            params.push(("filter_delivery_method".to_string(), Value::from(delivery_method.clone())));
+        }
+
+        if let Some(workflow_state) = &self.workflow_state {
+            conditions.push("EXISTS (SELECT 1 FROM order_workflow fw WHERE fw.order_id = o.order_id AND fw.state_key = :filter_workflow_state)");
+            params.push(("filter_workflow_state".to_string(), Value::from(workflow_state.clone())));
+        }
+
+        if let Some(assignee) = &self.assignee {
+            conditions.push("EXISTS (SELECT 1 FROM order_workflow fa WHERE fa.order_id = o.order_id AND fa.assignee = :filter_assignee)");
+            params.push(("filter_assignee".to_string(), Value::from(assignee.clone())));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        if let Some(days) = self.older_than_days {
+            conditions.push("o.order_date <= :filter_older_than");
+            params.push(("filter_older_than".to_string(), Value::from(now - days * 86400)));
+        }
+
+        if let Some(days) = self.newer_than_days {
+            conditions.push("o.order_date > :filter_newer_than");
+            params.push(("filter_newer_than".to_string(), Value::from(now - days * 86400)));
+        }
+
+        if let Some(min_risk_score) = self.min_risk_score {
+            conditions.push("EXISTS (SELECT 1 FROM order_risk_scores rs WHERE rs.order_id = o.order_id AND rs.score >= :filter_min_risk_score)");
+            params.push(("filter_min_risk_score".to_string(), Value::from(min_risk_score)));
+        }
+
+        let tag_conditions: Vec<String> = (0..self.tags.as_ref().map_or(0, |tags| tags.len()))
+            .map(|i| format!("EXISTS (SELECT 1 FROM order_tags ft WHERE ft.order_id = o.order_id AND ft.tag = :filter_tag_{})", i))
+            .collect();
+
+        if let Some(tags) = &self.tags {
+            for (i, tag) in tags.iter().enumerate() {
+                conditions.push(&tag_conditions[i]);
+                params.push((format!("filter_tag_{}", i), Value::from(tag.clone())));
+            }
+        }
+
+        let order_ids_condition;
+        if let Some(order_ids) = &self.order_ids {
+            let names: Vec<String> = (0..order_ids.len()).map(|i| format!(":filter_order_id_{}", i)).collect();
+            order_ids_condition = format!("o.order_id IN ({})", names.join(", "));
+            conditions.push(&order_ids_condition);
+
+            for (i, order_id) in order_ids.iter().enumerate() {
+                params.push((format!("filter_order_id_{}", i), Value::from(order_id.clone())));
+            }
+        }
+
+        (conditions.join(" AND "), params)
+    }
+}
+
+/// Parse a comma separated list parameter, such as order IDs or tags
+fn parse_list(qstring: &QString, name: &str) -> Result<Option<Vec<String>>, String> {
+    let value = match qstring.get(name) {
+        Some(value) => value,
+        None => return Ok(None)
+    };
+
+    let values: Vec<String> = value.split(',').map(|value| value.trim().to_string()).filter(|value| !value.is_empty()).collect();
+    if values.is_empty() {
+        return Err(format!("Parameter '{}' must contain at least one value", name));
+    }
+
+    Ok(Some(values))
+}
+
+/// Parse a number of days
+fn parse_days(qstring: &QString, name: &str) -> Result<Option<i64>, String> {
+    match qstring.get(name).map(|value| value.parse::<i64>()) {
+        None => Ok(None),
+        Some(Ok(days)) if days >= 0 => Ok(Some(days)),
+        Some(_) => Err(format!("Parameter '{}' must be a number of days", name))
+    }
+}
+
+/// Parse a date parameter given either as epoch seconds or as an RFC 3339 date-time
+fn parse_epoch(qstring: &QString, name: &str) -> Result<Option<i64>, String> {
+    let value = match qstring.get(name) {
+        Some(value) => value,
+        None => return Ok(None)
+    };
+
+    if let Ok(epoch) = value.parse::<i64>() {
+        return Ok(Some(epoch));
+    }
+
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(dt) => Ok(Some(dt.timestamp())),
+        Err(_) => Err(format!("Parameter '{}' must be epoch seconds or an RFC 3339 date-time", name))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,171 @@
+use mysql::{PooledConn, Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+use crate::order_filter::OrderFilter;
+use crate::types::order::{Order, OrderDetails, OrderSummary, OrderItem, ItemOption, Address, ORDER_COLUMNS};
+
+/**
+List the filtered orders, most recent first
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to list
+    **limit** Maximum number of orders to return
+    **offset** Number of orders to skip
+
+## Returns
+    **Ok**: The orders with their tags
+    **Err**: A summary of what went wrong
+*/
+pub fn list_orders(database: Database, filter: &OrderFilter, limit: u64, offset: u64) -> Result<Vec<OrderSummary>, String> {
+    let (condition, mut params) = filter.to_sql();
+    params.push(("limit".to_string(), mysql::Value::from(limit)));
+    params.push(("offset".to_string(), mysql::Value::from(offset)));
+
+    let query = format!("SELECT {}, o.buyer_name, \
+        (SELECT GROUP_CONCAT(t.tag ORDER BY t.tag SEPARATOR ',') FROM order_tags t WHERE t.order_id = o.order_id) AS tags \
+        FROM orders o WHERE {} ORDER BY o.order_date DESC LIMIT :limit OFFSET :offset", ORDER_COLUMNS, condition);
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(query, Params::from(params));
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    //Tags never contain a comma, see crate::tags
+    Ok(result.unwrap().iter().map(|row| OrderSummary {
+        order:          Order::from_row(row),
+        buyer_name:     row.get("buyer_name").unwrap(),
+        tags:           row.get::<Option<String>, &str>("tags").unwrap()
+            .map(|tags| tags.split(',').map(|tag| tag.to_string()).collect())
+            .unwrap_or_default()
+    }).collect())
+}
+
+/**
+Get an order with its buyer, addresses and line items
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+
+## Returns
+    **Ok**: The order, or None if it doesn't exist
+    **Err**: A summary of what went wrong
+*/
+pub fn get_order_details(database: Database, order_id: &str) -> Result<Option<OrderDetails>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+
+    let result = conn.exec::<Row, String, Params>(format!("SELECT {}, o.buyer_name, o.buyer_email, o.buyer_phone, o.buyer_note, o.billing_address_id, o.shipping_address_id \
+        FROM orders o WHERE o.order_id = :order_id", ORDER_COLUMNS), params! {
+        "order_id" => order_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let rows = result.unwrap();
+    let row = match rows.first() {
+        Some(row) => row,
+        None => return Ok(None)
+    };
+
+    let billing_address = match row.get::<Option<String>, &str>("billing_address_id").unwrap() {
+        Some(address_id) => get_address(&mut conn, &address_id)?,
+        None => None
+    };
+
+    let shipping_address = match row.get::<Option<String>, &str>("shipping_address_id").unwrap() {
+        Some(address_id) => get_address(&mut conn, &address_id)?,
+        None => None
+    };
+
+    let items = get_order_items(&mut conn, order_id)?;
+
+    Ok(Some(OrderDetails {
+        order:              Order::from_row(row),
+        buyer_name:         row.get("buyer_name").unwrap(),
+        buyer_email:        row.get("buyer_email").unwrap(),
+        buyer_phone:        row.get("buyer_phone").unwrap(),
+        buyer_note:         row.get::<Option<String>, &str>("buyer_note").unwrap().unwrap_or_default(),
+        billing_address,
+        shipping_address,
+        items
+    }))
+}
+
+fn get_address(conn: &mut PooledConn, address_id: &str) -> Result<Option<Address>, String> {
+    let result = conn.exec::<Row, &str, Params>("SELECT address_type, full_name, company, email, phone, address_line_1, address_line_2, street, house_number, house_number_addition, zip_code, city, country, needs_review FROM addresses WHERE address_id = :address_id", params! {
+        "address_id" => address_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().first().map(|row| Address {
+        address_type:           row.get::<Option<String>, &str>("address_type").unwrap().unwrap_or_default(),
+        full_name:              row.get::<Option<String>, &str>("full_name").unwrap().unwrap_or_default(),
+        company:                row.get::<Option<String>, &str>("company").unwrap().unwrap_or_default(),
+        email:                  row.get::<Option<String>, &str>("email").unwrap().unwrap_or_default(),
+        phone:                  row.get::<Option<String>, &str>("phone").unwrap().unwrap_or_default(),
+        address_line_1:         row.get("address_line_1").unwrap(),
+        address_line_2:         row.get("address_line_2").unwrap(),
+        street:                 row.get::<Option<String>, &str>("street").unwrap().unwrap_or_default(),
+        house_number:           row.get::<Option<String>, &str>("house_number").unwrap().unwrap_or_default(),
+        house_number_addition:  row.get::<Option<String>, &str>("house_number_addition").unwrap().unwrap_or_default(),
+        zip_code:               row.get("zip_code").unwrap(),
+        city:                   row.get("city").unwrap(),
+        country:                row.get("country").unwrap(),
+        needs_review:           row.get::<Option<bool>, &str>("needs_review").unwrap().unwrap_or_default()
+    }))
+}
+
+fn get_order_items(conn: &mut PooledConn, order_id: &str) -> Result<Vec<OrderItem>, String> {
+    let result = conn.exec::<Row, &str, Params>("SELECT order_item_id, product_id, variant_id, item_type, name, sku, quantity, weight, weight_kg, price, total, tax, tax_group_id, tax_included_in_price, discount FROM order_items WHERE order_id = :order_id ORDER BY order_item_id ASC", params! {
+        "order_id" => order_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut items = Vec::new();
+    for row in result.unwrap() {
+        let order_item_id: String = row.get("order_item_id").unwrap();
+
+        let options = conn.exec::<Row, &str, Params>("SELECT `option`, selection FROM order_item_options WHERE order_item_id = :order_item_id", params! {
+            "order_item_id" => order_item_id.clone()
+        });
+
+        if options.is_err() {
+            return Err(options.err().unwrap().to_string());
+        }
+
+        items.push(OrderItem {
+            order_item_id,
+            product_id:             row.get("product_id").unwrap(),
+            variant_id:             row.get("variant_id").unwrap(),
+            item_type:              row.get::<Option<String>, &str>("item_type").unwrap().unwrap_or_else(|| "UNSPECIFIED".to_string()),
+            name:                   row.get("name").unwrap(),
+            sku:                    row.get("sku").unwrap(),
+            quantity:               row.get("quantity").unwrap(),
+            weight:                 row.get::<Option<f64>, &str>("weight").unwrap().unwrap_or_default(),
+            weight_kg:              row.get("weight_kg").unwrap(),
+            price:                  row.get("price").unwrap(),
+            total:                  row.get("total").unwrap(),
+            tax:                    row.get("tax").unwrap(),
+            tax_group_id:           row.get("tax_group_id").unwrap(),
+            tax_included_in_price:  row.get("tax_included_in_price").unwrap(),
+            discount:               row.get("discount").unwrap(),
+            options:                options.unwrap().iter().map(|option| ItemOption {
+                option:     option.get("option").unwrap(),
+                selection:  option.get("selection").unwrap()
+            }).collect()
+        });
+    }
+
+    Ok(items)
+}
\ No newline at end of file
@@ -0,0 +1,799 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+use crate::database::Database;
+use crate::orders::get_order_details;
+use crate::types::order::OrderDetails;
+
+/// Seconds a receiver gets to respond to a delivery
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// A delivery is given up after this many failed attempts
+pub const MAX_ATTEMPTS: i64 = 8;
+
+/// Delay before the first retry. Every next retry waits twice as long
+const RETRY_BASE_DELAY_SECS: i64 = 30;
+
+/// Deliveries sent per run of the delivery job
+const DELIVERY_BATCH_SIZE: u64 = 100;
+
+/// Seconds a delivery stays claimed by an attempt. Long enough for the attempt to finish, after that it can be claimed again,
+/// so a delivery whose attempt never recorded its outcome is not stuck
+const CLAIM_SECS: i64 = DELIVERY_TIMEOUT_SECS as i64 * 6;
+
+/// An order event other systems can subscribe to
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderEvent {
+    OrderCreated,
+    OrderPaid,
+    OrderFulfilled,
+
+    /// The order was partially or fully refunded
+    OrderRefunded,
+    OrderCanceled,
+
+    /// The payment or fulfillment status of the order changed. Sent along with the more specific events
+    OrderStatusChanged,
+
+    /// Sent when a subscription is tested, can not be subscribed to
+    Ping
+}
+
+impl OrderEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderEvent::OrderCreated => "ORDER_CREATED",
+            OrderEvent::OrderPaid => "ORDER_PAID",
+            OrderEvent::OrderFulfilled => "ORDER_FULFILLED",
+            OrderEvent::OrderRefunded => "ORDER_REFUNDED",
+            OrderEvent::OrderCanceled => "ORDER_CANCELED",
+            OrderEvent::OrderStatusChanged => "ORDER_STATUS_CHANGED",
+            OrderEvent::Ping => "PING"
+        }
+    }
+
+    pub fn from_str(event: &str) -> Option<OrderEvent> {
+        match event {
+            "ORDER_CREATED" => Some(OrderEvent::OrderCreated),
+            "ORDER_PAID" => Some(OrderEvent::OrderPaid),
+            "ORDER_FULFILLED" => Some(OrderEvent::OrderFulfilled),
+            "ORDER_REFUNDED" => Some(OrderEvent::OrderRefunded),
+            "ORDER_CANCELED" => Some(OrderEvent::OrderCanceled),
+            "ORDER_STATUS_CHANGED" => Some(OrderEvent::OrderStatusChanged),
+            "PING" => Some(OrderEvent::Ping),
+            _ => None
+        }
+    }
+}
+
+/// An endpoint of another system receiving the order events of an instance
+#[derive(Serialize)]
+pub struct Subscription {
+    pub subscription_id:    String,
+    pub url:                String,
+    pub events:             Vec<OrderEvent>,
+
+    /// Key the deliveries are signed with. Only returned when the subscription is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret:             Option<String>,
+
+    /// Epoch seconds
+    pub created_at:         i64
+}
+
+/// The statuses of an order before it changed
+#[derive(Serialize)]
+pub struct PreviousStatus {
+    pub payment_status:     String,
+    pub fulfillment_status: String
+}
+
+/// A delivery of an event to a subscription, with the outcome of its last attempt
+#[derive(Serialize)]
+pub struct Delivery {
+    pub delivery_id:        String,
+    pub subscription_id:    String,
+    pub event:              String,
+
+    /// None for PING events
+    pub order_id:           Option<String>,
+
+    /// The JSON body, as signed
+    pub payload:            String,
+
+    /// 'PENDING', 'DELIVERED' or 'FAILED'
+    pub status:             String,
+    pub attempts:           i64,
+
+    /// When the next attempt is made, epoch seconds. None once the delivery succeeded or was given up
+    pub next_attempt_at:    Option<i64>,
+
+    /// HTTP status of the last response, None if the receiver could not be reached
+    pub response_status:    Option<u16>,
+    pub last_error:         Option<String>,
+
+    /// Epoch seconds
+    pub created_at:         i64,
+    pub delivered_at:       Option<i64>
+}
+
+/// Body of a delivery
+#[derive(Serialize)]
+struct EventPayload<'a> {
+    /// Same for every delivery of the event, so receivers can recognise duplicates
+    event_id:   &'a str,
+    event:      OrderEvent,
+    created_at: i64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous:   Option<&'a PreviousStatus>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order:      Option<&'a OrderDetails>
+}
+
+const DELIVERY_COLUMNS: &str = "d.delivery_id, d.subscription_id, d.event, d.order_id, d.payload, d.status, d.attempts, d.next_attempt_at, d.response_status, d.last_error, d.created_at, d.delivered_at";
+
+/**
+Get the webhook subscriptions of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the subscriptions for
+
+## Returns
+    **Ok**: The subscriptions, without their secrets
+    **Err**: A summary of what went wrong
+*/
+pub fn get_subscriptions(database: Database, instance_id: String) -> Result<Vec<Subscription>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT subscription_id, url, events, created_at FROM webhook_subscriptions WHERE instance_id = :instance_id ORDER BY created_at ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| Subscription {
+            subscription_id:    row.get("subscription_id").unwrap(),
+            url:                row.get("url").unwrap(),
+            events:             row.get::<String, &str>("events").unwrap().split(',').filter_map(OrderEvent::from_str).collect(),
+            secret:             None,
+            created_at:         row.get("created_at").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Subscribe a URL to order events of an instance. A secret is generated to sign the deliveries with
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance whose order events are sent
+    **url** The receiving endpoint, http URLs are accepted for receivers on a local network
+    **events** The events to send
+
+## Returns
+    **Ok**: The subscription, with its secret
+    **Err**: The URL or events are invalid, or a summary of what went wrong
+*/
+pub fn create_subscription(database: Database, instance_id: String, url: String, events: Vec<OrderEvent>) -> Result<Subscription, String> {
+    let url = url.trim().to_string();
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("Invalid webhook URL '{}'", url));
+    }
+
+    let mut unique_events: Vec<OrderEvent> = Vec::new();
+    for event in events {
+        if !unique_events.contains(&event) {
+            unique_events.push(event);
+        }
+    }
+
+    let events = unique_events;
+    if events.is_empty() || events.contains(&OrderEvent::Ping) {
+        return Err("Subscribe to at least one order event, PING can not be subscribed to".to_string());
+    }
+
+    let subscription = Subscription {
+        subscription_id:    rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect(),
+        url,
+        events,
+        secret:             Some(rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect()),
+        created_at:         chrono::Utc::now().timestamp()
+    };
+
+    let events: Vec<&str> = subscription.events.iter().map(OrderEvent::as_str).collect();
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("INSERT INTO webhook_subscriptions (subscription_id, instance_id, url, events, secret, created_at) \
+        VALUES (:subscription_id, :instance_id, :url, :events, :secret, :created_at)", params! {
+        "subscription_id" => subscription.subscription_id.clone(),
+        "instance_id" => instance_id,
+        "url" => subscription.url.clone(),
+        "events" => events.join(","),
+        "secret" => subscription.secret.clone(),
+        "created_at" => subscription.created_at
+    });
+
+    match result {
+        Ok(_) => Ok(subscription),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Delete a webhook subscription. Its pending deliveries are given up, the delivery log is kept
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the subscription belongs to
+    **subscription_id** The subscription to delete
+
+## Returns
+    **Ok**: Whether the subscription existed
+    **Err**: A summary of what went wrong
+*/
+pub fn delete_subscription(database: Database, instance_id: String, subscription_id: String) -> Result<bool, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("DELETE FROM webhook_subscriptions WHERE subscription_id = :subscription_id AND instance_id = :instance_id", params! {
+        "subscription_id" => subscription_id.clone(),
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    if conn.affected_rows() == 0 {
+        return Ok(false);
+    }
+
+    let result = conn.exec_drop("UPDATE webhook_deliveries SET status = 'FAILED', next_attempt_at = NULL, last_error = 'The subscription was deleted' \
+        WHERE subscription_id = :subscription_id AND instance_id = :instance_id AND status = 'PENDING'", params! {
+        "subscription_id" => subscription_id,
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Queue an order event for the subscriptions of the order's instance. The deliveries are sent by the delivery job
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+    **event** The event
+
+## Returns
+    **Ok**: The number of queued deliveries
+    **Err**: A summary of what went wrong
+*/
+pub fn publish(database: Database, order_id: &str, event: OrderEvent) -> Result<usize, String> {
+    match get_order_details(database.clone(), order_id)? {
+        Some(details) => queue_event(&database, &details, event, None),
+        None => Err(format!("No order with ID {}", order_id))
+    }
+}
+
+/**
+Queue the events for a change of the payment or fulfillment status of an order:
+ORDER_STATUS_CHANGED, and ORDER_PAID, ORDER_REFUNDED, ORDER_FULFILLED or ORDER_CANCELED if the new status calls for it
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order, already updated with the new statuses
+    **previous** The statuses before the change
+
+## Returns
+    **Ok**: The number of queued deliveries
+    **Err**: A summary of what went wrong
+*/
+pub fn publish_status_change(database: Database, order_id: &str, previous: PreviousStatus) -> Result<usize, String> {
+    let details = match get_order_details(database.clone(), order_id)? {
+        Some(details) => details,
+        None => return Err(format!("No order with ID {}", order_id))
+    };
+
+    let order = &details.order;
+    let mut events = Vec::new();
+
+    if order.payment_status != previous.payment_status {
+        match order.payment_status.as_str() {
+            "Paid" => events.push(OrderEvent::OrderPaid),
+            "PartiallyRefunded" | "FullyRefunded" => events.push(OrderEvent::OrderRefunded),
+            _ => {}
+        }
+    }
+
+    if order.fulfillment_status != previous.fulfillment_status {
+        match order.fulfillment_status.as_str() {
+            "Fulfilled" => events.push(OrderEvent::OrderFulfilled),
+            "Canceled" => events.push(OrderEvent::OrderCanceled),
+            _ => {}
+        }
+    }
+
+    if order.payment_status != previous.payment_status || order.fulfillment_status != previous.fulfillment_status {
+        events.push(OrderEvent::OrderStatusChanged);
+    }
+
+    let mut queued = 0;
+    for event in events {
+        queued += queue_event(&database, &details, event, Some(&previous))?;
+    }
+
+    Ok(queued)
+}
+
+/**
+Get the delivery log of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the deliveries for
+    **subscription_id** Only the deliveries to this subscription, if given
+    **limit** Maximum number of deliveries to return
+
+## Returns
+    **Ok**: The deliveries, most recent first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_deliveries(database: Database, instance_id: String, subscription_id: Option<String>, limit: u64) -> Result<Vec<Delivery>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(format!("SELECT {} FROM webhook_deliveries d WHERE d.instance_id = :instance_id \
+        AND (:subscription_id IS NULL OR d.subscription_id = :subscription_id) ORDER BY d.created_at DESC LIMIT :limit", DELIVERY_COLUMNS), params! {
+        "instance_id" => instance_id,
+        "subscription_id" => subscription_id,
+        "limit" => limit
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(delivery_from_row).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Send the deliveries which are due. Failed deliveries are retried with exponential backoff, and given up after MAX_ATTEMPTS attempts.
+A delivery which can't be attempted is logged and skipped, so it doesn't hold up the others
+
+## Params
+    **database** Instance of a Database object
+
+## Returns
+    **Ok**: The number of deliveries attempted
+    **Err**: A summary of what went wrong
+*/
+pub fn send_due_deliveries(database: Database) -> Result<usize, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<String, &str, Params>("SELECT delivery_id FROM webhook_deliveries WHERE status = 'PENDING' AND next_attempt_at <= :now \
+        ORDER BY next_attempt_at ASC LIMIT :limit", params! {
+        "now" => chrono::Utc::now().timestamp(),
+        "limit" => DELIVERY_BATCH_SIZE
+    });
+
+    let delivery_ids = match result {
+        Ok(delivery_ids) => delivery_ids,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut attempted = 0;
+    for delivery_id in &delivery_ids {
+        //A redelivery or another run may have sent it since it was selected
+        match claim_delivery(&database, delivery_id, true) {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(e) => {
+                eprintln!("Webhooks: unable to claim delivery {}: {}", delivery_id, e);
+                continue;
+            }
+        }
+
+        match attempt_delivery(&database, delivery_id) {
+            Ok(_) => attempted += 1,
+            Err(e) => eprintln!("Webhooks: unable to attempt delivery {}: {}", delivery_id, e)
+        }
+    }
+
+    Ok(attempted)
+}
+
+/**
+Send a delivery again right away, whatever its status. If it fails, it is retried as long as it has attempts left.
+A delivery which is being sent at the moment is not sent again, it is returned as it is
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the delivery belongs to
+    **delivery_id** The delivery to send
+
+## Returns
+    **Ok**: The delivery with the outcome of the attempt, or None if the instance has no such delivery
+    **Err**: A summary of what went wrong
+*/
+pub fn redeliver(database: Database, instance_id: String, delivery_id: String) -> Result<Option<Delivery>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<String, &str, Params>("SELECT delivery_id FROM webhook_deliveries WHERE delivery_id = :delivery_id AND instance_id = :instance_id", params! {
+        "delivery_id" => delivery_id.clone(),
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(Some(_)) => {},
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(e.to_string())
+    }
+
+    if claim_delivery(&database, &delivery_id, false)? {
+        attempt_delivery(&database, &delivery_id).map(Some)
+    } else {
+        get_delivery(&database, &delivery_id).map(Some)
+    }
+}
+
+/**
+Send a PING event to a subscription right away, to test the receiver
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the subscription belongs to
+    **subscription_id** The subscription to test
+
+## Returns
+    **Ok**: The delivery with the outcome of the attempt, or None if the instance has no such subscription
+    **Err**: A summary of what went wrong
+*/
+pub fn send_ping(database: Database, instance_id: String, subscription_id: String) -> Result<Option<Delivery>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<String, &str, Params>("SELECT subscription_id FROM webhook_subscriptions WHERE subscription_id = :subscription_id AND instance_id = :instance_id", params! {
+        "subscription_id" => subscription_id.clone(),
+        "instance_id" => instance_id.clone()
+    });
+
+    match result {
+        Ok(Some(_)) => {},
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(e.to_string())
+    }
+
+    let event_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let payload = EventPayload {
+        event_id:   &event_id,
+        event:      OrderEvent::Ping,
+        created_at: chrono::Utc::now().timestamp(),
+        previous:   None,
+        order:      None
+    };
+
+    let delivery_id = insert_delivery(&database, &instance_id, &subscription_id, OrderEvent::Ping, None, &serde_json::to_string(&payload).unwrap())?;
+    if claim_delivery(&database, &delivery_id, false)? {
+        attempt_delivery(&database, &delivery_id).map(Some)
+    } else {
+        get_delivery(&database, &delivery_id).map(Some)
+    }
+}
+
+/**
+Sign the body of a delivery. Receivers verify a delivery by computing the HMAC-SHA256 of '{X-OrderSync-Timestamp}.{body}'
+with the subscription's secret, and comparing its hex encoding with X-OrderSync-Signature after the 'sha256=' prefix
+
+## Params
+    **secret** The secret of the subscription
+    **timestamp** Epoch seconds, sent as X-OrderSync-Timestamp
+    **body** The body of the delivery
+
+## Returns
+    The signature, e.g. 'sha256=5d41...'
+*/
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+
+    let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", signature)
+}
+
+fn queue_event(database: &Database, details: &OrderDetails, event: OrderEvent, previous: Option<&PreviousStatus>) -> Result<usize, String> {
+    let subscriptions = get_subscriptions(database.clone(), details.order.instance_id.clone())?;
+    let subscriptions: Vec<&Subscription> = subscriptions.iter().filter(|subscription| subscription.events.contains(&event)).collect();
+    if subscriptions.is_empty() {
+        return Ok(0);
+    }
+
+    let event_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let payload = EventPayload {
+        event_id:   &event_id,
+        event,
+        created_at: chrono::Utc::now().timestamp(),
+        previous,
+        order:      Some(details)
+    };
+
+    let payload = serde_json::to_string(&payload).unwrap();
+    for subscription in &subscriptions {
+        insert_delivery(database, &details.order.instance_id, &subscription.subscription_id, event, Some(&details.order.order_id), &payload)?;
+    }
+
+    Ok(subscriptions.len())
+}
+
+fn insert_delivery(database: &Database, instance_id: &str, subscription_id: &str, event: OrderEvent, order_id: Option<&str>, payload: &str) -> Result<String, String> {
+    let delivery_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let now = chrono::Utc::now().timestamp();
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("INSERT INTO webhook_deliveries (delivery_id, subscription_id, instance_id, event, order_id, payload, status, attempts, next_attempt_at, created_at) \
+        VALUES (:delivery_id, :subscription_id, :instance_id, :event, :order_id, :payload, 'PENDING', 0, :now, :now)", params! {
+        "delivery_id" => delivery_id.clone(),
+        "subscription_id" => subscription_id,
+        "instance_id" => instance_id,
+        "event" => event.as_str(),
+        "order_id" => order_id,
+        "payload" => payload,
+        "now" => now
+    });
+
+    match result {
+        Ok(_) => Ok(delivery_id),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Claim a delivery for an attempt, so it is not sent by the delivery job and a redelivery at the same time.
+The claim ends when the outcome of the attempt is recorded, or after CLAIM_SECS
+*/
+fn claim_delivery(database: &Database, delivery_id: &str, due_only: bool) -> Result<bool, String> {
+    let now = chrono::Utc::now().timestamp();
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop(format!("UPDATE webhook_deliveries SET claimed_until = :claimed_until WHERE delivery_id = :delivery_id \
+        AND (claimed_until IS NULL OR claimed_until <= :now){}", if due_only { " AND status = 'PENDING' AND next_attempt_at <= :now" } else { "" }), params! {
+        "claimed_until" => now + CLAIM_SECS,
+        "delivery_id" => delivery_id,
+        "now" => now
+    });
+
+    match result {
+        Ok(_) => Ok(conn.affected_rows() == 1),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn get_delivery(database: &Database, delivery_id: &str) -> Result<Delivery, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, String, Params>(format!("SELECT {} FROM webhook_deliveries d WHERE d.delivery_id = :delivery_id", DELIVERY_COLUMNS), params! {
+        "delivery_id" => delivery_id
+    });
+
+    match result {
+        Ok(Some(row)) => Ok(delivery_from_row(&row)),
+        Ok(None) => Err(format!("No delivery with ID {}", delivery_id)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Make one attempt at a claimed delivery and record its outcome
+fn attempt_delivery(database: &Database, delivery_id: &str) -> Result<Delivery, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, String, Params>(format!("SELECT {}, s.url, s.secret FROM webhook_deliveries d \
+        LEFT JOIN webhook_subscriptions s ON s.subscription_id = d.subscription_id WHERE d.delivery_id = :delivery_id", DELIVERY_COLUMNS), params! {
+        "delivery_id" => delivery_id
+    });
+
+    let row = match result {
+        Ok(Some(row)) => row,
+        Ok(None) => return Err(format!("No delivery with ID {}", delivery_id)),
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut delivery = delivery_from_row(&row);
+    let now = chrono::Utc::now().timestamp();
+
+    match (row.get::<Option<String>, &str>("url").unwrap(), row.get::<Option<String>, &str>("secret").unwrap()) {
+        (Some(url), Some(secret)) => {
+            let (response_status, error) = send_delivery(&url, &secret, &delivery, now);
+            record_attempt(&mut delivery, response_status, error, false, now);
+        },
+        _ => record_attempt(&mut delivery, None, Some("The subscription was deleted".to_string()), true, now)
+    }
+
+    let result = conn.exec_drop("UPDATE webhook_deliveries SET status = :status, attempts = :attempts, next_attempt_at = :next_attempt_at, \
+        response_status = :response_status, last_error = :last_error, delivered_at = :delivered_at, claimed_until = NULL WHERE delivery_id = :delivery_id", params! {
+        "status" => delivery.status.clone(),
+        "attempts" => delivery.attempts,
+        "next_attempt_at" => delivery.next_attempt_at,
+        "response_status" => delivery.response_status,
+        "last_error" => delivery.last_error.clone(),
+        "delivered_at" => delivery.delivered_at,
+        "delivery_id" => delivery.delivery_id.clone()
+    });
+
+    match result {
+        Ok(_) => Ok(delivery),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// POST a delivery to a receiver, signed with the subscription's secret. Returns the response status, and the error if the attempt failed
+fn send_delivery(url: &str, secret: &str, delivery: &Delivery, now: i64) -> (Option<u16>, Option<String>) {
+    let result = reqwest::blocking::Client::new().post(url)
+        .header("Content-Type", "application/json")
+        .header("X-OrderSync-Event", delivery.event.clone())
+        .header("X-OrderSync-Delivery", delivery.delivery_id.clone())
+        .header("X-OrderSync-Timestamp", now.to_string())
+        .header("X-OrderSync-Signature", sign(secret, now, &delivery.payload))
+        .body(delivery.payload.clone())
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+        .send();
+
+    match result {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (Some(response.status().as_u16()), Some(format!("The receiver responded with {}", response.status()))),
+        Err(e) => (None, Some(e.to_string()))
+    }
+}
+
+/// Update a delivery with the outcome of an attempt. `give_up` is set when retrying is pointless
+fn record_attempt(delivery: &mut Delivery, response_status: Option<u16>, error: Option<String>, give_up: bool, now: i64) {
+    delivery.attempts += 1;
+    delivery.response_status = response_status;
+    delivery.last_error = error;
+
+    if delivery.last_error.is_none() {
+        delivery.status = "DELIVERED".to_string();
+        delivery.next_attempt_at = None;
+        delivery.delivered_at = Some(now);
+    } else if give_up || delivery.attempts >= MAX_ATTEMPTS {
+        delivery.status = "FAILED".to_string();
+        delivery.next_attempt_at = None;
+    } else {
+        //30 seconds, 1 minute, 2 minutes, ... after the previous attempt
+        delivery.status = "PENDING".to_string();
+        delivery.next_attempt_at = Some(now + RETRY_BASE_DELAY_SECS * 2i64.pow((delivery.attempts - 1) as u32));
+    }
+}
+
+fn delivery_from_row(row: &Row) -> Delivery {
+    Delivery {
+        delivery_id:        row.get("delivery_id").unwrap(),
+        subscription_id:    row.get("subscription_id").unwrap(),
+        event:              row.get("event").unwrap(),
+        order_id:           row.get("order_id").unwrap(),
+        payload:            row.get("payload").unwrap(),
+        status:             row.get("status").unwrap(),
+        attempts:           row.get("attempts").unwrap(),
+        next_attempt_at:    row.get("next_attempt_at").unwrap(),
+        response_status:    row.get("response_status").unwrap(),
+        last_error:         row.get("last_error").unwrap(),
+        created_at:         row.get("created_at").unwrap(),
+        delivered_at:       row.get("delivered_at").unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+
+    const SECRET: &str = "whsec_test";
+    const NOW: i64 = 1_614_556_800;
+
+    fn delivery() -> Delivery {
+        Delivery {
+            delivery_id:        "d1".to_string(),
+            subscription_id:    "s1".to_string(),
+            event:              "ORDER_PAID".to_string(),
+            order_id:           Some("o1".to_string()),
+            payload:            r#"{"eventId":"e1","event":"ORDER_PAID","createdAt":1614556800}"#.to_string(),
+            status:             "PENDING".to_string(),
+            attempts:           0,
+            next_attempt_at:    Some(NOW),
+            response_status:    None,
+            last_error:         None,
+            created_at:         NOW,
+            delivered_at:       None
+        }
+    }
+
+    /// URL of the local receiver, each test under its own path so they can run in parallel
+    fn receiver(path: &str) -> String {
+        format!("{}/{}", mockito::server_url(), path)
+    }
+
+    #[test]
+    fn deliveries_are_signed() {
+        let delivery = delivery();
+        let receiver_mock = mock("POST", "/signed")
+            .match_header("x-ordersync-event", "ORDER_PAID")
+            .match_header("x-ordersync-delivery", "d1")
+            .match_header("x-ordersync-timestamp", NOW.to_string().as_str())
+            .match_header("x-ordersync-signature", sign(SECRET, NOW, &delivery.payload).as_str())
+            .match_body(delivery.payload.as_str())
+            .with_status(204)
+            .create();
+
+        let (response_status, error) = send_delivery(&receiver("signed"), SECRET, &delivery, NOW);
+        receiver_mock.assert();
+
+        assert_eq!(response_status, Some(204));
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn signatures_are_hmac_sha256_of_timestamp_and_body() {
+        //echo -n '1614556800.{}' | openssl dgst -sha256 -hmac whsec_test
+        assert_eq!(sign(SECRET, NOW, "{}"), "sha256=6bd5ebbc9cbce4fe6d9c728ba6c962ae7ec5510179f82646471f20bba89e7ffd");
+    }
+
+    #[test]
+    fn error_responses_fail_the_attempt() {
+        let _receiver_mock = mock("POST", "/unavailable").with_status(503).create();
+
+        let (response_status, error) = send_delivery(&receiver("unavailable"), SECRET, &delivery(), NOW);
+        assert_eq!(response_status, Some(503));
+        assert!(error.unwrap().contains("503"));
+    }
+
+    #[test]
+    fn failed_attempts_are_retried_with_exponential_backoff() {
+        let mut delivery = delivery();
+        for attempt in 0..MAX_ATTEMPTS - 1 {
+            record_attempt(&mut delivery, Some(500), Some("The receiver responded with 500".to_string()), false, NOW);
+
+            assert_eq!(delivery.status, "PENDING");
+            assert_eq!(delivery.attempts, attempt + 1);
+            assert_eq!(delivery.next_attempt_at, Some(NOW + RETRY_BASE_DELAY_SECS * 2i64.pow(attempt as u32)));
+        }
+    }
+
+    #[test]
+    fn deliveries_fail_after_max_attempts() {
+        let mut delivery = delivery();
+        for _ in 0..MAX_ATTEMPTS {
+            record_attempt(&mut delivery, None, Some("Connection refused".to_string()), false, NOW);
+        }
+
+        assert_eq!(delivery.status, "FAILED");
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert_eq!(delivery.next_attempt_at, None);
+    }
+
+    #[test]
+    fn deliveries_to_deleted_subscriptions_are_given_up() {
+        let mut delivery = delivery();
+        record_attempt(&mut delivery, None, Some("The subscription was deleted".to_string()), true, NOW);
+
+        assert_eq!(delivery.status, "FAILED");
+        assert_eq!(delivery.attempts, 1);
+    }
+
+    #[test]
+    fn redelivering_a_failed_delivery_can_succeed() {
+        let _receiver_mock = mock("POST", "/recovered").with_status(200).create();
+
+        let mut delivery = delivery();
+        for _ in 0..MAX_ATTEMPTS {
+            record_attempt(&mut delivery, None, Some("Connection refused".to_string()), false, NOW);
+        }
+
+        let (response_status, error) = send_delivery(&receiver("recovered"), SECRET, &delivery, NOW + 60);
+        record_attempt(&mut delivery, response_status, error, false, NOW + 60);
+
+        assert_eq!(delivery.status, "DELIVERED");
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS + 1);
+        assert_eq!(delivery.response_status, Some(200));
+        assert_eq!(delivery.last_error, None);
+        assert_eq!(delivery.delivered_at, Some(NOW + 60));
+        assert_eq!(delivery.next_attempt_at, None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,118 @@
+use chrono::TimeZone;
+use serde::Serialize;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+
+/// An order waiting to be collected by the buyer
+#[derive(Serialize)]
+pub struct PickupOrder {
+    pub order_id:           String,
+    pub order_number:       i64,
+    pub order_date:         i64,
+    pub buyer_name:         String,
+    pub buyer_email:        String,
+    pub buyer_phone:        String,
+    pub payment_status:     String,
+
+    /// Location the order is collected at
+    pub pickup_location:    String,
+    pub quantity:           i64
+}
+
+/// The handover of a pickup order
+#[derive(Serialize)]
+pub struct Pickup {
+    pub order_id:           String,
+
+    /// Name of the staff member who handed over the order
+    pub handed_over_by:     String,
+
+    /// Epoch seconds
+    pub picked_up_at:       i64
+}
+
+/**
+Get the pickup orders of an instance which have not been collected yet
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the queue for
+
+## Returns
+    **Ok**: The orders, oldest first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_pickup_queue(database: Database, instance_id: String) -> Result<Vec<PickupOrder>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT o.order_id, o.wix_order_id, o.order_date, o.buyer_name, o.buyer_email, o.buyer_phone, o.payment_status, o.quantity, \
+        CONCAT_WS(', ', a.address_line_1, a.city) AS pickup_location \
+        FROM orders o LEFT JOIN addresses a ON a.address_id = o.shipping_address_id \
+        WHERE o.instance_id = :instance_id AND o.delivery_method = 'PICKUP' \
+        AND NOT EXISTS (SELECT 1 FROM order_pickups p WHERE p.order_id = o.order_id) ORDER BY o.order_date ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| PickupOrder {
+            order_id:           row.get("order_id").unwrap(),
+            order_number:       row.get("wix_order_id").unwrap(),
+            order_date:         row.get("order_date").unwrap(),
+            buyer_name:         row.get("buyer_name").unwrap(),
+            buyer_email:        row.get("buyer_email").unwrap(),
+            buyer_phone:        row.get("buyer_phone").unwrap(),
+            payment_status:     row.get("payment_status").unwrap(),
+            pickup_location:    row.get::<Option<String>, &str>("pickup_location").unwrap().unwrap_or_default(),
+            quantity:           row.get("quantity").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Record that a pickup order was collected by the buyer
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+    **handed_over_by** Name of the staff member who handed over the order
+
+## Returns
+    **Ok**: The recorded pickup, or None if the order does not exist
+    **Err**: The order is not a pickup order or was already collected, or a summary of what went wrong
+*/
+pub fn mark_picked_up(database: Database, order_id: String, handed_over_by: String) -> Result<Option<Pickup>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT o.delivery_method, p.handed_over_by, p.picked_up_at FROM orders o \
+        LEFT JOIN order_pickups p ON p.order_id = o.order_id WHERE o.order_id = :order_id", params! {
+        "order_id" => order_id.clone()
+    });
+
+    let row = match result {
+        Ok(Some(row)) => row,
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(e.to_string())
+    };
+
+    if row.get::<Option<String>, &str>("delivery_method").unwrap().as_deref() != Some("PICKUP") {
+        return Err("The order is not a pickup order".to_string());
+    }
+
+    if let Some(picked_up_at) = row.get::<Option<i64>, &str>("picked_up_at").unwrap() {
+        let by: String = row.get("handed_over_by").unwrap();
+        return Err(format!("The order was already handed over by {} at {}", by, chrono::Utc.timestamp_opt(picked_up_at, 0).unwrap()));
+    }
+
+    let picked_up_at = chrono::Utc::now().timestamp();
+    let result = conn.exec_drop("INSERT INTO order_pickups (order_id, handed_over_by, picked_up_at) VALUES (:order_id, :handed_over_by, :picked_up_at)", params! {
+        "order_id" => order_id.clone(),
+        "handed_over_by" => handed_over_by.clone(),
+        "picked_up_at" => picked_up_at
+    });
+
+    match result {
+        Ok(_) => Ok(Some(Pickup { order_id, handed_over_by, picked_up_at })),
+        Err(e) => Err(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::TimeZone;
+use chrono_tz::Tz;
+use serde::Serialize;
+use mysql::{Params, Row};
+use mysql::prelude::Queryable;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::database::Database;
+use crate::order_filter::OrderFilter;
+use crate::reports::{exchange_rate, get_settings, period_start, Granularity};
+use crate::types::money::Money;
+
+/// How to rank products
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ranking {
+    /// Most units sold first
+    TopSellers,
+
+    /// Fewest units sold first, including products which sold before but not within the period
+    SlowMovers
+}
+
+/// A product, variant and option combination as it was sold
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProductVariant {
+    /// Wix catalog product ID, None for items without a product
+    pub product_id:     Option<String>,
+    pub variant_id:     Option<String>,
+    pub name:           String,
+    pub sku:            String,
+
+    /// The selected options, e.g. 'Color: Red, Size: M'
+    pub options:        String
+}
+
+/// Sales of a product variant, for a single period or the whole selection
+#[derive(Serialize)]
+pub struct ProductSales {
+    /// Start of the period, epoch seconds. None when not bucketed by period
+    pub period_start:       Option<i64>,
+
+    /// Start of the period as a local date, e.g. '2021-03-01'
+    pub period:             Option<String>,
+    pub product:            ProductVariant,
+    pub units_sold:         i64,
+
+    /// Currency of the amounts, the instance's reporting currency
+    pub currency:           String,
+    pub revenue:            Decimal,
+    pub order_count:        i64,
+
+    /// Share of the revenue which was refunded
+    pub refunded_amount:    Decimal,
+
+    /// Refunded amount relative to revenue, 0 to 1
+    pub refund_rate:        Decimal,
+
+    /// Orders which couldn't be converted for lack of an exchange rate. Their units are counted, their amounts are left out
+    pub unconverted_orders: i64
+}
+
+#[derive(Default)]
+struct Totals {
+    units:          i64,
+    revenue:        Decimal,
+    orders:         HashSet<String>,
+    unconverted:    HashSet<String>,
+    refunded:       Decimal
+}
+
+/// Sales of custom amount items, which have no product, for a single period or the whole selection
+#[derive(Serialize)]
+pub struct CustomAmountSales {
+    /// Start of the period, epoch seconds. None when not bucketed by period
+    pub period_start:       Option<i64>,
+
+    /// Start of the period as a local date, e.g. '2021-03-01'
+    pub period:             Option<String>,
+    pub item_count:         i64,
+
+    /// Currency of the amounts, the instance's reporting currency
+    pub currency:           String,
+    pub revenue:            Decimal,
+    pub order_count:        i64,
+    pub refunded_amount:    Decimal,
+
+    /// Orders which couldn't be converted for lack of an exchange rate. Their items are counted, their amounts are left out
+    pub unconverted_orders: i64
+}
+
+/// A sold line item, with the share of its order that was refunded
+struct SoldItem {
+    order_id:       String,
+    order_date:     i64,
+    product:        ProductVariant,
+    quantity:       i64,
+
+    /// Total and refunded amount, converted to the reporting currency. None if the order hasn't been converted
+    converted:      Option<(Decimal, Decimal)>
+}
+
+/**
+Aggregate the sales of product variants, optionally per period. Custom amount items are not products, see custom_amount_sales.
+Wix refunds are not itemized, so a refund is attributed to the line items of its order in proportion to their totals.
+Amounts are converted to the instance's reporting currency at the exchange rate stored on each order, as in reports::sales_report.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to report on
+    **granularity** Length of the periods, or None for totals over the whole selection
+
+## Returns
+    **Ok**: The sales, by period and then by product
+    **Err**: A summary of what went wrong
+*/
+pub fn product_sales(database: Database, filter: &OrderFilter, granularity: Option<Granularity>) -> Result<Vec<ProductSales>, String> {
+    let settings = get_settings(database.clone(), filter.instance_id.clone())?;
+    let timezone = settings.parse_timezone()?;
+    let items = get_sold_items(database, filter, false, &settings.reporting_currency)?;
+
+    let mut buckets: BTreeMap<(Option<i64>, ProductVariant), Totals> = BTreeMap::new();
+    for item in items {
+        let period = granularity.map(|granularity| period_start(&timezone, granularity, item.order_date));
+        add_item(buckets.entry((period, item.product.clone())).or_default(), &item);
+    }
+
+    Ok(buckets.into_iter().map(|((period, product), totals)| {
+        to_sales(period.map(|period| (period, format_period(&timezone, period))), product, totals, &settings.reporting_currency)
+    }).collect())
+}
+
+/**
+Rank product variants by units sold within the filter's period
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to report on
+    **ranking** Whether to list the top sellers or the slow movers
+    **limit** Maximum number of products to return
+
+## Returns
+    **Ok**: The ranked products, with their sales over the whole period
+    **Err**: A summary of what went wrong
+*/
+pub fn rank_products(database: Database, filter: &OrderFilter, ranking: Ranking, limit: usize) -> Result<Vec<ProductSales>, String> {
+    //Slow movers include products without sales in the period, so the products sold at any time are needed
+    let query_filter = match ranking {
+        Ranking::TopSellers => filter.clone(),
+        Ranking::SlowMovers => OrderFilter { from: None, to: None, ..filter.clone() }
+    };
+
+    let reporting_currency = get_settings(database.clone(), filter.instance_id.clone())?.reporting_currency;
+    let items = get_sold_items(database, &query_filter, false, &reporting_currency)?;
+
+    let mut products: BTreeMap<ProductVariant, Totals> = BTreeMap::new();
+    for item in items {
+        let totals = products.entry(item.product.clone()).or_default();
+
+        let in_period = filter.from.map_or(true, |from| item.order_date >= from) && filter.to.map_or(true, |to| item.order_date < to);
+        if in_period {
+            add_item(totals, &item);
+        }
+    }
+
+    let mut sales: Vec<ProductSales> = products.into_iter().map(|(product, totals)| to_sales(None, product, totals, &reporting_currency)).collect();
+    match ranking {
+        Ranking::TopSellers => sales.sort_by(|a, b| b.units_sold.cmp(&a.units_sold).then(b.revenue.cmp(&a.revenue))),
+        Ranking::SlowMovers => sales.sort_by(|a, b| a.units_sold.cmp(&b.units_sold).then(a.revenue.cmp(&b.revenue)))
+    }
+
+    sales.truncate(limit);
+    Ok(sales)
+}
+
+/**
+Aggregate the sales of custom amount items, optionally per period. These are reported apart from product sales
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to report on
+    **granularity** Length of the periods, or None for totals over the whole selection
+
+## Returns
+    **Ok**: The sales, by period
+    **Err**: A summary of what went wrong
+*/
+pub fn custom_amount_sales(database: Database, filter: &OrderFilter, granularity: Option<Granularity>) -> Result<Vec<CustomAmountSales>, String> {
+    let settings = get_settings(database.clone(), filter.instance_id.clone())?;
+    let timezone = settings.parse_timezone()?;
+    let items = get_sold_items(database, filter, true, &settings.reporting_currency)?;
+
+    let mut buckets: BTreeMap<Option<i64>, Totals> = BTreeMap::new();
+    for item in items {
+        let period = granularity.map(|granularity| period_start(&timezone, granularity, item.order_date));
+        add_item(buckets.entry(period).or_default(), &item);
+    }
+
+    let round = |amount: Decimal| Money::new(amount, &settings.reporting_currency).round().amount;
+
+    Ok(buckets.into_iter().map(|(period, totals)| CustomAmountSales {
+        period_start:       period,
+        period:             period.map(|period| format_period(&timezone, period)),
+        item_count:         totals.units,
+        currency:           settings.reporting_currency.clone(),
+        revenue:            round(totals.revenue),
+        order_count:        totals.orders.len() as i64,
+        refunded_amount:    round(totals.refunded),
+        unconverted_orders: totals.unconverted.len() as i64
+    }).collect())
+}
+
+/// Get the sold line items of the filtered orders: either the custom amount items, or all other items
+fn get_sold_items(database: Database, filter: &OrderFilter, custom_amounts: bool, reporting_currency: &str) -> Result<Vec<SoldItem>, String> {
+    let (condition, params) = filter.to_sql();
+    let item_condition = if custom_amounts { "i.item_type = 'CUSTOM_AMOUNT_ITEM'" } else { "COALESCE(i.item_type, '') <> 'CUSTOM_AMOUNT_ITEM'" };
+    let query = format!("SELECT o.order_id, o.order_date, o.total_price, o.reporting_currency, o.exchange_rate, i.product_id, i.variant_id, i.name, i.sku, i.quantity, i.total, \
+        (SELECT GROUP_CONCAT(CONCAT(io.`option`, ': ', io.selection) ORDER BY io.`option` SEPARATOR ', ') FROM order_item_options io WHERE io.order_item_id = i.order_item_id) AS options, \
+        (SELECT COALESCE(SUM(r.amount), 0) FROM order_refunds r WHERE r.order_id = o.order_id) AS refunded \
+        FROM orders o INNER JOIN order_items i ON i.order_id = o.order_id WHERE {} AND {}", condition, item_condition);
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(query, Params::from(params));
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().iter().map(|row| {
+        let order_total: Decimal = row.get("total_price").unwrap();
+        let order_refunded: Decimal = row.get("refunded").unwrap();
+        let total: Decimal = row.get("total").unwrap();
+        let refunded = (total * order_refunded).checked_div(order_total).unwrap_or_default();
+
+        SoldItem {
+            order_id:   row.get("order_id").unwrap(),
+            order_date: row.get("order_date").unwrap(),
+            product:    ProductVariant {
+                product_id: row.get("product_id").unwrap(),
+                variant_id: row.get("variant_id").unwrap(),
+                name:       row.get("name").unwrap(),
+                sku:        row.get("sku").unwrap(),
+                options:    row.get::<Option<String>, &str>("options").unwrap().unwrap_or_default()
+            },
+            quantity:   row.get("quantity").unwrap(),
+            converted:  exchange_rate(row, reporting_currency).map(|rate| (total * rate, refunded * rate))
+        }
+    }).collect())
+}
+
+fn add_item(totals: &mut Totals, item: &SoldItem) {
+    totals.units += item.quantity;
+    totals.orders.insert(item.order_id.clone());
+
+    match item.converted {
+        Some((total, refunded)) => {
+            totals.revenue += total;
+            totals.refunded += refunded;
+        },
+        None => {
+            totals.unconverted.insert(item.order_id.clone());
+        }
+    }
+}
+
+/// Build the sales of a product from its totals. The period is given as its start and local date
+fn to_sales(period: Option<(i64, String)>, product: ProductVariant, totals: Totals, currency: &str) -> ProductSales {
+    let (period_start, period) = match period {
+        Some((start, date)) => (Some(start), Some(date)),
+        None => (None, None)
+    };
+
+    ProductSales {
+        period_start,
+        period,
+        product,
+        units_sold:         totals.units,
+        currency:           currency.to_string(),
+        revenue:            Money::new(totals.revenue, currency).round().amount,
+        order_count:        totals.orders.len() as i64,
+        refunded_amount:    Money::new(totals.refunded, currency).round().amount,
+        refund_rate:        totals.refunded.checked_div(totals.revenue).unwrap_or_default().round_dp_with_strategy(4, RoundingStrategy::MidpointAwayFromZero),
+        unconverted_orders: totals.unconverted.len() as i64
+    }
+}
+
+fn format_period(timezone: &Tz, period_start: i64) -> String {
+    timezone.timestamp_opt(period_start, 0).unwrap().format("%Y-%m-%d").to_string()
+}
\ No newline at end of file
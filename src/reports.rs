@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+
+use chrono::{Datelike, Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::database::Database;
+use crate::order_filter::OrderFilter;
+use crate::types::money::Money;
+
+/// Timezone used for instances which haven't set one
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// Currency reported in for instances which haven't set one
+pub const DEFAULT_REPORTING_CURRENCY: &str = "EUR";
+
+/// Reporting settings of an instance
+#[derive(Serialize, Deserialize)]
+pub struct ReportSettings {
+    pub instance_id:    String,
+
+    /// IANA timezone name, e.g. 'Europe/Amsterdam'. Periods start at midnight in this timezone
+    pub timezone:       String,
+
+    /// Currency code all amounts in reports are converted to
+    pub reporting_currency: String
+}
+
+impl ReportSettings {
+    /// Check the timezone and reporting currency are known
+    pub fn validate(&self) -> Result<(), String> {
+        self.parse_timezone()?;
+        if !Money::is_currency(&self.reporting_currency) {
+            return Err(format!("Unknown currency '{}', expected an ISO 4217 code such as 'EUR'", self.reporting_currency));
+        }
+
+        Ok(())
+    }
+
+    /// Parse the timezone name
+    pub fn parse_timezone(&self) -> Result<Tz, String> {
+        match self.timezone.parse() {
+            Ok(timezone) => Ok(timezone),
+            Err(_) => Err(format!("Unknown timezone '{}'", self.timezone))
+        }
+    }
+}
+
+/// Length of the periods in a report
+#[derive(Clone, Copy, PartialEq)]
+pub enum Granularity {
+    Day,
+
+    /// Weeks start on Monday
+    Week,
+    Month
+}
+
+/// Dimension to split each period by
+#[derive(Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Currency,
+    PaymentStatus,
+    Channel,
+
+    /// By SKU, or name for line items without a SKU. Revenue, units and tax are those of the product's line items.
+    /// Custom amount items are grouped together as 'Custom amounts'
+    Product
+}
+
+/// Aggregates of a single period and group
+#[derive(Serialize)]
+pub struct ReportRow {
+    /// Start of the period, epoch seconds
+    pub period_start:           i64,
+
+    /// Start of the period as a local date, e.g. '2021-03-01'
+    pub period:                 String,
+
+    /// Value of the grouping dimension, None when not grouped
+    pub group:                  Option<String>,
+
+    /// Currency of the amounts, the instance's reporting currency
+    pub currency:               String,
+    pub revenue:                Decimal,
+    pub order_count:            i64,
+    pub average_order_value:    Decimal,
+    pub units_sold:             i64,
+    pub tax:                    Decimal,
+    pub shipping:               Decimal,
+
+    /// Revenue in the order currency, only when grouped by currency
+    pub original_revenue:       Option<Decimal>,
+
+    /// Orders which couldn't be converted for lack of an exchange rate. They are left out of the amounts
+    pub unconverted_orders:     i64
+}
+
+#[derive(Default)]
+struct Totals {
+    revenue:            Decimal,
+    original_revenue:   Decimal,
+    orders:             HashSet<String>,
+    unconverted:        HashSet<String>,
+    units:              i64,
+    tax:                Decimal,
+    shipping:           Decimal
+}
+
+/**
+Get the reporting settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the settings for
+
+## Returns
+    **Ok**: The settings. Instances without settings report in UTC and EUR
+    **Err**: A summary of what went wrong
+*/
+pub fn get_settings(database: Database, instance_id: String) -> Result<ReportSettings, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT timezone, reporting_currency FROM report_settings WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    match result {
+        Ok(Some(row)) => Ok(ReportSettings { instance_id, timezone: row.get("timezone").unwrap(), reporting_currency: row.get("reporting_currency").unwrap() }),
+        Ok(None) => Ok(ReportSettings { instance_id, timezone: DEFAULT_TIMEZONE.to_string(), reporting_currency: DEFAULT_REPORTING_CURRENCY.to_string() }),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Create or update the reporting settings of an instance.
+Changing the reporting currency doesn't convert the orders, see exchange_rates::convert_orders
+
+## Params
+    **database** Instance of a Database object
+    **settings** The new settings
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong, including an unknown timezone or currency
+*/
+pub fn set_settings(database: Database, settings: ReportSettings) -> Result<(), String> {
+    settings.validate()?;
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO report_settings (instance_id, timezone, reporting_currency) VALUES (:instance_id, :timezone, :reporting_currency) \
+        ON DUPLICATE KEY UPDATE timezone = :timezone, reporting_currency = :reporting_currency", params! {
+        "instance_id" => settings.instance_id,
+        "timezone" => settings.timezone,
+        "reporting_currency" => settings.reporting_currency
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the timezone an instance reports in
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the timezone for
+
+## Returns
+    **Ok**: The timezone
+    **Err**: A summary of what went wrong
+*/
+pub fn get_timezone(database: Database, instance_id: String) -> Result<Tz, String> {
+    get_settings(database, instance_id)?.parse_timezone()
+}
+
+/**
+Aggregate the sales of the filtered orders per period.
+Periods are bucketed in the instance's timezone, so a day runs from local midnight to local midnight.
+Amounts are converted to the instance's reporting currency at the exchange rate stored on each order.
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to report on
+    **granularity** Length of the periods
+    **group_by** Dimension to split the periods by, if any
+
+## Returns
+    **Ok**: The rows, by period and then by group. Periods without orders are omitted
+    **Err**: A summary of what went wrong
+*/
+pub fn sales_report(database: Database, filter: &OrderFilter, granularity: Granularity, group_by: Option<GroupBy>) -> Result<Vec<ReportRow>, String> {
+    let settings = get_settings(database.clone(), filter.instance_id.clone())?;
+    let timezone = settings.parse_timezone()?;
+
+    let (condition, params) = filter.to_sql();
+    let query = match group_by {
+        Some(GroupBy::Product) => format!("SELECT o.order_id, o.order_date, IF(i.item_type = 'CUSTOM_AMOUNT_ITEM', 'Custom amounts', IF(i.sku = '', i.name, i.sku)) AS group_value, i.total AS revenue, i.quantity AS units, i.tax, 0 AS shipping, o.reporting_currency, o.exchange_rate \
+            FROM orders o INNER JOIN order_items i ON i.order_id = o.order_id WHERE {}", condition),
+        _ => format!("SELECT o.order_id, o.order_date, {} AS group_value, o.total_price AS revenue, o.quantity AS units, o.tax, o.shipping, o.reporting_currency, o.exchange_rate FROM orders o WHERE {}", match group_by {
+            Some(GroupBy::Currency) => "o.currency",
+            Some(GroupBy::PaymentStatus) => "o.payment_status",
+            Some(GroupBy::Channel) => "o.channel",
+            _ => "NULL"
+        }, condition)
+    };
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, String, Params>(query, Params::from(params));
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut buckets: BTreeMap<(i64, Option<String>), Totals> = BTreeMap::new();
+    for row in result.unwrap() {
+        let order_date: i64 = row.get("order_date").unwrap();
+        let group: Option<String> = row.get("group_value").unwrap();
+
+        let revenue: Decimal = row.get("revenue").unwrap();
+        let exchange_rate = exchange_rate(&row, &settings.reporting_currency);
+
+        let totals = buckets.entry((period_start(&timezone, granularity, order_date), group)).or_default();
+        totals.units += row.get::<i64, &str>("units").unwrap();
+        totals.original_revenue += revenue;
+
+        match exchange_rate {
+            Some(rate) => {
+                totals.revenue += revenue * rate;
+                totals.tax += row.get::<Decimal, &str>("tax").unwrap() * rate;
+                totals.shipping += row.get::<Decimal, &str>("shipping").unwrap() * rate;
+                totals.orders.insert(row.get("order_id").unwrap());
+            },
+            None => {
+                totals.unconverted.insert(row.get("order_id").unwrap());
+            }
+        }
+    }
+
+    let converted = |amount: Decimal| Money::new(amount, &settings.reporting_currency).round().amount;
+
+    Ok(buckets.into_iter().map(|((period_start, group), totals)| {
+        let order_count = totals.orders.len() as i64;
+
+        ReportRow {
+            period_start,
+            period:                 timezone.timestamp_opt(period_start, 0).unwrap().format("%Y-%m-%d").to_string(),
+            //The group is the order currency
+            original_revenue:       if group_by == Some(GroupBy::Currency) { group.as_deref().map(|currency| Money::new(totals.original_revenue, currency).round().amount) } else { None },
+            group,
+            currency:               settings.reporting_currency.clone(),
+            revenue:                converted(totals.revenue),
+            order_count,
+            average_order_value:    converted(totals.revenue.checked_div(Decimal::from(order_count)).unwrap_or_default()),
+            units_sold:             totals.units,
+            tax:                    converted(totals.tax),
+            shipping:               converted(totals.shipping),
+            unconverted_orders:     totals.unconverted.len() as i64
+        }
+    }).collect())
+}
+
+/**
+Get the exchange rate converting the amounts of an order to the reporting currency
+
+## Params
+    **row** A row with the order's 'reporting_currency' and 'exchange_rate' columns
+    **reporting_currency** The instance's current reporting currency
+
+## Returns
+    The rate, or None if the order hasn't been converted. Orders converted to a previous reporting currency
+    count as unconverted until they're converted again
+*/
+pub fn exchange_rate(row: &Row, reporting_currency: &str) -> Option<Decimal> {
+    let converted_to: Option<String> = row.get("reporting_currency").unwrap();
+    match row.get::<Option<f64>, &str>("exchange_rate").unwrap().map(Decimal::try_from) {
+        Some(Ok(rate)) if converted_to.as_deref() == Some(reporting_currency) => Some(rate),
+        _ => None
+    }
+}
+
+/// Get the start of the period containing a moment, as epoch seconds
+pub fn period_start(timezone: &Tz, granularity: Granularity, epoch: i64) -> i64 {
+    let date = timezone.timestamp_opt(epoch, 0).unwrap().date_naive();
+    let start = match granularity {
+        Granularity::Day => date,
+        Granularity::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        Granularity::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap()
+    };
+
+    //Midnight can be skipped by a DST transition, the period then starts at the first moment of the day
+    let midnight = start.and_hms_opt(0, 0, 0).unwrap();
+    match timezone.from_local_datetime(&midnight).earliest() {
+        Some(dt) => dt.timestamp(),
+        None => timezone.from_local_datetime(&(midnight + Duration::hours(1))).earliest().map(|dt| dt.timestamp()).unwrap_or(epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_start_at_local_midnight() {
+        let amsterdam: Tz = "Europe/Amsterdam".parse().unwrap();
+
+        //Wednesday 3 March 2021, 13:00 in Amsterdam
+        assert_eq!(period_start(&amsterdam, Granularity::Day, 1614772800), 1614726000);
+        assert_eq!(period_start(&amsterdam, Granularity::Week, 1614772800), 1614553200);
+        assert_eq!(period_start(&amsterdam, Granularity::Month, 1614772800), 1614553200);
+    }
+
+    #[test]
+    fn periods_start_after_a_skipped_midnight() {
+        //São Paulo skipped from 00:00 to 01:00 on 4 November 2018
+        let sao_paulo: Tz = "America/Sao_Paulo".parse().unwrap();
+        assert_eq!(period_start(&sao_paulo, Granularity::Day, 1541332800), 1541300400);
+    }
+
+    #[test]
+    fn unknown_timezones_and_currencies_are_rejected() {
+        let settings = |timezone: &str, reporting_currency: &str| ReportSettings { instance_id: String::new(), timezone: timezone.to_string(), reporting_currency: reporting_currency.to_string() };
+
+        assert!(settings("Europe/Amsterdam", "USD").validate().is_ok());
+        assert!(settings("Mars/Olympus", "USD").validate().is_err());
+        assert!(settings("Europe/Amsterdam", "usd").validate().is_err());
+        assert!(settings("Europe/Amsterdam", "ABC").validate().is_err());
+    }
+}
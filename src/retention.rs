@@ -0,0 +1,249 @@
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+use crate::gdpr::{anonymize_orders, order_and_address_ids, ERASED};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// OAuth states are only needed during the install flow, unused ones are dropped after this many seconds
+pub const STATE_MAX_AGE_SECONDS: i64 = 60 * 60;
+
+/// Retention settings for an instance. A setting of None means the data is kept indefinitely
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RetentionPolicy {
+    pub instance_id:                            String,
+
+    /// Anonymize the personal data on orders this many days after the order date
+    pub anonymize_after_days:                   Option<i64>,
+
+    /// Delete outbound webhook deliveries this many days after they were created
+    pub delete_webhook_deliveries_after_days:   Option<i64>,
+
+    /// Only report what the scheduled job would do, without changing anything
+    pub dry_run:                                bool
+}
+
+/// What applying a retention policy did, or would do in dry-run mode
+#[derive(Serialize, Default)]
+pub struct RetentionReport {
+    pub dry_run:                    bool,
+    pub orders_anonymized:          usize,
+    pub addresses_anonymized:       usize,
+    pub customers_anonymized:       usize,
+    pub webhook_deliveries_deleted: usize
+}
+
+/**
+Get the retention policy of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the policy for
+
+## Returns
+    **Ok**: The policy, or None if the instance has none
+    **Err**: A summary of what went wrong
+*/
+pub fn get_policy(database: Database, instance_id: String) -> Result<Option<RetentionPolicy>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT instance_id, anonymize_after_days, delete_webhook_deliveries_after_days, dry_run FROM retention_policies WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().iter().map(policy_from_row).next())
+}
+
+/**
+Get the retention policies of all instances
+
+## Params
+    **database** Instance of a Database object
+
+## Returns
+    **Ok**: The policies
+    **Err**: A summary of what went wrong
+*/
+pub fn get_policies(database: Database) -> Result<Vec<RetentionPolicy>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.query::<Row, &str>("SELECT instance_id, anonymize_after_days, delete_webhook_deliveries_after_days, dry_run FROM retention_policies");
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(result.unwrap().iter().map(policy_from_row).collect())
+}
+
+/**
+Create or replace the retention policy of an instance
+
+## Params
+    **database** Instance of a Database object
+    **policy** The new policy
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn set_policy(database: Database, policy: RetentionPolicy) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO retention_policies (instance_id, anonymize_after_days, delete_webhook_deliveries_after_days, dry_run) \
+        VALUES (:instance_id, :anonymize_after_days, :delete_webhook_deliveries_after_days, :dry_run) \
+        ON DUPLICATE KEY UPDATE anonymize_after_days = :anonymize_after_days, delete_webhook_deliveries_after_days = :delete_webhook_deliveries_after_days, dry_run = :dry_run", params! {
+        "instance_id" => policy.instance_id,
+        "anonymize_after_days" => policy.anonymize_after_days,
+        "delete_webhook_deliveries_after_days" => policy.delete_webhook_deliveries_after_days,
+        "dry_run" => policy.dry_run
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Apply a retention policy
+
+## Params
+    **database** Instance of a Database object
+    **policy** The policy to apply
+    **dry_run** Only count what would be affected, without changing anything
+
+## Returns
+    **Ok**: A report of the affected data
+    **Err**: A summary of what went wrong
+*/
+pub fn apply_policy(database: Database, policy: &RetentionPolicy, dry_run: bool) -> Result<RetentionReport, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let mut report = RetentionReport { dry_run, ..RetentionReport::default() };
+
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    if let Some(days) = policy.anonymize_after_days {
+        let cutoff = now - days * SECONDS_PER_DAY;
+
+        let result = tx.exec::<Row, &str, Params>("SELECT order_id, billing_address_id, shipping_address_id FROM orders \
+            WHERE instance_id = :instance_id AND order_date < :cutoff AND buyer_email <> :erased", params! {
+            "instance_id" => policy.instance_id.clone(),
+            "cutoff" => cutoff,
+            "erased" => ERASED
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        let (order_ids, address_ids) = order_and_address_ids(result.unwrap());
+        report.orders_anonymized = order_ids.len();
+        report.addresses_anonymized = address_ids.len();
+
+        if !dry_run {
+            anonymize_orders(&mut tx, &order_ids, &address_ids)?;
+        }
+
+        //Customers who haven't ordered since the cutoff only have anonymized orders left
+        let customers = tx.exec::<Row, &str, Params>("SELECT customer_id FROM customers WHERE instance_id = :instance_id AND last_order_date < :cutoff AND email <> :erased", params! {
+            "instance_id" => policy.instance_id.clone(),
+            "cutoff" => cutoff,
+            "erased" => ERASED
+        });
+
+        if customers.is_err() {
+            return Err(customers.err().unwrap().to_string());
+        }
+
+        let customer_ids: Vec<String> = customers.unwrap().iter().map(|row| row.get("customer_id").unwrap()).collect();
+        report.customers_anonymized = customer_ids.len();
+
+        if !dry_run {
+            for customer_id in &customer_ids {
+                let result = tx.exec_drop("UPDATE customers SET email = :erased, name = :erased, phone = :erased, wix_customer_id = :erased, customer_key = customer_id \
+                    WHERE customer_id = :customer_id", params! {
+                    "erased" => ERASED,
+                    "customer_id" => customer_id
+                });
+
+                if result.is_err() {
+                    return Err(result.err().unwrap().to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(days) = policy.delete_webhook_deliveries_after_days {
+        let cutoff = now - days * SECONDS_PER_DAY;
+
+        //Deliveries hold the order as it was sent, pending ones are kept until they are delivered or given up
+        let query = if dry_run {
+            "SELECT COUNT(*) AS count FROM webhook_deliveries WHERE instance_id = :instance_id AND created_at < :cutoff AND status <> 'PENDING'"
+        } else {
+            "DELETE FROM webhook_deliveries WHERE instance_id = :instance_id AND created_at < :cutoff AND status <> 'PENDING'"
+        };
+
+        let result = tx.exec::<Row, &str, Params>(query, params! {
+            "instance_id" => policy.instance_id.clone(),
+            "cutoff" => cutoff
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        report.webhook_deliveries_deleted = if dry_run {
+            result.unwrap().first().map(|row| row.get::<i64, &str>("count").unwrap() as usize).unwrap_or(0)
+        } else {
+            tx.affected_rows() as usize
+        };
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(report),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Delete OAuth states which were never used to complete an install
+
+## Params
+    **database** Instance of a Database object
+
+## Returns
+    **Ok**: The number of deleted states
+    **Err**: A summary of what went wrong
+*/
+pub fn delete_expired_states(database: Database) -> Result<usize, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_drop("DELETE FROM states WHERE created_at < :cutoff", params! {
+        "cutoff" => chrono::Utc::now().timestamp() - STATE_MAX_AGE_SECONDS
+    });
+
+    match result {
+        Ok(_) => Ok(conn.affected_rows() as usize),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn policy_from_row(row: &Row) -> RetentionPolicy {
+    RetentionPolicy {
+        instance_id:                            row.get("instance_id").unwrap(),
+        anonymize_after_days:                   row.get("anonymize_after_days").unwrap(),
+        delete_webhook_deliveries_after_days:   row.get("delete_webhook_deliveries_after_days").unwrap(),
+        dry_run:                                row.get("dry_run").unwrap()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,1016 @@
+use std::net::{IpAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::Url;
+use rust_decimal::Decimal;
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+
+use crate::database::Database;
+use crate::orders::get_order_details;
+use crate::types::order::OrderDetails;
+
+/// Seconds a webhook called by a rule gets to respond
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+/// A rule of an instance, evaluated against an order whenever it is imported or updated
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// Generated when the rule is first saved
+    #[serde(default)]
+    pub rule_id:        String,
+    pub name:           String,
+
+    #[serde(default = "default_enabled")]
+    pub enabled:        bool,
+
+    /// All conditions have to hold for the actions to run. A rule without conditions matches every order
+    #[serde(default)]
+    pub conditions:     Vec<Condition>,
+
+    /// Run in order when the rule matches
+    pub actions:        Vec<Action>
+}
+
+/// A test of a single order field, e.g. shipping_country not_equals 'NL'
+#[derive(Serialize, Deserialize)]
+pub struct Condition {
+    pub field:          Field,
+    pub operator:       Operator,
+
+    /// Compared case insensitively with text fields, and as a decimal number with numeric fields
+    pub value:          String
+}
+
+/// Order, buyer, address and line item fields a condition can test.
+/// Line item fields hold when any item matches, or for the negated operators when no item matches the value
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    OrderNumber,
+    Total,
+    Subtotal,
+    Tax,
+    Shipping,
+    Discount,
+    Currency,
+    PaymentStatus,
+    PaymentMethod,
+    Channel,
+    FulfillmentStatus,
+    DeliveryMethod,
+    Quantity,
+    WeightKg,
+    BuyerName,
+    BuyerEmail,
+    BuyerPhone,
+    BuyerNote,
+    BillingName,
+    BillingCompany,
+    BillingCountry,
+    BillingCity,
+    BillingZipCode,
+    ShippingName,
+    ShippingCompany,
+    ShippingCountry,
+    ShippingCity,
+    ShippingZipCode,
+    ItemName,
+    ItemSku,
+    ItemType,
+    ItemQuantity,
+    ItemPrice
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Contains,
+    NotContains
+}
+
+/// What a rule does with a matching order
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Tag the order, see crate::tags
+    Tag { tag: String },
+
+    /// Move the order to a workflow state, see crate::workflow
+    SetState { state: String },
+
+    /// Leave a notification for the staff of the instance
+    Notify { message: String },
+
+    /// POST the rule and the order as JSON to a URL
+    CallWebhook { url: String }
+}
+
+/// What caused the rules to be evaluated
+#[derive(Clone, Copy)]
+pub enum Trigger {
+    /// The order was imported from Wix
+    OrderCreated,
+
+    /// The order was updated with changes from Wix
+    OrderUpdated,
+
+    /// Evaluated on request of a staff member
+    Manual
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::OrderCreated => "ORDER_CREATED",
+            Trigger::OrderUpdated => "ORDER_UPDATED",
+            Trigger::Manual => "MANUAL"
+        }
+    }
+}
+
+/// The evaluation of a rule against an order, as logged
+#[derive(Serialize)]
+pub struct RuleEvaluation {
+    pub evaluation_id:  String,
+    pub order_id:       String,
+    pub rule_id:        String,
+
+    /// Name of the rule at the time of the evaluation
+    pub rule_name:      String,
+
+    /// 'ORDER_CREATED', 'ORDER_UPDATED' or 'MANUAL'
+    pub trigger:        String,
+    pub matched:        bool,
+
+    /// The actions which ran, empty if the rule did not match. Notifications and webhooks don't run again for an update of an order which already matched
+    pub actions:        Vec<ActionOutcome>,
+
+    /// Epoch seconds
+    pub evaluated_at:   i64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ActionOutcome {
+    /// Description of the action, e.g. "tag 'customs'"
+    pub action:         String,
+
+    /// Why the action failed, None if it succeeded
+    pub error:          Option<String>
+}
+
+/// A notification left by a rule
+#[derive(Serialize)]
+pub struct Notification {
+    pub notification_id:    String,
+    pub order_id:           String,
+    pub order_number:       i64,
+    pub rule_name:          String,
+    pub message:            String,
+
+    /// Epoch seconds
+    pub created_at:         i64
+}
+
+/// Body of the request made by a CallWebhook action
+#[derive(Serialize)]
+struct RuleWebhook<'a> {
+    event:      &'static str,
+    rule_id:    &'a str,
+    rule_name:  &'a str,
+    trigger:    &'static str,
+    order:      &'a OrderDetails
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Field {
+    /// Whether the field holds a number
+    fn is_numeric(&self) -> bool {
+        matches!(self, Field::OrderNumber | Field::Total | Field::Subtotal | Field::Tax | Field::Shipping | Field::Discount
+            | Field::Quantity | Field::WeightKg | Field::ItemQuantity | Field::ItemPrice)
+    }
+
+    /// The values of the field in an order. Empty if the order does not have the field, e.g. an order without shipping address
+    fn values(&self, details: &OrderDetails) -> Vec<String> {
+        let order = &details.order;
+        let billing = details.billing_address.iter();
+        let shipping = details.shipping_address.iter();
+        let items = details.items.iter();
+
+        match self {
+            Field::OrderNumber => vec![order.wix_order_id.to_string()],
+            Field::Total => vec![order.total_price.to_string()],
+            Field::Subtotal => vec![order.subtotal.to_string()],
+            Field::Tax => vec![order.tax.to_string()],
+            Field::Shipping => vec![order.shipping.to_string()],
+            Field::Discount => vec![order.discount.to_string()],
+            Field::Currency => vec![order.currency.clone()],
+            Field::PaymentStatus => vec![order.payment_status.clone()],
+            Field::PaymentMethod => vec![order.payment_method.clone()],
+            Field::Channel => vec![order.channel.clone()],
+            Field::FulfillmentStatus => vec![order.fulfillment_status.clone()],
+            Field::DeliveryMethod => vec![order.delivery_method.clone()],
+            Field::Quantity => vec![order.quantity.to_string()],
+            Field::WeightKg => order.weight_kg.iter().map(|weight| weight.to_string()).collect(),
+            Field::BuyerName => vec![details.buyer_name.clone()],
+            Field::BuyerEmail => vec![details.buyer_email.clone()],
+            Field::BuyerPhone => vec![details.buyer_phone.clone()],
+            Field::BuyerNote => vec![details.buyer_note.clone()],
+            Field::BillingName => billing.map(|address| address.addressee(&details.buyer_name).to_string()).collect(),
+            Field::BillingCompany => billing.map(|address| address.company.clone()).collect(),
+            Field::BillingCountry => billing.map(|address| address.country.clone()).collect(),
+            Field::BillingCity => billing.map(|address| address.city.clone()).collect(),
+            Field::BillingZipCode => billing.map(|address| address.zip_code.clone()).collect(),
+            Field::ShippingName => shipping.map(|address| address.addressee(&details.buyer_name).to_string()).collect(),
+            Field::ShippingCompany => shipping.map(|address| address.company.clone()).collect(),
+            Field::ShippingCountry => shipping.map(|address| address.country.clone()).collect(),
+            Field::ShippingCity => shipping.map(|address| address.city.clone()).collect(),
+            Field::ShippingZipCode => shipping.map(|address| address.zip_code.clone()).collect(),
+            Field::ItemName => items.map(|item| item.name.clone()).collect(),
+            Field::ItemSku => items.map(|item| item.sku.clone()).collect(),
+            Field::ItemType => items.map(|item| item.item_type.clone()).collect(),
+            Field::ItemQuantity => items.map(|item| item.quantity.to_string()).collect(),
+            Field::ItemPrice => items.map(|item| item.price.to_string()).collect()
+        }
+    }
+}
+
+impl Operator {
+    fn is_negated(&self) -> bool {
+        matches!(self, Operator::NotEquals | Operator::NotContains)
+    }
+
+    fn is_ordering(&self) -> bool {
+        matches!(self, Operator::GreaterThan | Operator::GreaterThanOrEqual | Operator::LessThan | Operator::LessThanOrEqual)
+    }
+
+    /// Test a single value, ignoring negation
+    fn test(&self, actual: &str, expected: &str, numeric: bool) -> bool {
+        if numeric {
+            let (actual, expected) = match (Decimal::from_str(actual), Decimal::from_str(expected.trim())) {
+                (Ok(actual), Ok(expected)) => (actual, expected),
+                _ => return false
+            };
+
+            return match self {
+                Operator::Equals | Operator::NotEquals => actual == expected,
+                Operator::GreaterThan => actual > expected,
+                Operator::GreaterThanOrEqual => actual >= expected,
+                Operator::LessThan => actual < expected,
+                Operator::LessThanOrEqual => actual <= expected,
+                Operator::Contains | Operator::NotContains => false
+            };
+        }
+
+        match self {
+            Operator::Equals | Operator::NotEquals => actual.trim().eq_ignore_ascii_case(expected.trim()),
+            Operator::Contains | Operator::NotContains => actual.to_lowercase().contains(&expected.trim().to_lowercase()),
+            _ => false
+        }
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds for an order
+    fn matches(&self, details: &OrderDetails) -> bool {
+        let numeric = self.field.is_numeric();
+        let any = self.field.values(details).iter().any(|actual| self.operator.test(actual, &self.value, numeric));
+        any != self.operator.is_negated()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.field.is_numeric() {
+            if matches!(self.operator, Operator::Contains | Operator::NotContains) {
+                return Err(format!("Field {:?} is a number and can not be tested with contains", self.field));
+            }
+
+            if Decimal::from_str(self.value.trim()).is_err() {
+                return Err(format!("Field {:?} is a number, '{}' is not", self.field, self.value));
+            }
+        } else if self.operator.is_ordering() {
+            return Err(format!("Field {:?} is text and can only be tested with equals or contains", self.field));
+        }
+
+        Ok(())
+    }
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::Tag { tag } => format!("tag '{}'", tag),
+            Action::SetState { state } => format!("set state '{}'", state),
+            Action::Notify { .. } => "notify".to_string(),
+            Action::CallWebhook { url } => format!("call webhook {}", url)
+        }
+    }
+
+    /// Whether the action runs again when an order which already matched is updated. Tags and states are set once,
+    /// notifications and webhooks would be repeated on every sync
+    fn repeats_on_update(&self) -> bool {
+        matches!(self, Action::Tag { .. } | Action::SetState { .. })
+    }
+}
+
+/**
+Check that a webhook URL uses https and does not point at a loopback, private or link-local address,
+so rules can't be used to reach OrderSync's own network
+
+## Params
+    **url** The URL of a CallWebhook action
+
+## Returns
+    **Ok**: The parsed URL
+    **Err**: Why the URL is not allowed
+*/
+fn validate_webhook_url(url: &str) -> Result<Url, String> {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Err(format!("Invalid webhook URL '{}': {}", url, e))
+    };
+
+    if parsed.scheme() != "https" {
+        return Err(format!("Webhook URL '{}' must use https", url));
+    }
+
+    //IPv6 hosts are given in brackets
+    let host = parsed.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_lowercase();
+    let internal = match IpAddr::from_str(&host) {
+        Ok(ip) => is_internal_ip(ip),
+        Err(_) => host.is_empty() || host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local") || host.ends_with(".internal")
+    };
+
+    if internal {
+        return Err(format!("Webhook URL '{}' points at a private or loopback host", url));
+    }
+
+    Ok(parsed)
+}
+
+/// Whether an address is loopback, private, link-local, shared (carrier-grade NAT) or unspecified
+fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || (octets[0] == 100 && (64..128).contains(&octets[1]))
+        },
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_internal_ip(IpAddr::V4(ip));
+            }
+
+            let first = ip.segments()[0];
+            ip.is_loopback() || ip.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/**
+Get the rules of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the rules for
+
+## Returns
+    **Ok**: The rules, in the order they are evaluated
+    **Err**: A summary of what went wrong
+*/
+pub fn get_rules(database: Database, instance_id: String) -> Result<Vec<Rule>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT rule_id, name, enabled, conditions, actions FROM rules WHERE instance_id = :instance_id ORDER BY position ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut rules = Vec::new();
+    for row in result.unwrap() {
+        let conditions: String = row.get("conditions").unwrap();
+        let actions: String = row.get("actions").unwrap();
+
+        let rule = match (serde_json::from_str(&conditions), serde_json::from_str(&actions)) {
+            (Ok(conditions), Ok(actions)) => Rule {
+                rule_id:    row.get("rule_id").unwrap(),
+                name:       row.get("name").unwrap(),
+                enabled:    row.get("enabled").unwrap(),
+                conditions,
+                actions
+            },
+            (Err(e), _) | (_, Err(e)) => return Err(format!("Rule {} is corrupt: {}", row.get::<String, &str>("rule_id").unwrap(), e))
+        };
+
+        rules.push(rule);
+    }
+
+    Ok(rules)
+}
+
+/**
+Replace the rules of an instance. Rules are evaluated in the order given
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to set the rules for
+    **rules** The new rules. Rules without an ID are new, rules which are not listed are removed
+
+## Returns
+    **Ok**: The saved rules, with their IDs
+    **Err**: A rule is invalid, or a summary of what went wrong
+*/
+pub fn set_rules(database: Database, instance_id: String, mut rules: Vec<Rule>) -> Result<Vec<Rule>, String> {
+    let states = crate::workflow::get_states(database.clone(), instance_id.clone())?;
+
+    for rule in &mut rules {
+        rule.name = rule.name.trim().to_string();
+        if rule.name.is_empty() {
+            return Err("The name of a rule may not be empty".to_string());
+        }
+
+        if rule.actions.is_empty() {
+            return Err(format!("Rule '{}' has no actions", rule.name));
+        }
+
+        for condition in &rule.conditions {
+            if let Err(e) = condition.validate() {
+                return Err(format!("Rule '{}': {}", rule.name, e));
+            }
+        }
+
+        for action in &rule.actions {
+            let result = match action {
+                Action::Tag { tag } => crate::tags::validate_tag(tag.trim()),
+                Action::SetState { state } if !states.iter().any(|known| known.key == *state) => Err(format!("Unknown state '{}'", state)),
+                Action::Notify { message } if message.trim().is_empty() => Err("The message of a notification may not be empty".to_string()),
+                Action::CallWebhook { url } => validate_webhook_url(url).map(|_| ()),
+                _ => Ok(())
+            };
+
+            if let Err(e) = result {
+                return Err(format!("Rule '{}': {}", rule.name, e));
+            }
+        }
+
+        if rule.rule_id.is_empty() {
+            rule.rule_id = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+        }
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let result = tx.exec_drop("DELETE FROM rules WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    for (position, rule) in rules.iter().enumerate() {
+        let result = tx.exec_drop("INSERT INTO rules (rule_id, instance_id, position, name, enabled, conditions, actions) \
+            VALUES (:rule_id, :instance_id, :position, :name, :enabled, :conditions, :actions)", params! {
+            "rule_id" => rule.rule_id.clone(),
+            "instance_id" => instance_id.clone(),
+            "position" => position,
+            "name" => rule.name.clone(),
+            "enabled" => rule.enabled,
+            "conditions" => serde_json::to_string(&rule.conditions).unwrap(),
+            "actions" => serde_json::to_string(&rule.actions).unwrap()
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(rules),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Evaluate the enabled rules of the order's instance against an order, and run the actions of the rules which match.
+A failing action does not stop the other actions, its error is logged with the evaluation.
+When an updated order already matched a rule at its previous evaluation, only the actions which repeat on update run again.
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+    **trigger** What caused the evaluation
+
+## Returns
+    **Ok**: The evaluations, as logged, or None if the order does not exist
+    **Err**: A summary of what went wrong
+*/
+pub fn evaluate_order(database: Database, order_id: String, trigger: Trigger) -> Result<Option<Vec<RuleEvaluation>>, String> {
+    let details = match get_order_details(database.clone(), &order_id)? {
+        Some(details) => details,
+        None => return Ok(None)
+    };
+
+    let instance_id = details.order.instance_id.clone();
+    let rules = get_rules(database.clone(), instance_id.clone())?;
+
+    let mut evaluations = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let matched = rule.conditions.iter().all(|condition| condition.matches(&details));
+        let still_matching = matched && matches!(trigger, Trigger::OrderUpdated) && previously_matched(database.clone(), &rule.rule_id, &order_id)?;
+        let actions: Vec<ActionOutcome> = if matched {
+            rule.actions.iter().filter(|action| !still_matching || action.repeats_on_update()).map(|action| ActionOutcome {
+                action: action.describe(),
+                error:  run_action(database.clone(), &details, rule, action, trigger).err()
+            }).collect()
+        } else {
+            Vec::new()
+        };
+
+        let evaluation = RuleEvaluation {
+            evaluation_id:  rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect(),
+            order_id:       order_id.clone(),
+            rule_id:        rule.rule_id.clone(),
+            rule_name:      rule.name.clone(),
+            trigger:        trigger.as_str().to_string(),
+            matched,
+            actions,
+            evaluated_at:   chrono::Utc::now().timestamp()
+        };
+
+        let mut conn = database.pool.get_conn().unwrap();
+        let result = conn.exec_drop("INSERT INTO rule_evaluations (evaluation_id, instance_id, order_id, rule_id, rule_name, trigger_event, matched, actions, evaluated_at) \
+            VALUES (:evaluation_id, :instance_id, :order_id, :rule_id, :rule_name, :trigger_event, :matched, :actions, :evaluated_at)", params! {
+            "evaluation_id" => evaluation.evaluation_id.clone(),
+            "instance_id" => instance_id.clone(),
+            "order_id" => evaluation.order_id.clone(),
+            "rule_id" => evaluation.rule_id.clone(),
+            "rule_name" => evaluation.rule_name.clone(),
+            "trigger_event" => evaluation.trigger.clone(),
+            "matched" => evaluation.matched,
+            "actions" => serde_json::to_string(&evaluation.actions).unwrap(),
+            "evaluated_at" => evaluation.evaluated_at
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        evaluations.push(evaluation);
+    }
+
+    Ok(Some(evaluations))
+}
+
+/// Whether the last logged evaluation of a rule against an order matched
+fn previously_matched(database: Database, rule_id: &str, order_id: &str) -> Result<bool, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<bool, &str, Params>("SELECT matched FROM rule_evaluations WHERE rule_id = :rule_id AND order_id = :order_id \
+        ORDER BY evaluated_at DESC LIMIT 1", params! {
+        "rule_id" => rule_id,
+        "order_id" => order_id
+    });
+
+    match result {
+        Ok(matched) => Ok(matched.unwrap_or(false)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the logged rule evaluations of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the evaluations for
+    **order_id** Only the evaluations of this order, if given
+    **limit** Maximum number of evaluations to return
+
+## Returns
+    **Ok**: The evaluations, most recent first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_evaluations(database: Database, instance_id: String, order_id: Option<String>, limit: u64) -> Result<Vec<RuleEvaluation>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT evaluation_id, order_id, rule_id, rule_name, trigger_event, matched, actions, evaluated_at FROM rule_evaluations \
+        WHERE instance_id = :instance_id AND (:order_id IS NULL OR order_id = :order_id) ORDER BY evaluated_at DESC LIMIT :limit", params! {
+        "instance_id" => instance_id,
+        "order_id" => order_id,
+        "limit" => limit
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| RuleEvaluation {
+            evaluation_id:  row.get("evaluation_id").unwrap(),
+            order_id:       row.get("order_id").unwrap(),
+            rule_id:        row.get("rule_id").unwrap(),
+            rule_name:      row.get("rule_name").unwrap(),
+            trigger:        row.get("trigger_event").unwrap(),
+            matched:        row.get("matched").unwrap(),
+            actions:        serde_json::from_str(&row.get::<String, &str>("actions").unwrap()).unwrap_or_default(),
+            evaluated_at:   row.get("evaluated_at").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the notifications rules left for an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the notifications for
+    **limit** Maximum number of notifications to return
+
+## Returns
+    **Ok**: The notifications, most recent first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_notifications(database: Database, instance_id: String, limit: u64) -> Result<Vec<Notification>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT n.notification_id, n.order_id, o.wix_order_id, n.rule_name, n.message, n.created_at \
+        FROM rule_notifications n INNER JOIN orders o ON o.order_id = n.order_id \
+        WHERE n.instance_id = :instance_id ORDER BY n.created_at DESC LIMIT :limit", params! {
+        "instance_id" => instance_id,
+        "limit" => limit
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| Notification {
+            notification_id:    row.get("notification_id").unwrap(),
+            order_id:           row.get("order_id").unwrap(),
+            order_number:       row.get("wix_order_id").unwrap(),
+            rule_name:          row.get("rule_name").unwrap(),
+            message:            row.get("message").unwrap(),
+            created_at:         row.get("created_at").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn run_action(database: Database, details: &OrderDetails, rule: &Rule, action: &Action, trigger: Trigger) -> Result<(), String> {
+    let order = &details.order;
+    let changed_by = format!("Rule '{}'", rule.name);
+
+    match action {
+        Action::Tag { tag } => {
+            crate::tags::add_tags(database, order.instance_id.clone(), std::slice::from_ref(&order.order_id), std::slice::from_ref(tag), &changed_by)?;
+            Ok(())
+        },
+        Action::SetState { state } => {
+            //Moving to the state the order is already in is not a transition, and not an error either
+            let workflow = crate::workflow::get_order_workflow(database.clone(), order.order_id.clone())?;
+            if workflow.state.as_deref() == Some(state.as_str()) {
+                return Ok(());
+            }
+
+            let result = crate::workflow::move_orders(database, order.instance_id.clone(), vec![order.order_id.clone()], state.clone(), changed_by)?;
+            match result.rejected.into_iter().next() {
+                Some(rejected) => Err(rejected.reason),
+                None => Ok(())
+            }
+        },
+        Action::Notify { message } => {
+            let notification_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+            let mut conn = database.pool.get_conn().unwrap();
+            let result = conn.exec_drop("INSERT INTO rule_notifications (notification_id, instance_id, order_id, rule_name, message, created_at) \
+                VALUES (:notification_id, :instance_id, :order_id, :rule_name, :message, :created_at)", params! {
+                "notification_id" => notification_id,
+                "instance_id" => order.instance_id.clone(),
+                "order_id" => order.order_id.clone(),
+                "rule_name" => rule.name.clone(),
+                "message" => message.clone(),
+                "created_at" => chrono::Utc::now().timestamp()
+            });
+
+            match result {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string())
+            }
+        },
+        Action::CallWebhook { url } => {
+            //The URL was checked when the rule was saved, the host may resolve to an internal address since
+            let url = validate_webhook_url(url)?;
+            let host = url.host_str().unwrap_or_default();
+            let addresses = match (host, url.port_or_known_default().unwrap_or(443)).to_socket_addrs() {
+                Ok(addresses) => addresses.collect::<Vec<_>>(),
+                Err(e) => return Err(format!("Unable to resolve '{}': {}", host, e))
+            };
+
+            if addresses.iter().any(|address| is_internal_ip(address.ip())) {
+                return Err(format!("Webhook host '{}' resolves to a private or loopback address", host));
+            }
+
+            let body = RuleWebhook {
+                event:      "rule_matched",
+                rule_id:    &rule.rule_id,
+                rule_name:  &rule.name,
+                trigger:    trigger.as_str(),
+                order:      details
+            };
+
+            let client = reqwest::blocking::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build();
+
+            let client = match client {
+                Ok(client) => client,
+                Err(e) => return Err(e.to_string())
+            };
+
+            let result = client.post(url)
+                .json(&body)
+                .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+                .send();
+
+            match result {
+                Ok(response) if response.status().is_success() => Ok(()),
+                Ok(response) => Err(format!("The webhook responded with {}", response.status())),
+                Err(e) => Err(e.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::order::{Address, Order, OrderItem};
+
+    const FIELDS: [Field; 33] = [Field::OrderNumber, Field::Total, Field::Subtotal, Field::Tax, Field::Shipping, Field::Discount, Field::Currency,
+        Field::PaymentStatus, Field::PaymentMethod, Field::Channel, Field::FulfillmentStatus, Field::DeliveryMethod, Field::Quantity, Field::WeightKg,
+        Field::BuyerName, Field::BuyerEmail, Field::BuyerPhone, Field::BuyerNote, Field::BillingName, Field::BillingCompany, Field::BillingCountry,
+        Field::BillingCity, Field::BillingZipCode, Field::ShippingName, Field::ShippingCompany, Field::ShippingCountry, Field::ShippingCity,
+        Field::ShippingZipCode, Field::ItemName, Field::ItemSku, Field::ItemType, Field::ItemQuantity, Field::ItemPrice];
+
+    const OPERATORS: [Operator; 8] = [Operator::Equals, Operator::NotEquals, Operator::GreaterThan, Operator::GreaterThanOrEqual,
+        Operator::LessThan, Operator::LessThanOrEqual, Operator::Contains, Operator::NotContains];
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    /// The value of each field in order_details
+    fn value_of(field: Field) -> &'static str {
+        match field {
+            Field::OrderNumber => "10042",
+            Field::Total => "54.45",
+            Field::Subtotal => "49.50",
+            Field::Tax => "10.40",
+            Field::Shipping => "4.95",
+            Field::Discount => "10.40",
+            Field::Currency => "EUR",
+            Field::PaymentStatus => "Paid",
+            Field::PaymentMethod => "CreditCard",
+            Field::Channel => "Web",
+            Field::FulfillmentStatus => "NotFulfilled",
+            Field::DeliveryMethod => "SHIP",
+            Field::Quantity => "3",
+            Field::WeightKg => "1.25",
+            Field::BuyerName => "Jan de Vries",
+            Field::BuyerEmail => "jan@example.com",
+            Field::BuyerPhone => "+31612345678",
+            Field::BuyerNote => "Please ring twice",
+            Field::BillingName => "Jan de Vries",
+            Field::BillingCompany => "Winkel B.V.",
+            Field::BillingCountry => "NL",
+            Field::BillingCity => "Utrecht",
+            Field::BillingZipCode => "1234 AB",
+            Field::ShippingName => "Piet Jansen",
+            Field::ShippingCompany => "Jansen Transport",
+            Field::ShippingCountry => "BE",
+            Field::ShippingCity => "Gent",
+            Field::ShippingZipCode => "9000",
+            Field::ItemName => "Mug",
+            Field::ItemSku => "SKU-MUG",
+            Field::ItemType => "PHYSICAL",
+            Field::ItemQuantity => "3",
+            Field::ItemPrice => "16.50"
+        }
+    }
+
+    fn address(address_type: &str, full_name: &str, company: &str, zip_code: &str, city: &str, country: &str) -> Address {
+        Address {
+            address_type:           address_type.to_string(),
+            full_name:              full_name.to_string(),
+            company:                company.to_string(),
+            email:                  String::new(),
+            phone:                  String::new(),
+            address_line_1:         String::new(),
+            address_line_2:         String::new(),
+            street:                 String::new(),
+            house_number:           String::new(),
+            house_number_addition:  String::new(),
+            zip_code:               zip_code.to_string(),
+            city:                   city.to_string(),
+            country:                country.to_string(),
+            needs_review:           false
+        }
+    }
+
+    fn item(name: &str, item_type: &str, quantity: i64, price: &str) -> OrderItem {
+        OrderItem {
+            order_item_id:          name.to_string(),
+            product_id:             None,
+            variant_id:             None,
+            item_type:              item_type.to_string(),
+            name:                   name.to_string(),
+            sku:                    format!("SKU-{}", name.to_uppercase()),
+            quantity,
+            weight:                 0.0,
+            weight_kg:              None,
+            price:                  decimal(price),
+            total:                  decimal(price) * Decimal::from(quantity),
+            tax:                    Decimal::ZERO,
+            tax_group_id:           "standard".to_string(),
+            tax_included_in_price:  false,
+            discount:               Decimal::ZERO,
+            options:                Vec::new()
+        }
+    }
+
+    fn order_details() -> OrderDetails {
+        OrderDetails {
+            order: Order {
+                order_id:           "order".to_string(),
+                instance_id:        "instance".to_string(),
+                wix_order_id:       10042,
+                order_date:         1614556800,
+                currency:           "EUR".to_string(),
+                payment_status:     "Paid".to_string(),
+                payment_method:     "CreditCard".to_string(),
+                channel:            "Web".to_string(),
+                fulfillment_status: "NotFulfilled".to_string(),
+                delivery_method:    "SHIP".to_string(),
+                requires_shipping:  true,
+                total_price:        decimal("54.45"),
+                subtotal:           decimal("49.50"),
+                tax:                decimal("10.40"),
+                shipping:           decimal("4.95"),
+                discount:           decimal("10.40"),
+                quantity:           3,
+                weight:             1.25,
+                weight_unit:        "KG".to_string(),
+                weight_kg:          Some(1.25)
+            },
+            buyer_name:         "Jan de Vries".to_string(),
+            buyer_email:        "jan@example.com".to_string(),
+            buyer_phone:        "+31612345678".to_string(),
+            buyer_note:         "Please ring twice".to_string(),
+            billing_address:    Some(address("BILLING", "", "Winkel B.V.", "1234 AB", "Utrecht", "NL")),
+            shipping_address:   Some(address("SHIPPING", "Piet Jansen", "Jansen Transport", "9000", "Gent", "BE")),
+            items:              vec![item("Mug", "PHYSICAL", 3, "16.50")]
+        }
+    }
+
+    fn condition(field: Field, operator: Operator, value: &str) -> Condition {
+        Condition { field, operator, value: value.to_string() }
+    }
+
+    /// Whether a valid condition holds for order_details, None if the condition is invalid
+    fn holds(field: Field, operator: Operator, value: &str) -> std::option::Option<bool> {
+        let condition = condition(field, operator, value);
+        condition.validate().ok().map(|_| condition.matches(&order_details()))
+    }
+
+    #[test]
+    fn numeric_fields_are_compared_as_numbers() {
+        for field in FIELDS.iter().copied().filter(Field::is_numeric) {
+            let actual = decimal(value_of(field));
+            let lower = (actual - decimal("0.01")).to_string();
+            let higher = (actual + decimal("0.01")).to_string();
+            let actual = actual.to_string();
+
+            for operator in OPERATORS.iter().copied() {
+                let expected = match operator {
+                    Operator::Equals => [Some(false), Some(true), Some(false)],
+                    Operator::NotEquals => [Some(true), Some(false), Some(true)],
+                    Operator::GreaterThan => [Some(true), Some(false), Some(false)],
+                    Operator::GreaterThanOrEqual => [Some(true), Some(true), Some(false)],
+                    Operator::LessThan => [Some(false), Some(false), Some(true)],
+                    Operator::LessThanOrEqual => [Some(false), Some(true), Some(true)],
+                    Operator::Contains | Operator::NotContains => [None, None, None]
+                };
+
+                let results = [holds(field, operator, &lower), holds(field, operator, &actual), holds(field, operator, &higher)];
+                assert_eq!(results, expected, "{:?} against {} - 0.01, {} and + 0.01", field, actual, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn text_fields_are_compared_case_insensitively() {
+        for field in FIELDS.iter().copied().filter(|field| !field.is_numeric()) {
+            let actual = value_of(field);
+            let part: String = actual.chars().skip(1).collect::<String>().to_uppercase();
+
+            for operator in OPERATORS.iter().copied() {
+                let expected = match operator {
+                    Operator::Equals => [Some(true), Some(false), Some(false)],
+                    Operator::NotEquals => [Some(false), Some(true), Some(true)],
+                    Operator::Contains => [Some(true), Some(true), Some(false)],
+                    Operator::NotContains => [Some(false), Some(false), Some(true)],
+                    _ => [None, None, None]
+                };
+
+                let results = [holds(field, operator, &format!(" {} ", actual.to_uppercase())), holds(field, operator, &part), holds(field, operator, "zzz")];
+                assert_eq!(results, expected, "{:?} against '{}'", field, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn money_is_compared_exactly() {
+        assert_eq!(holds(Field::Total, Operator::Equals, "54.450"), Some(true));
+        assert_eq!(holds(Field::Total, Operator::Equals, "54.4500000001"), Some(false));
+        assert_eq!(holds(Field::Total, Operator::GreaterThan, "54.4499999999"), Some(true));
+        assert_eq!(holds(Field::Tax, Operator::Equals, "10.4"), Some(true));
+
+        //0.1 + 0.2 is 0.30000000000000004 as f64
+        let mut details = order_details();
+        details.order.discount = decimal("0.1") + decimal("0.2");
+        assert!(condition(Field::Discount, Operator::LessThanOrEqual, "0.3").matches(&details));
+        assert!(!condition(Field::Discount, Operator::GreaterThan, "0.3").matches(&details));
+    }
+
+    #[test]
+    fn item_fields_match_any_item() {
+        let mut details = order_details();
+        details.items.push(item("Ebook", "DIGITAL", 1, "20.00"));
+
+        assert!(condition(Field::ItemType, Operator::Equals, "digital").matches(&details));
+        assert!(!condition(Field::ItemType, Operator::NotEquals, "digital").matches(&details));
+        assert!(condition(Field::ItemPrice, Operator::GreaterThan, "19.99").matches(&details));
+        assert!(!condition(Field::ItemSku, Operator::NotContains, "SKU-").matches(&details));
+    }
+
+    #[test]
+    fn missing_fields_only_match_negated_operators() {
+        let mut details = order_details();
+        details.shipping_address = None;
+        details.order.weight_kg = None;
+
+        assert!(!condition(Field::ShippingCountry, Operator::Equals, "BE").matches(&details));
+        assert!(condition(Field::ShippingCountry, Operator::NotEquals, "BE").matches(&details));
+        assert!(!condition(Field::WeightKg, Operator::LessThan, "10").matches(&details));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        assert!(condition(Field::Total, Operator::Contains, "5").validate().is_err());
+        assert!(condition(Field::Total, Operator::Equals, "five").validate().is_err());
+        assert!(condition(Field::Total, Operator::Equals, "").validate().is_err());
+        assert!(condition(Field::BuyerName, Operator::GreaterThan, "A").validate().is_err());
+        assert!(condition(Field::ItemPrice, Operator::LessThanOrEqual, " 10.00 ").validate().is_ok());
+    }
+
+    #[test]
+    fn rules_parse_from_json() {
+        let rule: Rule = serde_json::from_str(r#"{"name": "Big orders", "conditions": [{"field": "total", "operator": "greater_than_or_equal", "value": "100"}],
+            "actions": [{"type": "tag", "tag": "big"}, {"type": "call_webhook", "url": "https://example.com/hook"}]}"#).unwrap();
+
+        assert!(rule.enabled);
+        assert!(rule.conditions[0].field == Field::Total && rule.conditions[0].operator == Operator::GreaterThanOrEqual);
+        assert!(serde_json::from_str::<Rule>(r#"{"name": "Unknown", "conditions": [{"field": "colour", "operator": "equals", "value": "red"}], "actions": []}"#).is_err());
+        assert!(serde_json::from_str::<Rule>(r#"{"name": "Unknown", "actions": [{"type": "email", "to": "x@example.com"}]}"#).is_err());
+    }
+
+    #[test]
+    fn webhooks_must_use_https_and_a_public_host() {
+        assert!(validate_webhook_url("https://example.com/hook").is_ok());
+        assert!(validate_webhook_url("https://93.184.216.34:8443/hook").is_ok());
+
+        for url in &["http://example.com/hook", "ftp://example.com", "not a url", "https://localhost/hook", "https://api.localhost/hook",
+            "https://printer.local/hook", "https://127.0.0.1/hook", "https://10.1.2.3/hook", "https://172.16.0.1/hook", "https://192.168.1.1/hook",
+            "https://169.254.169.254/latest/meta-data", "https://100.64.0.1/hook", "https://0.0.0.0/hook", "https://[::1]/hook", "https://[fd00::1]/hook",
+            "https://[fe80::1]/hook", "https://[::ffff:127.0.0.1]/hook"] {
+            assert!(validate_webhook_url(url).is_err(), "{} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn only_tags_and_states_repeat_on_update() {
+        assert!(Action::Tag { tag: "big".to_string() }.repeats_on_update());
+        assert!(Action::SetState { state: "review".to_string() }.repeats_on_update());
+        assert!(!Action::Notify { message: "Check this order".to_string() }.repeats_on_update());
+        assert!(!Action::CallWebhook { url: "https://example.com/hook".to_string() }.repeats_on_update());
+    }
+}
@@ -0,0 +1,203 @@
+use serde::Serialize;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use qstring::QString;
+use rand::Rng;
+
+use crate::database::Database;
+use crate::order_filter::OrderFilter;
+
+/// A named order filter, saved per instance
+#[derive(Serialize)]
+pub struct SavedView {
+    pub view_id:        String,
+    pub name:           String,
+
+    /// The filter as query parameters, e.g. 'paymentStatus=Paid&fulfillmentStatus=NotFulfilled&tags=gift&olderThanDays=2'.
+    /// See OrderFilter::from_query for the parameters
+    pub query:          String,
+
+    /// Epoch seconds
+    pub updated_at:     i64
+}
+
+/**
+Get the saved views of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the views for
+
+## Returns
+    **Ok**: The views, by name
+    **Err**: A summary of what went wrong
+*/
+pub fn get_views(database: Database, instance_id: String) -> Result<Vec<SavedView>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT view_id, name, query, updated_at FROM saved_views WHERE instance_id = :instance_id ORDER BY name ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(view_from_row).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get a saved view
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the view belongs to
+    **view** The ID or the name of the view
+
+## Returns
+    **Ok**: The view, or None if the instance has no such view
+    **Err**: A summary of what went wrong
+*/
+pub fn get_view(database: Database, instance_id: String, view: String) -> Result<Option<SavedView>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT view_id, name, query, updated_at FROM saved_views \
+        WHERE instance_id = :instance_id AND (view_id = :view OR name = :view)", params! {
+        "instance_id" => instance_id,
+        "view" => view
+    });
+
+    match result {
+        Ok(row) => Ok(row.as_ref().map(view_from_row)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Save a view. A view with the same name is replaced
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to save the view for
+    **name** Name of the view, e.g. 'Gifts to ship'
+    **query** The filter as query parameters, without 'instanceId'
+
+## Returns
+    **Ok**: The saved view
+    **Err**: The name or the filter is invalid, or a summary of what went wrong
+*/
+pub fn save_view(database: Database, instance_id: String, name: String, query: String) -> Result<SavedView, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("The name of a view may not be empty".to_string());
+    }
+
+    let query = query.trim_start_matches('?').to_string();
+    let qstring = QString::from(query.as_str());
+    if qstring.has("instanceId") || qstring.has("view") {
+        return Err("A view may not contain the parameters 'instanceId' or 'view'".to_string());
+    }
+
+    //Validate the filter the same way the endpoints using it will
+    let mut pairs = qstring.into_pairs();
+    pairs.push(("instanceId".to_string(), instance_id.clone()));
+    OrderFilter::from_query(&QString::new(pairs))?;
+
+    let updated_at = chrono::Utc::now().timestamp();
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<String, &str, Params>("SELECT view_id FROM saved_views WHERE instance_id = :instance_id AND name = :name", params! {
+        "instance_id" => instance_id.clone(),
+        "name" => name.clone()
+    });
+
+    let view_id = match result {
+        Ok(Some(view_id)) => {
+            let result = conn.exec_drop("UPDATE saved_views SET query = :query, updated_at = :updated_at WHERE view_id = :view_id", params! {
+                "view_id" => view_id.clone(),
+                "query" => query.clone(),
+                "updated_at" => updated_at
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+
+            view_id
+        },
+        Ok(None) => {
+            let view_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+            let result = conn.exec_drop("INSERT INTO saved_views (view_id, instance_id, name, query, updated_at) VALUES (:view_id, :instance_id, :name, :query, :updated_at)", params! {
+                "view_id" => view_id.clone(),
+                "instance_id" => instance_id,
+                "name" => name.clone(),
+                "query" => query.clone(),
+                "updated_at" => updated_at
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+
+            view_id
+        },
+        Err(e) => return Err(e.to_string())
+    };
+
+    Ok(SavedView { view_id, name, query, updated_at })
+}
+
+/**
+Delete a saved view
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the view belongs to
+    **view_id** The view to delete
+
+## Returns
+    **Ok**: Whether the view existed
+    **Err**: A summary of what went wrong
+*/
+pub fn delete_view(database: Database, instance_id: String, view_id: String) -> Result<bool, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_iter("DELETE FROM saved_views WHERE view_id = :view_id AND instance_id = :instance_id", params! {
+        "view_id" => view_id,
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(result) => Ok(result.affected_rows() > 0),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Apply a view to the query parameters of a request. Parameters given in the request take precedence over those of the view
+
+## Params
+    **qstring** The query parameters of the request
+    **view** The view to apply
+
+## Returns
+    The combined query parameters, without 'view'
+*/
+pub fn apply_view(qstring: &QString, view: &SavedView) -> QString {
+    let mut pairs: Vec<(String, String)> = qstring.to_pairs().into_iter()
+        .filter(|(key, _)| *key != "view")
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    for (key, value) in QString::from(view.query.as_str()).into_pairs() {
+        if !pairs.iter().any(|(existing, _)| *existing == key) {
+            pairs.push((key, value));
+        }
+    }
+
+    QString::new(pairs)
+}
+
+fn view_from_row(row: &Row) -> SavedView {
+    SavedView {
+        view_id:        row.get("view_id").unwrap(),
+        name:           row.get("name").unwrap(),
+        query:          row.get("query").unwrap(),
+        updated_at:     row.get("updated_at").unwrap()
+    }
+}
\ No newline at end of file
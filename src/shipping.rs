@@ -0,0 +1,454 @@
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+
+use crate::carriers::{carrier_for, ShipmentRequest};
+use crate::database::Database;
+use crate::order_filter::OrderFilter;
+use crate::orders::get_order_details;
+use crate::types::order::{Address, OrderDetails};
+use crate::wix::order_writeback::{submit_mutation, MutationOutcome, MutationType, TrackingInfo};
+
+/// Shipping settings of an instance
+#[derive(Serialize, Deserialize)]
+pub struct ShippingSettings {
+    pub instance_id:            String,
+
+    /// Name of the carrier the limit applies to, e.g. 'PostNL'
+    pub carrier:                String,
+
+    /// Heaviest parcel the carrier accepts, in kilograms
+    pub max_parcel_weight_kg:   f64,
+
+    /// Base URL of the carrier's shipment API
+    #[serde(default)]
+    pub api_url:                String,
+
+    /// Never returned once stored
+    #[serde(default, skip_serializing)]
+    pub api_key:                String,
+
+    /// Customer code assigned by the carrier
+    #[serde(default)]
+    pub customer_code:          String,
+
+    /// Whether to add the tracking number of a new shipment to the order in Wix
+    #[serde(default)]
+    pub push_tracking:          bool
+}
+
+/// A shipment registered with a carrier
+#[derive(Serialize)]
+pub struct Shipment {
+    pub shipment_id:        String,
+    pub order_id:           String,
+    pub carrier:            String,
+
+    /// 'PENDING' while the carrier is registering it, 'CREATED' once it is registered
+    pub status:             String,
+
+    /// Empty while the shipment is pending
+    pub tracking_number:    String,
+    pub tracking_url:       Option<String>,
+    pub created_at:         i64,
+
+    /// Outcome of adding the tracking number to the order in Wix. None if this is not enabled or failed
+    pub tracking_pushed:    Option<MutationOutcome>,
+
+    /// Why the tracking number could not be added to the order in Wix
+    pub tracking_error:     Option<String>
+}
+
+/// A line item in a parcel
+#[derive(Serialize)]
+pub struct ManifestItem {
+    pub name:           String,
+    pub sku:            String,
+    pub quantity:       i64,
+
+    /// Weight of all items on the line, in kilograms
+    pub weight_kg:      Option<f64>
+}
+
+/// An order to be shipped as a single parcel
+#[derive(Serialize)]
+pub struct ManifestEntry {
+    pub order_id:               String,
+    pub order_number:           i64,
+    pub buyer_name:             String,
+
+    /// Who the parcel is addressed to: the addressee of the shipping address, or the buyer
+    pub recipient_name:         String,
+    pub shipping_address:       Option<Address>,
+    pub items:                  Vec<ManifestItem>,
+
+    /// Weight as declared on the order, in the store's unit
+    pub declared_weight:        f64,
+    pub declared_weight_unit:   String,
+
+    /// Weight of the parcel in kilograms: the line item weights when all items have one, otherwise the declared weight
+    pub weight_kg:              Option<f64>,
+
+    /// Whether the parcel is heavier than the carrier accepts
+    pub exceeds_limit:          bool,
+
+    /// Problems which need attention before shipping
+    pub warnings:               Vec<String>
+}
+
+/**
+Get the shipping settings of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the settings for
+
+## Returns
+    **Ok**: The settings, or None if the instance has none
+    **Err**: A summary of what went wrong
+*/
+pub fn get_settings(database: Database, instance_id: String) -> Result<Option<ShippingSettings>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Row, &str, Params>("SELECT instance_id, carrier, max_parcel_weight_kg, api_url, api_key, customer_code, push_tracking FROM shipping_settings WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(row) => Ok(row.map(|row| ShippingSettings {
+            instance_id:            row.get("instance_id").unwrap(),
+            carrier:                row.get("carrier").unwrap(),
+            max_parcel_weight_kg:   row.get("max_parcel_weight_kg").unwrap(),
+            api_url:                row.get("api_url").unwrap(),
+            api_key:                row.get("api_key").unwrap(),
+            customer_code:          row.get("customer_code").unwrap(),
+            push_tracking:          row.get("push_tracking").unwrap()
+        })),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Create or update the shipping settings of an instance. An empty API key keeps the stored key
+
+## Params
+    **database** Instance of a Database object
+    **settings** The new settings
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong
+*/
+pub fn set_settings(database: Database, settings: ShippingSettings) -> Result<(), String> {
+    if settings.max_parcel_weight_kg <= 0.0 {
+        return Err("The maximum parcel weight must be positive".to_string());
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO shipping_settings (instance_id, carrier, max_parcel_weight_kg, api_url, api_key, customer_code, push_tracking) \
+        VALUES (:instance_id, :carrier, :max_parcel_weight_kg, :api_url, :api_key, :customer_code, :push_tracking) \
+        ON DUPLICATE KEY UPDATE carrier = :carrier, max_parcel_weight_kg = :max_parcel_weight_kg, api_url = :api_url, \
+        api_key = IF(:api_key = '', api_key, :api_key), customer_code = :customer_code, push_tracking = :push_tracking", params! {
+        "instance_id" => settings.instance_id,
+        "carrier" => settings.carrier,
+        "max_parcel_weight_kg" => settings.max_parcel_weight_kg,
+        "api_url" => settings.api_url,
+        "api_key" => settings.api_key,
+        "customer_code" => settings.customer_code,
+        "push_tracking" => settings.push_tracking
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Build the shipping manifest for the filtered orders, one parcel per order
+
+## Params
+    **database** Instance of a Database object
+    **filter** The orders to ship
+
+## Returns
+    **Ok**: The parcels, by order date
+    **Err**: A summary of what went wrong
+*/
+pub fn build_manifest(database: Database, filter: &OrderFilter) -> Result<Vec<ManifestEntry>, String> {
+    let settings = get_settings(database.clone(), filter.instance_id.clone())?;
+
+    let (condition, params) = filter.to_sql();
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<String, String, Params>(format!("SELECT o.order_id FROM orders o WHERE {} ORDER BY o.order_date ASC", condition), Params::from(params));
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut manifest = Vec::new();
+    for order_id in result.unwrap() {
+        if let Some(details) = get_order_details(database.clone(), &order_id)? {
+            manifest.push(manifest_entry(details, settings.as_ref()));
+        }
+    }
+
+    Ok(manifest)
+}
+
+fn manifest_entry(details: OrderDetails, settings: Option<&ShippingSettings>) -> ManifestEntry {
+    let mut warnings = Vec::new();
+
+    //Digital and custom amount items are not in the parcel
+    let items: Vec<ManifestItem> = details.items.iter().filter(|item| item.requires_shipping()).map(|item| ManifestItem {
+        name:       item.name.clone(),
+        sku:        item.sku.clone(),
+        quantity:   item.quantity,
+        weight_kg:  item.weight_kg.filter(|weight| *weight > 0.0).map(|weight| weight * item.quantity as f64)
+    }).collect();
+
+    let weight_kg = if !items.is_empty() && items.iter().all(|item| item.weight_kg.is_some()) {
+        Some(items.iter().filter_map(|item| item.weight_kg).sum())
+    } else {
+        if items.iter().any(|item| item.weight_kg.is_none()) {
+            warnings.push("Not all line items have a weight, the declared order weight is used".to_string());
+        }
+        details.order.weight_kg
+    };
+
+    if details.order.weight_kg.is_none() {
+        warnings.push("The store's weight unit is not specified".to_string());
+    }
+
+    match &details.shipping_address {
+        Some(address) if address.is_pickup() => warnings.push("The order is picked up by the buyer".to_string()),
+        Some(_) => (),
+        None => warnings.push("The order has no shipping address".to_string())
+    }
+
+    let recipient_name = match &details.shipping_address {
+        Some(address) => address.addressee(&details.buyer_name).to_string(),
+        None => details.buyer_name.clone()
+    };
+
+    let exceeds_limit = match settings {
+        Some(settings) => details.order.weight_kg.into_iter().chain(weight_kg).any(|weight| weight > settings.max_parcel_weight_kg),
+        None => false
+    };
+
+    if let (true, Some(settings)) = (exceeds_limit, settings) {
+        warnings.push(format!("The parcel exceeds the {} limit of {} kg", settings.carrier, settings.max_parcel_weight_kg));
+    }
+
+    ManifestEntry {
+        order_id:               details.order.order_id,
+        order_number:           details.order.wix_order_id,
+        buyer_name:             details.buyer_name,
+        recipient_name,
+        shipping_address:       details.shipping_address,
+        items,
+        declared_weight:        details.order.weight,
+        declared_weight_unit:   details.order.weight_unit,
+        weight_kg,
+        exceeds_limit,
+        warnings
+    }
+}
+
+/**
+Register an order as a single parcel with the instance's carrier and store the label.
+The shipment is stored as 'PENDING' before it is sent to the carrier, and becomes 'CREATED' or 'FAILED' once the carrier responded.
+An order has at most one pending or created shipment: if it already has one, that shipment is returned and the carrier is not called.
+When enabled in the settings, the tracking number is added to the order in Wix as well.
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order to ship
+
+## Returns
+    **Ok**: The shipment, or None if the order does not exist
+    **Err**: A summary of what went wrong, e.g. the carrier rejected the shipment
+*/
+pub fn create_shipment(database: Database, order_id: String) -> Result<Option<Shipment>, String> {
+    let details = match get_order_details(database.clone(), &order_id)? {
+        Some(details) => details,
+        None => return Ok(None)
+    };
+
+    //The order is locked until the pending shipment is stored, so concurrent requests can't both register a parcel
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let result = tx.exec_drop("SELECT order_id FROM orders WHERE order_id = :order_id FOR UPDATE", params! {
+        "order_id" => order_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let result = tx.exec_first::<Row, &str, Params>("SELECT shipment_id, carrier, status, tracking_number, tracking_url, created_at FROM shipments \
+        WHERE order_id = :order_id AND status IN ('PENDING', 'CREATED') ORDER BY created_at DESC LIMIT 1", params! {
+        "order_id" => order_id.clone()
+    });
+
+    match result {
+        Ok(Some(row)) => return Ok(Some(Shipment {
+            shipment_id:        row.get("shipment_id").unwrap(),
+            order_id,
+            carrier:            row.get("carrier").unwrap(),
+            status:             row.get("status").unwrap(),
+            tracking_number:    row.get::<Option<String>, &str>("tracking_number").unwrap().unwrap_or_default(),
+            tracking_url:       row.get("tracking_url").unwrap(),
+            created_at:         row.get("created_at").unwrap(),
+            tracking_pushed:    None,
+            tracking_error:     None
+        })),
+        Ok(None) => {},
+        Err(e) => return Err(e.to_string())
+    }
+
+    if !details.order.requires_shipping {
+        return Err("The order has no items which have to be shipped".to_string());
+    }
+
+    let settings = match get_settings(database.clone(), details.order.instance_id.clone())? {
+        Some(settings) => settings,
+        None => return Err("No carrier is configured for this instance".to_string())
+    };
+
+    let carrier = carrier_for(&settings)?;
+    let buyer_email = details.buyer_email.clone();
+    let buyer_phone = details.buyer_phone.clone();
+    let entry = manifest_entry(details, Some(&settings));
+
+    let address = match entry.shipping_address {
+        Some(address) if address.is_pickup() => return Err("The order is picked up by the buyer and is not shipped".to_string()),
+        Some(address) => address,
+        None => return Err("The order has no shipping address".to_string())
+    };
+
+    //The carrier notifies the recipient, who is not necessarily the buyer
+    let email = if address.email.is_empty() { buyer_email } else { address.email.clone() };
+    let phone = if address.phone.is_empty() { buyer_phone } else { address.phone.clone() };
+
+    let weight_kg = match entry.weight_kg {
+        Some(weight_kg) if !entry.exceeds_limit => weight_kg,
+        Some(weight_kg) => return Err(format!("The parcel weighs {} kg, which exceeds the {} limit of {} kg", weight_kg, settings.carrier, settings.max_parcel_weight_kg)),
+        None => return Err("The weight of the parcel is unknown".to_string())
+    };
+
+    let shipment_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let created_at = chrono::Utc::now().timestamp();
+
+    //Stored before the carrier is called, so a shipment registered with the carrier is never untraceable here
+    let result = tx.exec_drop("INSERT INTO shipments (shipment_id, order_id, instance_id, carrier, status, created_at) \
+        VALUES (:shipment_id, :order_id, :instance_id, :carrier, 'PENDING', :created_at)", params! {
+        "shipment_id" => shipment_id.clone(),
+        "order_id" => order_id.clone(),
+        "instance_id" => settings.instance_id.clone(),
+        "carrier" => carrier.name(),
+        "created_at" => created_at
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    if let Err(e) = tx.commit() {
+        return Err(e.to_string());
+    }
+
+    let created = carrier.create_shipment(&ShipmentRequest {
+        reference:      entry.order_number.to_string(),
+        recipient_name: entry.recipient_name,
+        company:        address.company.clone(),
+        email,
+        phone,
+        address,
+        weight_kg
+    });
+
+    let created = match created {
+        Ok(created) => created,
+        Err(e) => {
+            let result = conn.exec_drop("UPDATE shipments SET status = 'FAILED', error = :error WHERE shipment_id = :shipment_id", params! {
+                "error" => e.clone(),
+                "shipment_id" => shipment_id
+            });
+
+            if result.is_err() {
+                return Err(format!("{}, and storing the failure failed: {}", e, result.err().unwrap()));
+            }
+
+            return Err(e);
+        }
+    };
+
+    let result = conn.exec_drop("UPDATE shipments SET status = 'CREATED', tracking_number = :tracking_number, tracking_url = :tracking_url, label = :label \
+        WHERE shipment_id = :shipment_id", params! {
+        "tracking_number" => created.tracking_number.clone(),
+        "tracking_url" => created.tracking_url.clone(),
+        "label" => created.label_pdf,
+        "shipment_id" => shipment_id.clone()
+    });
+
+    //The shipment exists at the carrier, so the tracking number must not get lost with the error
+    if result.is_err() {
+        return Err(format!("{} registered shipment {} with tracking number {}, but storing it failed: {}", carrier.name(), shipment_id, created.tracking_number, result.err().unwrap()));
+    }
+
+    //The shipment exists at the carrier now, so failing to update Wix is reported rather than returned as an error
+    let (tracking_pushed, tracking_error) = if settings.push_tracking {
+        let tracking_info = TrackingInfo {
+            tracking_number:    created.tracking_number.clone(),
+            shipping_provider:  carrier.name().to_string(),
+            tracking_link:      created.tracking_url.clone()
+        };
+
+        match submit_mutation(database, settings.instance_id.clone(), order_id.clone(), MutationType::AddTracking, serde_json::to_string(&tracking_info).unwrap()) {
+            Ok(outcome) => (Some(outcome), None),
+            Err(e) => (None, Some(e))
+        }
+    } else {
+        (None, None)
+    };
+
+    Ok(Some(Shipment {
+        shipment_id,
+        order_id,
+        carrier:            carrier.name().to_string(),
+        status:             "CREATED".to_string(),
+        tracking_number:    created.tracking_number,
+        tracking_url:       created.tracking_url,
+        created_at,
+        tracking_pushed,
+        tracking_error
+    }))
+}
+
+/**
+Get the shipping label of a shipment
+
+## Params
+    **database** Instance of a Database object
+    **shipment_id** The ID of the shipment
+
+## Returns
+    **Ok**: The label as a PDF, or None if the shipment does not exist or was not created by the carrier
+    **Err**: A summary of what went wrong
+*/
+pub fn get_label(database: Database, shipment_id: String) -> Result<Option<Vec<u8>>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec_first::<Option<Vec<u8>>, &str, Params>("SELECT label FROM shipments WHERE shipment_id = :shipment_id", params! {
+        "shipment_id" => shipment_id
+    });
+
+    match result {
+        Ok(label) => Ok(label.flatten()),
+        Err(e) => Err(e.to_string())
+    }
+}
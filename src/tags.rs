@@ -0,0 +1,175 @@
+use serde::Serialize;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+
+use crate::database::Database;
+
+/// Longest tag accepted
+const MAX_TAG_LENGTH: usize = 64;
+
+/// A tag in use by an instance
+#[derive(Serialize)]
+pub struct TagUsage {
+    pub tag:            String,
+    pub order_count:    i64
+}
+
+/**
+Get the tags in use by an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the tags for
+
+## Returns
+    **Ok**: The tags with the number of orders carrying them, alphabetically
+    **Err**: A summary of what went wrong
+*/
+pub fn get_tags(database: Database, instance_id: String) -> Result<Vec<TagUsage>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT t.tag, COUNT(*) AS order_count FROM order_tags t INNER JOIN orders o ON o.order_id = t.order_id \
+        WHERE o.instance_id = :instance_id GROUP BY t.tag ORDER BY t.tag ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(rows) => Ok(rows.iter().map(|row| TagUsage {
+            tag:            row.get("tag").unwrap(),
+            order_count:    row.get("order_count").unwrap()
+        }).collect()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the tags of an order
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+
+## Returns
+    **Ok**: The tags, alphabetically
+    **Err**: A summary of what went wrong
+*/
+pub fn get_order_tags(database: Database, order_id: &str) -> Result<Vec<String>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<String, &str, Params>("SELECT tag FROM order_tags WHERE order_id = :order_id ORDER BY tag ASC", params! {
+        "order_id" => order_id
+    });
+
+    match result {
+        Ok(tags) => Ok(tags),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Tag orders. Tags an order already has are left as they are
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the orders belong to, orders of other instances are not tagged
+    **order_ids** The orders to tag
+    **tags** The tags to add, e.g. 'VIP'
+    **added_by** Staff member adding the tags, or the rule which added them
+
+## Returns
+    **Ok**: The number of tags added
+    **Err**: A tag is invalid, or a summary of what went wrong
+*/
+pub fn add_tags(database: Database, instance_id: String, order_ids: &[String], tags: &[String], added_by: &str) -> Result<usize, String> {
+    let tags = normalize_tags(tags)?;
+    let added_at = chrono::Utc::now().timestamp();
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut added = 0;
+    for order_id in order_ids {
+        for tag in &tags {
+            let result = conn.exec_iter("INSERT IGNORE INTO order_tags (order_id, tag, added_by, added_at) \
+                SELECT order_id, :tag, :added_by, :added_at FROM orders WHERE order_id = :order_id AND instance_id = :instance_id", params! {
+                "order_id" => order_id,
+                "instance_id" => instance_id.clone(),
+                "tag" => tag,
+                "added_by" => added_by,
+                "added_at" => added_at
+            });
+
+            match result {
+                Ok(result) => added += result.affected_rows() as usize,
+                Err(e) => return Err(e.to_string())
+            }
+        }
+    }
+
+    Ok(added)
+}
+
+/**
+Remove tags from orders
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the orders belong to
+    **order_ids** The orders to remove the tags from
+    **tags** The tags to remove
+
+## Returns
+    **Ok**: The number of tags removed
+    **Err**: A summary of what went wrong
+*/
+pub fn remove_tags(database: Database, instance_id: String, order_ids: &[String], tags: &[String]) -> Result<usize, String> {
+    let tags = normalize_tags(tags)?;
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut removed = 0;
+    for order_id in order_ids {
+        for tag in &tags {
+            let result = conn.exec_iter("DELETE t FROM order_tags t INNER JOIN orders o ON o.order_id = t.order_id \
+                WHERE t.order_id = :order_id AND t.tag = :tag AND o.instance_id = :instance_id", params! {
+                "order_id" => order_id,
+                "instance_id" => instance_id.clone(),
+                "tag" => tag
+            });
+
+            match result {
+                Ok(result) => removed += result.affected_rows() as usize,
+                Err(e) => return Err(e.to_string())
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/**
+Check whether a tag can be stored
+
+## Params
+    **tag** The tag, without surrounding whitespace
+
+## Returns
+    **Ok**: Nothing
+    **Err**: Why the tag is invalid
+*/
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() || tag.contains(',') || tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!("Invalid tag '{}', tags are at most {} characters and may not contain a comma", tag, MAX_TAG_LENGTH));
+    }
+
+    Ok(())
+}
+
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        validate_tag(tag)?;
+
+        if !normalized.iter().any(|existing| existing == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+
+    Ok(normalized)
+}
\ No newline at end of file
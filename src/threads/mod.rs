@@ -1 +1,3 @@
-pub mod wix_fetch_orders;
\ No newline at end of file
+pub mod wix_fetch_orders;
+pub mod retention;
+pub mod webhook_delivery;
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::database::Database;
+use crate::retention::{get_policies, apply_policy, delete_expired_states};
+
+/// How often retention policies are enforced
+const RETENTION_INTERVAL_SECONDS: u64 = 60 * 60;
+
+/**
+Start the background job enforcing the retention policies of all instances.
+Policies with dry_run set are only reported on, nothing is changed for them.
+
+## Params
+    **database** Instance of a Database object
+*/
+pub fn start_retention_job(database: Database) {
+    std::thread::spawn(move || {
+        loop {
+            match delete_expired_states(database.clone()) {
+                Ok(count) if count > 0 => println!("Retention: deleted {} expired states", count),
+                Ok(_) => {},
+                Err(e) => eprintln!("Retention: unable to delete expired states: {}", e)
+            }
+
+            match get_policies(database.clone()) {
+                Err(e) => eprintln!("Retention: unable to load retention policies: {}", e),
+                Ok(policies) => for policy in policies {
+                    match apply_policy(database.clone(), &policy, policy.dry_run) {
+                        Ok(report) => println!("Retention{} for instance {}: {} orders, {} addresses and {} customers anonymized and {} webhook deliveries deleted",
+                            if report.dry_run { " (dry run)" } else { "" },
+                            policy.instance_id,
+                            report.orders_anonymized,
+                            report.addresses_anonymized,
+                            report.customers_anonymized,
+                            report.webhook_deliveries_deleted
+                        ),
+                        Err(e) => eprintln!("Retention: unable to apply policy for instance {}: {}", policy.instance_id, e)
+                    }
+                }
+            }
+
+            std::thread::sleep(std::time::Duration::from_secs(RETENTION_INTERVAL_SECONDS));
+        }
+    });
+}
\ No newline at end of file
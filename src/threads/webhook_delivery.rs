@@ -0,0 +1,23 @@
+use crate::database::Database;
+use crate::outbound_webhooks::send_due_deliveries;
+
+/// How often due webhook deliveries are sent
+const DELIVERY_INTERVAL_SECONDS: u64 = 15;
+
+/**
+Start the background job sending outbound webhook deliveries, including the retries of failed ones
+
+## Params
+    **database** Instance of a Database object
+*/
+pub fn start_delivery_job(database: Database) {
+    std::thread::spawn(move || {
+        loop {
+            if let Err(e) = send_due_deliveries(database.clone()) {
+                eprintln!("Webhooks: unable to send deliveries: {}", e);
+            }
+
+            std::thread::sleep(std::time::Duration::from_secs(DELIVERY_INTERVAL_SECONDS));
+        }
+    });
+}
\ No newline at end of file
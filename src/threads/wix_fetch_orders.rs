@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize};
 use reqwest::header::AUTHORIZATION;
-use mysql::{Params, params};
+use mysql::{Params, Row, Transaction, params};
 use mysql::prelude::Queryable;
 use rand::Rng;
+use rust_decimal::Decimal;
 
+use crate::addresses::{normalize, RawAddress};
 use crate::database::Database;
+use crate::types::money::Money;
 use crate::types::wix::{BuyerInfo, WeightUnit, Totals, PaymentStatus, FulfilmentStatus, IdentityType};
 use term::terminfo::parm::Param;
 
@@ -179,21 +182,30 @@ struct Address {
     city: String,
 
     /// Email address
+    #[serde(default)]
     email: String,
 
+    /// Phone number
+    #[serde(default)]
+    phone: String,
+
     /// Addressee name
+    #[serde(default)]
     full_name: FullName,
 
     /// ZIP/postal code
+    #[serde(default)]
     zip_code: String,
 
     /// Country code (2 letters)
     country: String,
 
     /// Company name
+    #[serde(default)]
     company: String,
 
     /// address line
+    #[serde(default)]
     address_line_2: String,
 
     /// Address line 1 (free text)
@@ -205,20 +217,25 @@ struct Address {
 
 /// Address line 1 (street)
 #[serde(rename_all = "lowerCamelCase")]
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 struct Street {
     /// Street number
+    #[serde(default)]
     number: String,
 
     /// Street name
+    #[serde(default)]
     name: String
 }
 
 /// Addressee name
 #[serde(rename_all = "lowerCamelCase")]
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 struct FullName {
+    #[serde(default)]
     first_name: String,
+
+    #[serde(default)]
     last_name: String
 }
 
@@ -240,7 +257,7 @@ struct LineItem {
     translated_name: String,
 
     /// Line item product ID (optional for POS orders)
-    product_id: std::Option<String>,
+    product_id: std::option::Option<String>,
 
     /// Line item type (may be extended)
     line_item_type: LineItemType,
@@ -267,13 +284,13 @@ struct LineItem {
     variant_id: String,
 
     /// Line item fulfillerId from stores fulfillers. No value equals self fulfilled
-    fulfiller_id: std::Option<String>,
+    fulfiller_id: std::option::Option<String>,
 
     /// Discount applied for this line item
-    discount: String,
+    discount: Decimal,
 
     /// Tax applied for this line item
-    tax: String,
+    tax: Decimal,
 
     /// Tax group ID
     tax_group_id: String,
@@ -287,8 +304,8 @@ struct LineItem {
 #[derive(Deserialize)]
 struct PriceData {
     tax_included_in_price:  bool,
-    price:                  String,
-    total_price:            String
+    price:                  Decimal,
+    total_price:            Decimal
 }
 
 /// Primary media for preview of the line item
@@ -339,7 +356,7 @@ struct Option {
     option:     String,
 
     /// Selected choice for this option
-    selection:  Stirng
+    selection:  String
 }
 
 /// Line item type (may be extended)
@@ -352,6 +369,22 @@ enum LineItemType {
     CustomAmountItem
 }
 
+impl LineItemType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineItemType::UnspecifiedLineItemType => "UNSPECIFIED",
+            LineItemType::Physical => "PHYSICAL",
+            LineItemType::Digital => "DIGITAL",
+            LineItemType::CustomAmountItem => "CUSTOM_AMOUNT_ITEM"
+        }
+    }
+
+    /// Whether the item has to be picked, packed and shipped. Items of an unspecified type are assumed to be physical
+    fn requires_shipping(&self) -> bool {
+        matches!(self, LineItemType::Physical | LineItemType::UnspecifiedLineItemType)
+    }
+}
+
 /// Log of updates related to the order.
 #[derive(Deserialize)]
 struct Activity {
@@ -505,7 +538,7 @@ struct ChannelInfo {
 
 /// Sales channel that submitted the order
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 enum ChannelInfoType {
     Unspecified,
     Web,
@@ -532,10 +565,10 @@ struct Refund {
     date_created:                       String,
 
     /// Refund amount.
-    amount:                             String,
+    amount:                             Decimal,
 
     /// Reason for refund, given by user (optional).
-    reason:                             std::Option<String>,
+    reason:                             std::option::Option<String>,
 
     /// Payment provider transaction ID. Used to find refund transaction info on the payment provider's side.
     payment_provider_transaction_id:    String,
@@ -570,10 +603,22 @@ pub fn fetch_orders(database: Database, instance_id: String) {
         //Create a database connection
         let mut conn = database.pool.get_conn().unwrap();
 
+        //New orders are only scored for fraud risk when the instance configured it
+        let risk_settings = match crate::fraud::get_settings(database.clone(), instance_id.clone()) {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("Unable to get the risk settings of instance {}: {}", instance_id, e);
+                None
+            }
+        };
+
         //Now we need to fetch the orders from Wix
         let mut orders_received: u64 = 0;
         let mut order_ids: Vec<String> = Vec::new();
 
+        //Stored orders with their number, processed further once all orders are stored
+        let mut created_orders: Vec<(String, i64)> = Vec::new();
+
         while orders_received % 100 == 0 {
             let query = QueryOrdersRequest {
                 query: Query {
@@ -602,113 +647,356 @@ pub fn fetch_orders(database: Database, instance_id: String) {
 
             //Iterate over all received orders
             for order in query_order_response.orders {
-                let order_items = order.line_items;
-                let order_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+                let existing = conn.exec_first::<Row, &str, Params>("SELECT order_id, currency, last_updated, payment_status, fulfillment_status FROM orders WHERE instance_id = :instance_id AND wix_id = :wix_id", params! {
+                    "instance_id" => instance_id.clone(),
+                    "wix_id" => order.id.clone()
+                });
 
-                //Create an entry in the order_items table for each order
-                for item in order_items {
-                    let cloned_order = order.clone();
-                    let item_price_data = item.price_data;
+                let existing = match existing {
+                    Ok(existing) => existing,
+                    Err(e) => {
+                        eprintln!("Unable to look up order {} of instance {}: {}", order.number, instance_id, e);
+                        continue;
+                    }
+                };
 
-                    let order_item_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+                //Orders which were imported before are updated, their items and addresses are kept as they are
+                if let Some(existing) = existing {
+                    let order_id: String = existing.get("order_id").unwrap();
+                    let order_last_updated = chrono::DateTime::parse_from_rfc3339(&order.last_updated).unwrap().timestamp_millis();
 
-                    conn.exec::<usize, &str, Params>("INSERT INTO order_items (order_item_id, order_id, name, sku, total, price) VALUES (:order_item_id, :order_id, :name, :sku, :total, :price)", params!{
-                        "order_item_id" => order_item_id.clone(),
-                        "order_id" => order_id.clone(),
-                        "name" => cloned_order.name,
-                        "sku" => cloned_order.sku,
-                        "total" => item_price_data.total_price,
-                        "price" => item_price_data.price
-                    });
-                }
+                    //Not changed in Wix since it was stored
+                    if existing.get::<i64, &str>("last_updated").unwrap() == order_last_updated {
+                        continue;
+                    }
 
-                //Next insert the billing address details
-                let billing_address_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
-                let billing_address = order.billing_info.address;
-
-                let street: std::option::Option<Street> = billing_address.street;
-                let address_line_1 =
-                    if street.is_some() {
-                        let street_unw = street.unwrap();
-                        format!("{} {}", street_unw.name, street_unw.number)
-                    } else {
-                        billing_address.address_line_1.unwrap()
+                    //The statuses and the refunds made since the order was stored are updated together
+                    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+                        Ok(tx) => tx,
+                        Err(e) => {
+                            eprintln!("Unable to update order {} of instance {}: {}", order.number, instance_id, e);
+                            continue;
+                        }
                     };
 
-                conn.exec::<usize, &str, Params>("INSERT INTO addresses (address_id, city, zip_code, country, address_line_2, address_line_!) VALUES (:address_id, :city, :zip_code, :country, :address_line_2, :address_line_1)", params! {
-                    "address_id" => billing_address_id,
-                    "city" => billing_address.city,
-                    "zip_code" =>  billing_address.zip_code,
-                    "country" => billing_address.country,
-                    "address_line_2" => billing_address.address_line_2,
-                    "address_line_1" => address_line_1
-                });
+                    let result = tx.exec_drop("UPDATE orders SET last_updated = :last_updated, payment_status = :payment_status, fulfillment_status = :fulfillment_status WHERE order_id = :order_id", params! {
+                        "last_updated" => order_last_updated,
+                        "payment_status" => order.payment_status.to_string(),
+                        "fulfillment_status" => order.fulfillment_status.to_string(),
+                        "order_id" => order_id.clone()
+                    });
 
-                //Next insert the shipping address
-                let shipping_address_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
-                let shipping_address =
-                    if order.shipping_info.pickup_details.is_some() {
-                        let pickup_details = order.shipping_info.pickup_details.unwrap();
-                        pickup_details.pickup_address.clone()
-                    } else {
-                        let shipping_details = order.shipping_info.shipment_details.unwrap();
-                        shipping_details.address.clone()
-                    };
+                    if result.is_err() {
+                        eprintln!("Unable to update order {} of instance {}: {}", order.number, instance_id, result.err().unwrap());
+                        continue;
+                    }
+
+                    //Refunds made since the order was stored no longer count for the customer
+                    let currency: String = existing.get("currency").unwrap();
+                    let result = insert_refunds(&mut tx, &order_id, &currency, order.refunds)
+                        .and_then(|refunded| if refunded.amount.is_zero() { Ok(()) } else { crate::customers::subtract_refunds(&mut tx, &order_id, &refunded) });
+
+                    if let Err(e) = result {
+                        eprintln!("Unable to store the refunds of order {} of instance {}: {}", order.number, instance_id, e);
+                        continue;
+                    }
 
-                let street = shipping_address.street;
-                let shipping_address_line_1 =
-                    if street.is_some() {
-                        let street_unw = street.unwrap();
-                        format!("{} {}", street_unw.name, street_unw.number)
-                    } else {
-                        shipping_address.address_line_1.unwrap()
+                    if let Err(e) = tx.commit() {
+                        eprintln!("Unable to update order {} of instance {}: {}", order.number, instance_id, e);
+                        continue;
+                    }
+
+                    //Let subscribed systems know about status changes, e.g. an order which became paid or fulfilled
+                    let previous = crate::outbound_webhooks::PreviousStatus {
+                        payment_status:     existing.get("payment_status").unwrap(),
+                        fulfillment_status: existing.get("fulfillment_status").unwrap()
                     };
 
-                conn.exec::<usize, &str, Params>("INSERT INTO addresses (address_id, city, zip_code, country, address_line_2, address_line_!) VALUES (:address_id, :city, :zip_code, :country, :address_line_2, :address_line_1)", params! {
-                    "address_id" => shipping_address_id,
-                    "city" => shipping_address.city,
-                    "zip_code" =>  shipping_address.zip_code,
-                    "country" => shipping_address.country,
-                    "address_line_2" => shipping_address.address_line_2,
-                    "address_line_1" => shipping_address_line_1
-                });
+                    if let Err(e) = crate::outbound_webhooks::publish_status_change(database.clone(), &order_id, previous) {
+                        eprintln!("Unable to publish the status change of order {} of instance {}: {}", order.number, instance_id, e);
+                    }
+
+                    //The stored order changed, the instance's rules may react to that
+                    if let Err(e) = crate::rules::evaluate_order(database.clone(), order_id, crate::rules::Trigger::OrderUpdated) {
+                        eprintln!("Unable to evaluate the rules for order {} of instance {}: {}", order.number, instance_id, e);
+                    }
+
+                    continue;
+                }
 
-                let order_created_dt = chrono::DateTime::parse_from_rfc3339(&order.date_created).unwrap();
-                let order_created_epoch = order_created_dt.timestamp();
-
-                let total: f64 = order.totals.total.parse().unwrap();
-                let weight: f64 = order.totals.weight.parse().unwrap();
-                let quantity: i64 = order.totals.quantity.parse().unwrap();
-                let subtotal: f64 = order.totals.subtotal.parse().unwrap();
-                let tax: f64 = order.totals.tax.parse().unwrap();
-
-                //Now we're going to insert the order details itself into the database
-                conn.exec::<usize, &str, Params>(
-                    "INSERT INTO orders \
-                    (order_id, wix_order_id, order_date, currency, weight_unit, payment_status, fulfillment_status, total_price, weight, quantity, subtotal, tax, buyer_email, \
-                    buyer_name, buyer_phone, billing_address_id, shipping_address_id) \
-                    VALUES (:order_id, :wix_order_id, :order_date, :currency, :weight_unit, :payment_status, :fulfillment_status, :total_price, :weight, :quantity, :subtotal, \
-                    :tax, :buyer_email, :buyer_name, :buyer_phone, :billing_address_id, :shipping_address_id)", params! {
-
-                    "order_id" => order_id,
-                    "wix_order_id" => order.number,
-                    "order_date" => order_created_epoch,
-                    "currency" => order.currency,
-                    "weight_unit" => order.weight_unit.to_string(),
-                    "payment_status" => order.payment_status.to_string(),
-                    "fulfillment_status" => order.fulfillment_status.to_string(),
-                    "total_price" => total,
-                    "weight" => weight,
-                    "quantity" => quantity,
-                    "subtotal" => subtotal,
-                    "tax" => tax,
-                    "buyer_email" => order.buyer_info.email,
-                    "buyer_name" => format!("{} {}", order.buyer_info.first_name, order.buyer_info.last_name),
-                    "buyer_phone" =>  order.buyer_info.phone,
-                    "billing_address_id" => billing_address_id,
-                    "shipping_address_id" => shipping_address_id
-                 });
+                let order_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+                let number = order.number;
+
+                //The order, its items, addresses, refunds and customer are stored together, so nothing is left of an order which failed to store
+                let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        eprintln!("Unable to store order {} of instance {}: {}", number, instance_id, e);
+                        continue;
+                    }
+                };
+
+                if let Err(e) = store_order(&mut tx, &instance_id, &order_id, order) {
+                    eprintln!("Unable to store order {} of instance {}: {}", number, instance_id, e);
+                    continue;
+                }
+
+                if let Err(e) = tx.commit() {
+                    eprintln!("Unable to store order {} of instance {}: {}", number, instance_id, e);
+                    continue;
+                }
+
+                created_orders.push((order_id, number));
+            }
+        }
+
+        //Convert the new orders to the reporting currency, fraud scoring compares their totals in it
+        if let Err(e) = crate::exchange_rates::convert_orders(database.clone(), instance_id.clone(), crate::exchange_rates::Conversion::Missing) {
+            eprintln!("Unable to convert orders of instance {} to the reporting currency: {}", instance_id, e);
+        }
+
+        for (order_id, number) in created_orders {
+            //Scored first, so subscribed systems and rules see whether the order is on hold
+            if let Some(settings) = &risk_settings {
+                if let Err(e) = crate::fraud::score_order(database.clone(), settings, order_id.clone()) {
+                    eprintln!("Unable to score order {} of instance {} for fraud risk: {}", number, instance_id, e);
+                }
+            }
+
+            if let Err(e) = crate::outbound_webhooks::publish(database.clone(), &order_id, crate::outbound_webhooks::OrderEvent::OrderCreated) {
+                eprintln!("Unable to publish order {} of instance {}: {}", number, instance_id, e);
+            }
+
+            //Let the instance's rules tag and route the new order
+            if let Err(e) = crate::rules::evaluate_order(database.clone(), order_id, crate::rules::Trigger::OrderCreated) {
+                eprintln!("Unable to evaluate the rules for order {} of instance {}: {}", number, instance_id, e);
             }
         }
     });
-}
\ No newline at end of file
+}
+/**
+Store a new order with its items, addresses and refunds, and count it for its customer
+
+## Params
+    **tx** The transaction to store the order in
+    **instance_id** The instance the order belongs to
+    **order_id** The OrderSync ID for the order
+    **order** The order as received from Wix
+
+## Returns
+    **Ok**: Nothing
+    **Err**: A summary of what went wrong, the transaction should not be committed
+*/
+fn store_order(tx: &mut Transaction, instance_id: &str, order_id: &str, order: Order) -> Result<(), String> {
+    let tax_included_in_price = order.line_items.iter().any(|item| item.price_data.tax_included_in_price);
+    let requires_shipping = order.line_items.iter().any(|item| item.line_item_type.requires_shipping());
+    let order_items = order.line_items;
+
+    //Create an entry in the order_items table for each order
+    for item in order_items {
+        let item_price_data = item.price_data;
+        let item_options = item.options;
+
+        //Weight of a single item, in the store's unit. Not every product has a weight, and digital and custom amount items are not shipped
+        let item_weight: f64 = if item.line_item_type.requires_shipping() { item.weight.parse().unwrap_or_default() } else { 0.0 };
+
+        let order_item_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+
+        let result = tx.exec_drop("INSERT INTO order_items (order_item_id, order_id, product_id, variant_id, item_type, name, sku, quantity, weight, weight_kg, total, price, tax, tax_group_id, tax_included_in_price, discount) \
+            VALUES (:order_item_id, :order_id, :product_id, :variant_id, :item_type, :name, :sku, :quantity, :weight, :weight_kg, :total, :price, :tax, :tax_group_id, :tax_included_in_price, :discount)", params!{
+            "order_item_id" => order_item_id.clone(),
+            "order_id" => order_id,
+            "product_id" => item.product_id,
+            "variant_id" => item.variant_id,
+            "item_type" => item.line_item_type.as_str(),
+            "name" => item.name,
+            "sku" => item.sku,
+            "quantity" => item.quantity,
+            "weight" => item_weight,
+            "weight_kg" => order.weight_unit.to_kilograms(item_weight),
+            "total" => item_price_data.total_price,
+            "price" => item_price_data.price,
+            "tax" => item.tax,
+            "tax_group_id" => item.tax_group_id,
+            "tax_included_in_price" => item_price_data.tax_included_in_price,
+            "discount" => item.discount
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        for item_option in item_options {
+            let result = tx.exec_drop("INSERT INTO order_item_options (order_item_id, `option`, selection) VALUES (:order_item_id, :option, :selection)", params! {
+                "order_item_id" => order_item_id.clone(),
+                "option" => item_option.option,
+                "selection" => item_option.selection
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+        }
+    }
+
+    //Next insert the billing address details
+    let billing_address_id = insert_address(tx, &order.billing_info.address, "BILLING")?;
+
+    //Next insert the shipping address, orders without shipping (e.g. digital products) have neither
+    let shipping_address = match (&order.shipping_info.pickup_details, &order.shipping_info.shipment_details) {
+        (Some(pickup_details), _) => Some((&pickup_details.pickup_address, "PICKUP")),
+        (None, Some(shipment_details)) => Some((&shipment_details.address, "SHIPMENT")),
+        (None, None) => None
+    };
+
+    let shipping_address_id = match shipping_address {
+        Some((address, address_type)) => Some(insert_address(tx, address, address_type)?),
+        None => None
+    };
+
+    let delivery_method = match shipping_address {
+        _ if !requires_shipping => "DIGITAL",
+        Some((_, "PICKUP")) => "PICKUP",
+        Some(_) => "SHIP",
+        None => "DIGITAL"
+    };
+
+    let order_created_dt = chrono::DateTime::parse_from_rfc3339(&order.date_created).unwrap();
+    let order_created_epoch = order_created_dt.timestamp();
+
+    //Kept in milliseconds, it is compared against Wix before writing changes back
+    let order_last_updated = chrono::DateTime::parse_from_rfc3339(&order.last_updated).unwrap().timestamp_millis();
+
+    //Amounts are kept as exact decimals, the columns are DECIMAL
+    if let Err(e) = order.totals.verify(&order.currency, tax_included_in_price) {
+        eprintln!("Order {} of instance {}: {}", order.number, instance_id, e);
+    }
+
+    let total = order.totals.total;
+    let weight: f64 = if requires_shipping { order.totals.weight.parse().unwrap_or_default() } else { 0.0 };
+    let quantity = i64::from(order.totals.quantity);
+    let subtotal = order.totals.subtotal;
+    let tax = order.totals.tax;
+    let shipping = order.totals.shipping;
+    let discount = order.totals.discount;
+
+    //Store the refunds, credit notes are issued for them
+    let refunded = insert_refunds(tx, order_id, &order.currency, order.refunds)?;
+
+    //Stored with the order, so the customer only counts orders which were actually stored. Refunds don't count towards the lifetime value
+    let customer_id = crate::customers::upsert_customer(tx, instance_id, &order.buyer_info, order_created_epoch, &Money::new(total, &order.currency).sub(&refunded)?)?;
+
+    //Now we're going to insert the order details itself into the database
+    let result = tx.exec_drop(
+        "INSERT INTO orders \
+        (order_id, instance_id, wix_id, wix_order_id, order_date, last_updated, customer_id, currency, weight_unit, payment_status, payment_method, channel, fulfillment_status, delivery_method, requires_shipping, total_price, weight, weight_kg, quantity, subtotal, tax, shipping, discount, buyer_email, \
+        buyer_name, buyer_phone, buyer_note, billing_address_id, shipping_address_id) \
+        VALUES (:order_id, :instance_id, :wix_id, :wix_order_id, :order_date, :last_updated, :customer_id, :currency, :weight_unit, :payment_status, :payment_method, :channel, :fulfillment_status, :delivery_method, :requires_shipping, :total_price, :weight, :weight_kg, :quantity, :subtotal, \
+        :tax, :shipping, :discount, :buyer_email, :buyer_name, :buyer_phone, :buyer_note, :billing_address_id, :shipping_address_id)", params! {
+
+        "order_id" => order_id,
+        "instance_id" => instance_id,
+        "wix_id" => order.id,
+        "wix_order_id" => order.number,
+        "order_date" => order_created_epoch,
+        "last_updated" => order_last_updated,
+        "customer_id" => customer_id,
+        "currency" => order.currency,
+        "weight_unit" => order.weight_unit.to_string(),
+        "payment_status" => order.payment_status.to_string(),
+        "payment_method" => order.billing_info.payment_method,
+        "channel" => format!("{:?}", order.channel_info.channel_info_type),
+        "fulfillment_status" => order.fulfillment_status.to_string(),
+        "delivery_method" => delivery_method,
+        "requires_shipping" => requires_shipping,
+        "total_price" => total,
+        "weight" => weight,
+        "weight_kg" => order.weight_unit.to_kilograms(weight),
+        "quantity" => quantity,
+        "subtotal" => subtotal,
+        "tax" => tax,
+        "shipping" => shipping,
+        "discount" => discount,
+        "buyer_email" => order.buyer_info.email,
+        "buyer_name" => format!("{} {}", order.buyer_info.first_name, order.buyer_info.last_name),
+        "buyer_phone" =>  order.buyer_info.phone,
+        "buyer_note" => order.buyer_note,
+        "billing_address_id" => billing_address_id,
+        "shipping_address_id" => shipping_address_id
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Store the refunds of an order, credit notes are issued for them. Refunds which are already stored are skipped.
+/// Returns the sum of the refunds which were newly stored
+fn insert_refunds(tx: &mut Transaction, order_id: &str, currency: &str, refunds: Vec<Refund>) -> Result<Money, String> {
+    let mut refunded = Money::zero(currency);
+    for refund in refunds {
+        let refund_date = chrono::DateTime::parse_from_rfc3339(&refund.date_created).unwrap().timestamp();
+        let refund_amount = refund.amount;
+
+        let result = tx.exec_drop("INSERT IGNORE INTO order_refunds (refund_id, order_id, refund_date, amount, reason, external_refund) \
+            VALUES (:refund_id, :order_id, :refund_date, :amount, :reason, :external_refund)", params! {
+            "refund_id" => refund.id,
+            "order_id" => order_id,
+            "refund_date" => refund_date,
+            "amount" => refund_amount,
+            "reason" => refund.reason,
+            "external_refund" => refund.external_refund
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        if tx.affected_rows() == 1 {
+            refunded = refunded.add(&Money::new(refund_amount, currency))?;
+        }
+    }
+
+    Ok(refunded)
+}
+
+/// Normalize and store an address with its addressee. Addresses which fail validation are stored as well, flagged for review.
+/// The address type is 'BILLING', 'SHIPMENT' or 'PICKUP'
+fn insert_address(tx: &mut Transaction, address: &Address, address_type: &str) -> Result<String, String> {
+    let normalized = normalize(&RawAddress {
+        street_name:    address.street.as_ref().map(|street| street.name.as_str()),
+        street_number:  address.street.as_ref().map(|street| street.number.as_str()),
+        address_line_1: address.address_line_1.as_deref(),
+        address_line_2: &address.address_line_2,
+        zip_code:       &address.zip_code,
+        city:           &address.city,
+        country:        &address.country
+    });
+
+    let address_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let result = tx.exec_drop("INSERT INTO addresses (address_id, address_type, full_name, company, email, phone, street, house_number, house_number_addition, address_line_1, address_line_2, zip_code, city, country, needs_review, validation_issues) \
+        VALUES (:address_id, :address_type, :full_name, :company, :email, :phone, :street, :house_number, :house_number_addition, :address_line_1, :address_line_2, :zip_code, :city, :country, :needs_review, :validation_issues)", params! {
+        "address_id" => address_id.clone(),
+        "address_type" => address_type,
+        "full_name" => format!("{} {}", address.full_name.first_name.trim(), address.full_name.last_name.trim()).trim().to_string(),
+        "company" => address.company.trim(),
+        "email" => address.email.trim(),
+        "phone" => address.phone.trim(),
+        "street" => normalized.street,
+        "house_number" => normalized.house_number,
+        "house_number_addition" => normalized.house_number_addition,
+        "address_line_1" => normalized.address_line_1,
+        "address_line_2" => normalized.address_line_2,
+        "zip_code" => normalized.zip_code,
+        "city" => normalized.city,
+        "country" => normalized.country,
+        "needs_review" => !normalized.issues.is_empty(),
+        "validation_issues" => normalized.issues.join("; ")
+    });
+
+    match result {
+        Ok(_) => Ok(address_id),
+        Err(e) => Err(e.to_string())
+    }
+}
@@ -1 +1,3 @@
-pub mod wix;
\ No newline at end of file
+pub mod wix;
+pub mod order;
+pub mod money;
\ No newline at end of file
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+/** Active ISO 4217 currency codes, excluding funds, precious metals and testing codes */
+const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL",
+    "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP",
+    "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL",
+    "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR",
+    "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX",
+    "USD", "UYU", "UZS", "VES", "VND", "VUV", "WST", "XAF", "XCD", "XCG", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG"
+];
+
+/** An exact amount of money in a currency */
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Money {
+    /** Fixed-point amount, serialized as a string so no precision is lost */
+    pub amount:     Decimal,
+
+    /** ISO 4217 currency code */
+    pub currency:   String
+}
+
+impl Money {
+    /** Create an amount of money */
+    pub fn new(amount: Decimal, currency: &str) -> Money {
+        Money { amount, currency: currency.to_string() }
+    }
+
+    /** Zero in a currency */
+    pub fn zero(currency: &str) -> Money {
+        Money::new(Decimal::ZERO, currency)
+    }
+
+    /** Whether a code is an active ISO 4217 currency code, e.g. 'EUR' */
+    pub fn is_currency(code: &str) -> bool {
+        CURRENCY_CODES.contains(&code)
+    }
+
+    /** Number of decimals used for amounts in a currency */
+    pub fn minor_units(currency: &str) -> u32 {
+        match currency {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2
+        }
+    }
+
+    /**
+    Round to the currency's minor units, with halves rounded away from zero as Wix does.
+    The result always has that number of decimals, so '12.5' becomes '12.50'
+    */
+    pub fn round(&self) -> Money {
+        let minor_units = Money::minor_units(&self.currency);
+        let mut amount = self.amount.round_dp_with_strategy(minor_units, RoundingStrategy::MidpointAwayFromZero);
+        amount.rescale(minor_units);
+        Money::new(amount, &self.currency)
+    }
+
+    /**
+    Add an amount in the same currency
+
+    ## Returns
+        **Ok**: The sum
+        **Err**: The currencies differ, or the sum overflows
+    */
+    pub fn add(&self, other: &Money) -> Result<Money, String> {
+        self.check_currency(other)?;
+        match self.amount.checked_add(other.amount) {
+            Some(amount) => Ok(Money::new(amount, &self.currency)),
+            None => Err("Amount overflow".to_string())
+        }
+    }
+
+    /**
+    Subtract an amount in the same currency
+
+    ## Returns
+        **Ok**: The difference
+        **Err**: The currencies differ, or the difference overflows
+    */
+    pub fn sub(&self, other: &Money) -> Result<Money, String> {
+        self.check_currency(other)?;
+        match self.amount.checked_sub(other.amount) {
+            Some(amount) => Ok(Money::new(amount, &self.currency)),
+            None => Err("Amount overflow".to_string())
+        }
+    }
+
+    /**
+    Multiply by a quantity, e.g. a unit price by the number of items
+
+    ## Returns
+        **Ok**: The product
+        **Err**: The product overflows
+    */
+    pub fn times(&self, quantity: i64) -> Result<Money, String> {
+        match self.amount.checked_mul(Decimal::from(quantity)) {
+            Some(amount) => Ok(Money::new(amount, &self.currency)),
+            None => Err("Amount overflow".to_string())
+        }
+    }
+
+    /**
+    Sum amounts in a currency
+
+    ## Params
+        **amounts** The amounts, all in `currency`
+        **currency** Currency of the sum, which is also the result for no amounts
+
+    ## Returns
+        **Ok**: The sum
+        **Err**: A currency differs, or the sum overflows
+    */
+    pub fn sum<'a, I: IntoIterator<Item = &'a Money>>(amounts: I, currency: &str) -> Result<Money, String> {
+        amounts.into_iter().try_fold(Money::zero(currency), |sum, amount| sum.add(amount))
+    }
+
+    fn check_currency(&self, other: &Money) -> Result<(), String> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(format!("Can't combine amounts in {} and {}", self.currency, other.currency))
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::types::wix::Totals;
+    use proptest::prelude::*;
+
+    /// Amounts in cents, as Wix sends them
+    fn cents() -> impl Strategy<Value = Decimal> {
+        (-1_000_000_000i64..1_000_000_000i64).prop_map(|cents| Decimal::new(cents, 2))
+    }
+
+    /// A line item: unit price, quantity, tax and discount of the line
+    fn line_item() -> impl Strategy<Value = (Decimal, i64, Decimal, Decimal)> {
+        (0i64..100_000, 1i64..20, 0i64..10_000, 0i64..1_000).prop_map(|(price, quantity, tax, discount)| {
+            (Decimal::new(price, 2), quantity, Decimal::new(tax, 2), Decimal::new(discount, 2))
+        })
+    }
+
+    /// The totals of an order as Wix calculates them from its line items
+    fn wix_totals(items: &[(Decimal, i64, Decimal, Decimal)], shipping: Decimal, tax_included_in_price: bool) -> Totals {
+        let subtotal: Decimal = items.iter().map(|(price, quantity, _, _)| *price * Decimal::from(*quantity)).sum();
+        let tax: Decimal = items.iter().map(|(_, _, tax, _)| *tax).sum();
+        let discount: Decimal = items.iter().map(|(_, _, _, discount)| *discount).sum();
+        let total = subtotal - discount + shipping + if tax_included_in_price { Decimal::ZERO } else { tax };
+
+        Totals {
+            weight:     "0".to_string(),
+            quantity:   items.iter().map(|(_, quantity, _, _)| *quantity as i16).sum(),
+            tax,
+            total,
+            subtotal,
+            discount,
+            shipping
+        }
+    }
+
+    fn parse(amount: &str) -> Money {
+        Money::new(Decimal::from_str(amount).unwrap(), "EUR")
+    }
+
+    proptest! {
+        #[test]
+        fn sum_is_exact(amounts in proptest::collection::vec(cents(), 0..50)) {
+            let money: Vec<Money> = amounts.iter().map(|amount| Money::new(*amount, "EUR")).collect();
+            let expected: i64 = amounts.iter().map(|amount| amount.mantissa() as i64).sum();
+
+            prop_assert_eq!(Money::sum(&money, "EUR").unwrap().amount, Decimal::new(expected, 2));
+        }
+
+        #[test]
+        fn times_is_repeated_addition(amount in cents(), quantity in 0i64..100) {
+            let money = Money::new(amount, "EUR");
+            let repeated = Money::sum(&vec![money.clone(); quantity as usize], "EUR").unwrap();
+
+            prop_assert_eq!(money.times(quantity).unwrap(), repeated);
+        }
+
+        #[test]
+        fn sub_undoes_add(a in cents(), b in cents()) {
+            let (a, b) = (Money::new(a, "USD"), Money::new(b, "USD"));
+            prop_assert_eq!(a.add(&b).unwrap().sub(&b).unwrap(), a);
+        }
+
+        #[test]
+        fn round_uses_minor_units(mantissa in -1_000_000_000i64..1_000_000_000i64, scale in 0u32..6, currency in prop_oneof!["EUR", "JPY", "KWD"]) {
+            let money = Money::new(Decimal::new(mantissa, scale), &currency);
+            let rounded = money.round();
+
+            prop_assert_eq!(rounded.amount.scale(), Money::minor_units(&currency));
+            prop_assert!((rounded.amount - money.amount).abs() * Decimal::from(10i64.pow(Money::minor_units(&currency))) <= Decimal::new(5, 1));
+            prop_assert_eq!(rounded.round(), rounded.clone());
+        }
+
+        #[test]
+        fn totals_of_line_items_verify(items in proptest::collection::vec(line_item(), 1..20), shipping in 0i64..5_000, tax_included_in_price: bool) {
+            let totals = wix_totals(&items, Decimal::new(shipping, 2), tax_included_in_price);
+            prop_assert!(totals.verify("EUR", tax_included_in_price).is_ok());
+
+            //A cent off in the total, or tax counted the other way, doesn't add up
+            let mut off = wix_totals(&items, Decimal::new(shipping, 2), tax_included_in_price);
+            off.total += Decimal::new(1, 2);
+            prop_assert!(off.verify("EUR", tax_included_in_price).is_err());
+
+            if !totals.tax.is_zero() {
+                prop_assert!(totals.verify("EUR", !tax_included_in_price).is_err());
+            }
+        }
+
+        #[test]
+        fn mixed_currencies_are_rejected(a in cents(), b in cents()) {
+            let (eur, usd) = (Money::new(a, "EUR"), Money::new(b, "USD"));
+
+            prop_assert!(eur.add(&usd).is_err());
+            prop_assert!(eur.sub(&usd).is_err());
+            prop_assert!(Money::sum(&[eur.clone(), usd], "EUR").is_err());
+            prop_assert!(Money::sum(&[eur], "USD").is_err());
+        }
+    }
+
+    #[test]
+    fn halves_round_away_from_zero() {
+        assert_eq!(parse("2.345").round().amount.to_string(), "2.35");
+        assert_eq!(parse("-2.345").round().amount.to_string(), "-2.35");
+        assert_eq!(parse("12.5").round().amount.to_string(), "12.50");
+        assert_eq!(Money::new(Decimal::from_str("1234.5").unwrap(), "JPY").round().amount.to_string(), "1235");
+    }
+
+    #[test]
+    fn currency_codes_are_iso_4217() {
+        assert!(Money::is_currency("EUR"));
+        assert!(Money::is_currency("JPY"));
+        assert!(!Money::is_currency("eur"));
+        assert!(!Money::is_currency("EURO"));
+        assert!(!Money::is_currency("XXX"));
+    }
+}
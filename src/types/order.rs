@@ -0,0 +1,234 @@
+use serde::Serialize;
+use mysql::Row;
+use rust_decimal::Decimal;
+
+use crate::types::money::Money;
+
+/** Column list matching Order::from_row, for use in SELECT statements with the orders table aliased as `o` */
+pub const ORDER_COLUMNS: &str = "o.order_id, o.instance_id, o.wix_order_id, o.order_date, o.currency, o.payment_status, o.payment_method, o.channel, o.fulfillment_status, o.delivery_method, o.requires_shipping, o.total_price, o.subtotal, o.tax, o.shipping, o.discount, o.quantity, o.weight, o.weight_unit, o.weight_kg";
+
+/** An order as stored by OrderSync */
+#[derive(Serialize)]
+pub struct Order {
+    /** OrderSync order ID */
+    pub order_id:           String,
+
+    /** Wix instance the order belongs to */
+    pub instance_id:        String,
+
+    /** Order number displayed in the owner's store */
+    pub wix_order_id:       i64,
+
+    /** Order creation date, epoch seconds */
+    pub order_date:         i64,
+
+    /** Currency used for pricing */
+    pub currency:           String,
+
+    /** Order payment status */
+    pub payment_status:     String,
+
+    /** Payment method used for this order, e.g. 'CreditCard' */
+    pub payment_method:     String,
+
+    /** Sales channel that submitted the order, e.g. 'Web' or 'Pos' */
+    pub channel:            String,
+
+    /** Order fulfillment status */
+    pub fulfillment_status: String,
+
+    /** How the order reaches the buyer: 'SHIP', 'PICKUP' or 'DIGITAL' */
+    pub delivery_method:    String,
+
+    /** Whether the order contains items which have to be picked, packed and handed over, false for digital and custom amount items only */
+    pub requires_shipping:  bool,
+
+    /** Total price charged */
+    pub total_price:        Decimal,
+
+    /** Subtotal of all the line items, before tax */
+    pub subtotal:           Decimal,
+
+    /** Total tax */
+    pub tax:                Decimal,
+
+    /** Total shipping price, before tax */
+    pub shipping:           Decimal,
+
+    /** Total calculated discount value */
+    pub discount:           Decimal,
+
+    /** Total number of line items */
+    pub quantity:           i64,
+
+    /** Total items weight */
+    pub weight:             f64,
+
+    /** Weight unit used in the store */
+    pub weight_unit:        String,
+
+    /** Total items weight in kilograms, None if the store's weight unit is unspecified */
+    pub weight_kg:          std::option::Option<f64>
+}
+
+impl Order {
+    /** Build an Order from a row selected with ORDER_COLUMNS */
+    pub fn from_row(row: &Row) -> Order {
+        Order {
+            order_id:           row.get("order_id").unwrap(),
+            instance_id:        row.get("instance_id").unwrap(),
+            wix_order_id:       row.get("wix_order_id").unwrap(),
+            order_date:         row.get("order_date").unwrap(),
+            currency:           row.get("currency").unwrap(),
+            payment_status:     row.get("payment_status").unwrap(),
+            payment_method:     row.get::<Option<String>, &str>("payment_method").unwrap().unwrap_or_default(),
+            channel:            row.get::<Option<String>, &str>("channel").unwrap().unwrap_or_default(),
+            fulfillment_status: row.get("fulfillment_status").unwrap(),
+            delivery_method:    row.get::<Option<String>, &str>("delivery_method").unwrap().unwrap_or_default(),
+            requires_shipping:  row.get::<Option<bool>, &str>("requires_shipping").unwrap().unwrap_or(true),
+            total_price:        row.get("total_price").unwrap(),
+            subtotal:           row.get("subtotal").unwrap(),
+            tax:                row.get("tax").unwrap(),
+            shipping:           row.get("shipping").unwrap(),
+            discount:           row.get("discount").unwrap(),
+            quantity:           row.get("quantity").unwrap(),
+            weight:             row.get("weight").unwrap(),
+            weight_unit:        row.get("weight_unit").unwrap(),
+            weight_kg:          row.get("weight_kg").unwrap()
+        }
+    }
+
+    /** An amount in the order's currency, e.g. `order.money(order.total_price)` */
+    pub fn money(&self, amount: Decimal) -> Money {
+        Money::new(amount, &self.currency)
+    }
+}
+/** A line item of a stored order */
+#[derive(Serialize)]
+pub struct OrderItem {
+    pub order_item_id:          String,
+
+    /** Wix catalog product ID, None for items without a product such as some POS items */
+    pub product_id:             std::option::Option<String>,
+
+    /** Wix catalog variant ID */
+    pub variant_id:             std::option::Option<String>,
+
+    /** 'PHYSICAL', 'DIGITAL', 'CUSTOM_AMOUNT_ITEM' or 'UNSPECIFIED' */
+    pub item_type:              String,
+    pub name:                   String,
+    pub sku:                    String,
+    pub quantity:               i64,
+
+    /** Weight of a single item, in the order's weight unit */
+    pub weight:                 f64,
+
+    /** Weight of a single item in kilograms, None if the store's weight unit is unspecified */
+    pub weight_kg:              std::option::Option<f64>,
+
+    /** Price of a single item */
+    pub price:                  Decimal,
+
+    /** Price of all items on this line, after the line's discount */
+    pub total:                  Decimal,
+
+    /** Tax applied to this line */
+    pub tax:                    Decimal,
+
+    /** Wix tax group of the product */
+    pub tax_group_id:           String,
+
+    /** Whether price and total include tax */
+    pub tax_included_in_price:  bool,
+
+    /** Discount applied to this line */
+    pub discount:               Decimal,
+
+    /** Options the buyer selected, e.g. size and color */
+    pub options:                Vec<ItemOption>
+}
+
+impl OrderItem {
+    /** Whether the item has to be picked, packed and shipped. Items of an unspecified type are assumed to be physical */
+    pub fn requires_shipping(&self) -> bool {
+        self.item_type == "PHYSICAL" || self.item_type == "UNSPECIFIED"
+    }
+}
+
+/** A selected option of a line item */
+#[derive(Serialize)]
+pub struct ItemOption {
+    pub option:     String,
+    pub selection:  String
+}
+
+/** A stored billing, shipping or pickup address */
+#[derive(Serialize)]
+pub struct Address {
+    /** 'BILLING', 'SHIPMENT' or 'PICKUP'. A pickup address is the store location the buyer collects the order at */
+    pub address_type:           String,
+
+    /** Name of the addressee, which may differ from the buyer */
+    pub full_name:              String,
+    pub company:                String,
+    pub email:                  String,
+    pub phone:                  String,
+
+    /** Street line as printed on labels, composed from the street and house number */
+    pub address_line_1:         String,
+    pub address_line_2:         String,
+    pub street:                 String,
+    pub house_number:           String,
+
+    /** House number addition, e.g. 'A' in '12A' */
+    pub house_number_addition:  String,
+    pub zip_code:               String,
+    pub city:                   String,
+
+    /** ISO 3166-1 alpha-2 country code */
+    pub country:                String,
+
+    /** Whether the address failed validation on import */
+    pub needs_review:           bool
+}
+
+impl Address {
+    /** Whether the order is collected by the buyer instead of shipped to this address */
+    pub fn is_pickup(&self) -> bool {
+        self.address_type == "PICKUP"
+    }
+
+    /** Name to address the parcel or invoice to: the addressee, or `buyer_name` if the address has none */
+    pub fn addressee<'a>(&'a self, buyer_name: &'a str) -> &'a str {
+        if self.full_name.is_empty() {
+            buyer_name
+        } else {
+            &self.full_name
+        }
+    }
+}
+
+/** An order with its buyer, addresses and line items */
+#[derive(Serialize)]
+pub struct OrderDetails {
+    pub order:              Order,
+    pub buyer_name:         String,
+    pub buyer_email:        String,
+    pub buyer_phone:        String,
+
+    /** Note the buyer added to the order */
+    pub buyer_note:         String,
+    pub billing_address:    std::option::Option<Address>,
+    pub shipping_address:   std::option::Option<Address>,
+    pub items:              Vec<OrderItem>
+}
+
+/** An order in a listing, with its buyer and tags */
+#[derive(Serialize)]
+pub struct OrderSummary {
+    pub order:              Order,
+    pub buyer_name:         String,
+
+    /** Tags of the order, alphabetically */
+    pub tags:               Vec<String>
+}
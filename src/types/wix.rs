@@ -1,5 +1,8 @@
 use serde::Deserialize;
 use std::fmt;
+use rust_decimal::Decimal;
+
+use crate::types::money::Money;
 
 /** Raw JWT Payload */
 #[derive(Deserialize)]
@@ -54,23 +57,54 @@ pub struct Totals {
     pub quantity:   i16,
 
     /** Total tax. */
-    pub tax:        String,
+    pub tax:        Decimal,
 
     /** Total price charged. */
-    pub total:      String,
+    pub total:      Decimal,
 
     /** Subtotal of all the line items, before tax. */
-    pub subtotal:   String,
+    pub subtotal:   Decimal,
 
     /** Total calculated discount value. */
-    pub discount:   String,
+    pub discount:   Decimal,
 
     /** Total shipping price, before tax. */
-    pub shipping:   String,
+    pub shipping:   Decimal,
 
     //Omitted: refund, giftCard
 }
 
+impl Totals {
+    /**
+    Check the totals add up the way Wix calculates them: subtotal - discount + shipping + tax,
+    where tax is already part of the subtotal and shipping when prices include tax
+
+    ## Params
+        **currency** Currency of the order
+        **tax_included_in_price** Whether the order's prices include tax
+
+    ## Returns
+        **Ok**: Nothing
+        **Err**: The expected and actual total, if they differ
+    */
+    pub fn verify(&self, currency: &str, tax_included_in_price: bool) -> Result<(), String> {
+        let mut expected = Money::new(self.subtotal, currency)
+            .sub(&Money::new(self.discount, currency))?
+            .add(&Money::new(self.shipping, currency))?;
+
+        if !tax_included_in_price {
+            expected = expected.add(&Money::new(self.tax, currency))?;
+        }
+
+        let total = Money::new(self.total, currency);
+        if expected.round() == total.round() {
+            Ok(())
+        } else {
+            Err(format!("Totals don't add up: expected {}, Wix reported {}", expected.round(), total))
+        }
+    }
+}
+
 /** Customer information */
 #[derive(Deserialize)]
 pub struct BuyerInfo {
@@ -131,7 +165,7 @@ impl fmt::Display for FulfilmentStatus {
 }
 
 /** Customer type */
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum IdentityType {
     Contact,
@@ -139,6 +173,12 @@ pub enum IdentityType {
     Member
 }
 
+impl fmt::Display for IdentityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 /** Weight unit used in this store */
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -152,4 +192,18 @@ impl fmt::Display for WeightUnit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
+}
+
+/** Kilograms in an international avoirdupois pound */
+pub const KILOGRAMS_PER_POUND: f64 = 0.45359237;
+
+impl WeightUnit {
+    /** Convert a weight in this unit to kilograms, the canonical unit. None if the store didn't specify a unit */
+    pub fn to_kilograms(&self, weight: f64) -> Option<f64> {
+        match self {
+            WeightUnit::Kg => Some(weight),
+            WeightUnit::Lb => Some(weight * KILOGRAMS_PER_POUND),
+            WeightUnit::UnspecifiedWeightUnit => None
+        }
+    }
 }
\ No newline at end of file
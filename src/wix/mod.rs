@@ -0,0 +1 @@
+pub mod order_writeback;
\ No newline at end of file
@@ -0,0 +1,569 @@
+use serde::{Serialize, Deserialize};
+use reqwest::header::AUTHORIZATION;
+use mysql::{Params, Row, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+
+use crate::database::Database;
+use crate::types::wix::{PaymentStatus, FulfilmentStatus};
+
+const WIX_ORDERS_ENDPOINT: &str = "https://www.wixapis.com/stores/v2/orders";
+
+/// Kind of change OrderSync wants to write back to Wix
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MutationType {
+    /// Mark the order as paid (e.g. a received bank transfer)
+    MarkPaid,
+
+    /// Add a merchant comment to the order's activity log
+    MerchantComment,
+
+    /// Fulfill the order's line items with the tracking information of a shipment
+    AddTracking
+}
+
+impl MutationType {
+    fn from_str(value: &str) -> Option<MutationType> {
+        match value {
+            "MARK_PAID" => Some(MutationType::MarkPaid),
+            "MERCHANT_COMMENT" => Some(MutationType::MerchantComment),
+            "ADD_TRACKING" => Some(MutationType::AddTracking),
+            _ => None
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            MutationType::MarkPaid => "MARK_PAID",
+            MutationType::MerchantComment => "MERCHANT_COMMENT",
+            MutationType::AddTracking => "ADD_TRACKING"
+        }
+    }
+}
+
+/// Result of trying to write a mutation back to Wix
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MutationOutcome {
+    /// The mutation was written to Wix
+    Applied {
+        mutation_id:    String
+    },
+
+    /// The conflicting mutation was discarded
+    Discarded {
+        mutation_id:    String
+    },
+
+    /// The order was changed in Wix since we last saw it in a way the mutation depends on, the mutation was not written
+    Conflict {
+        mutation_id:            String,
+        expected_last_updated:  i64,
+        remote_last_updated:    i64
+    }
+}
+
+/// A mutation which could not be written because of a concurrent edit in Wix
+#[derive(Serialize)]
+pub struct Conflict {
+    pub mutation_id:            String,
+    pub order_id:               String,
+    pub wix_order_id:           i64,
+    pub mutation_type:          MutationType,
+    pub payload:                String,
+    pub expected_last_updated:  i64,
+    pub remote_last_updated:    i64,
+    pub created_at:             i64
+}
+
+#[derive(Deserialize)]
+struct GetOrderResponse {
+    order: RemoteOrder
+}
+
+/// The fields of a Wix order we need to detect concurrent edits
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteOrder {
+    last_updated:       String,
+    payment_status:     PaymentStatus,
+
+    /// Not needed to detect edits, kept up to date along with the payment status
+    #[serde(default)]
+    fulfillment_status: Option<FulfilmentStatus>,
+
+    #[serde(default)]
+    line_items:         Vec<RemoteLineItem>
+}
+
+#[derive(Deserialize)]
+struct RemoteLineItem {
+    index:      i64,
+    quantity:   i64
+}
+
+/// Tracking information of a shipment, the payload of an AddTracking mutation
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackingInfo {
+    pub tracking_number:    String,
+    pub shipping_provider:  String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracking_link:      Option<String>
+}
+
+#[derive(Serialize)]
+struct CreateFulfillmentRequest<'a> {
+    fulfillment: Fulfillment<'a>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Fulfillment<'a> {
+    line_items:     Vec<FulfillmentLineItem>,
+    tracking_info:  &'a TrackingInfo
+}
+
+#[derive(Serialize)]
+struct FulfillmentLineItem {
+    index:      i64,
+    quantity:   i64
+}
+
+#[derive(Serialize)]
+struct AddActivityRequest {
+    activity: Activity
+}
+
+#[derive(Serialize)]
+struct Activity {
+    #[serde(rename(serialize = "type"))]
+    activity_type:  String,
+    message:        String
+}
+
+/// The locally stored state of the order a mutation applies to
+struct StoredOrder {
+    instance_id:        String,
+    wix_id:             String,
+    last_updated:       i64,
+    payment_status:     String,
+    fulfillment_status: String
+}
+
+/**
+Queue a mutation for an order and try to write it to Wix
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the order belongs to
+    **order_id** The OrderSync ID of the order
+    **mutation_type** The kind of change to write
+    **payload** Mutation data, e.g. the comment message or TrackingInfo as JSON. Empty for MarkPaid
+
+## Returns
+    **Ok**: Whether the mutation was applied or conflicted
+    **Err**: A summary of what went wrong. When writing to Wix failed, the mutation is marked as failed with this error
+*/
+pub fn submit_mutation(database: Database, instance_id: String, order_id: String, mutation_type: MutationType, payload: String) -> Result<MutationOutcome, String> {
+    let stored_order = get_stored_order(&database, &instance_id, &order_id)?;
+    let mutation_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("INSERT INTO order_mutations (mutation_id, order_id, mutation_type, payload, status, expected_last_updated, created_at) \
+        VALUES (:mutation_id, :order_id, :mutation_type, :payload, 'PENDING', :expected_last_updated, :created_at)", params! {
+        "mutation_id" => mutation_id.clone(),
+        "order_id" => order_id,
+        "mutation_type" => mutation_type.as_str(),
+        "payload" => payload,
+        "expected_last_updated" => stored_order.last_updated,
+        "created_at" => chrono::Utc::now().timestamp()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    apply_mutation(database, &instance_id, mutation_id, false)
+}
+
+/**
+Resolve a conflicting mutation
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the mutation's order belongs to
+    **mutation_id** The ID of the conflicting mutation
+    **overwrite** true to write the mutation to Wix regardless of the concurrent edit, false to discard it
+
+## Returns
+    **Ok**: The outcome of the resolution
+    **Err**: A summary of what went wrong
+*/
+pub fn resolve_conflict(database: Database, instance_id: String, mutation_id: String, overwrite: bool) -> Result<MutationOutcome, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT m.status FROM order_mutations m INNER JOIN orders o ON o.order_id = m.order_id \
+        WHERE m.mutation_id = :mutation_id AND o.instance_id = :instance_id", params! {
+        "mutation_id" => mutation_id.clone(),
+        "instance_id" => instance_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let rows = result.unwrap();
+    let status = match rows.first() {
+        Some(row) => row.get::<String, &str>("status").unwrap(),
+        None => return Err(format!("No mutation with ID {}", mutation_id))
+    };
+
+    if status != "CONFLICT" {
+        return Err(format!("Mutation {} is not in conflict", mutation_id));
+    }
+
+    if overwrite {
+        return apply_mutation(database, &instance_id, mutation_id, true);
+    }
+
+    let result = conn.exec::<usize, &str, Params>("UPDATE order_mutations SET status = 'DISCARDED', resolved_at = :resolved_at WHERE mutation_id = :mutation_id", params! {
+        "resolved_at" => chrono::Utc::now().timestamp(),
+        "mutation_id" => mutation_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    Ok(MutationOutcome::Discarded { mutation_id })
+}
+
+/**
+Get all unresolved conflicts for an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the conflicts for
+
+## Returns
+    **Ok**: The unresolved conflicts, oldest first
+    **Err**: A summary of what went wrong
+*/
+pub fn get_conflicts(database: Database, instance_id: String) -> Result<Vec<Conflict>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT m.mutation_id, m.order_id, o.wix_order_id, m.mutation_type, m.payload, m.expected_last_updated, m.remote_last_updated, m.created_at \
+        FROM order_mutations m INNER JOIN orders o ON o.order_id = m.order_id \
+        WHERE o.instance_id = :instance_id AND m.status = 'CONFLICT' ORDER BY m.created_at ASC", params! {
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    for row in result.unwrap() {
+        let mutation_type = MutationType::from_str(&row.get::<String, &str>("mutation_type").unwrap());
+        if mutation_type.is_none() {
+            continue;
+        }
+
+        conflicts.push(Conflict {
+            mutation_id:            row.get("mutation_id").unwrap(),
+            order_id:               row.get("order_id").unwrap(),
+            wix_order_id:           row.get("wix_order_id").unwrap(),
+            mutation_type:          mutation_type.unwrap(),
+            payload:                row.get("payload").unwrap(),
+            expected_last_updated:  row.get("expected_last_updated").unwrap(),
+            remote_last_updated:    row.get("remote_last_updated").unwrap(),
+            created_at:             row.get("created_at").unwrap()
+        });
+    }
+
+    Ok(conflicts)
+}
+
+/**
+Write a queued mutation to Wix
+
+Before writing, the order is fetched from Wix and compared with what we stored. If it was edited in Wix in the
+meantime in a way the mutation depends on, and `force` is not set, the mutation is marked as a conflict instead of
+being written. A mutation which can't be written to Wix is marked as failed
+*/
+fn apply_mutation(database: Database, instance_id: &str, mutation_id: String, force: bool) -> Result<MutationOutcome, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT order_id, mutation_type, payload FROM order_mutations WHERE mutation_id = :mutation_id", params! {
+        "mutation_id" => mutation_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let rows = result.unwrap();
+    let row = match rows.first() {
+        Some(row) => row,
+        None => return Err(format!("No mutation with ID {}", mutation_id))
+    };
+
+    let order_id: String = row.get("order_id").unwrap();
+    let payload: String = row.get("payload").unwrap();
+    let mutation_type = match MutationType::from_str(&row.get::<String, &str>("mutation_type").unwrap()) {
+        Some(mutation_type) => mutation_type,
+        None => return Err(format!("Mutation {} has an unknown type", mutation_id))
+    };
+
+    let stored_order = get_stored_order(&database, instance_id, &order_id)?;
+
+    //Anything going wrong while talking to Wix fails the mutation, rather than leaving it pending forever
+    let written = match write_mutation(&database, &stored_order, mutation_type, payload, force) {
+        Ok(written) => written,
+        Err(e) => {
+            let result = conn.exec::<usize, &str, Params>("UPDATE order_mutations SET status = 'FAILED', last_error = :last_error, resolved_at = :resolved_at WHERE mutation_id = :mutation_id", params! {
+                "last_error" => e.clone(),
+                "resolved_at" => chrono::Utc::now().timestamp(),
+                "mutation_id" => mutation_id.clone()
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+
+            return Err(e);
+        }
+    };
+
+    match written {
+        WriteResult::Conflict(remote_order) => {
+            let remote_last_updated = parse_timestamp(&remote_order.last_updated)?;
+            let result = conn.exec::<usize, &str, Params>("UPDATE order_mutations SET status = 'CONFLICT', remote_last_updated = :remote_last_updated WHERE mutation_id = :mutation_id", params! {
+                "remote_last_updated" => remote_last_updated,
+                "mutation_id" => mutation_id.clone()
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+
+            //The conflict is reported now, so later mutations are compared with the order as it is in Wix
+            store_remote_order(&database, order_id, &stored_order, &remote_order)?;
+
+            Ok(MutationOutcome::Conflict {
+                mutation_id,
+                expected_last_updated: stored_order.last_updated,
+                remote_last_updated
+            })
+        },
+        WriteResult::Written(updated_order) => {
+            let result = conn.exec::<usize, &str, Params>("UPDATE order_mutations SET status = 'APPLIED', resolved_at = :resolved_at WHERE mutation_id = :mutation_id", params! {
+                "resolved_at" => chrono::Utc::now().timestamp(),
+                "mutation_id" => mutation_id.clone()
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+
+            //Our own write bumped lastUpdated in Wix, store the new value so it doesn't show up as a conflict later
+            store_remote_order(&database, order_id, &stored_order, &updated_order)?;
+
+            Ok(MutationOutcome::Applied { mutation_id })
+        }
+    }
+}
+
+/// Outcome of writing a mutation to Wix, with the order as it is in Wix afterwards
+enum WriteResult {
+    Written(RemoteOrder),
+    Conflict(RemoteOrder)
+}
+
+fn write_mutation(database: &Database, stored_order: &StoredOrder, mutation_type: MutationType, payload: String, force: bool) -> Result<WriteResult, String> {
+    let access_token = crate::auth::wix_access_token::get_access_token(database.clone(), stored_order.instance_id.clone())?;
+
+    //Compare the order as it is in Wix with what we last saw
+    let remote_order = get_remote_order(&access_token, &stored_order.wix_id)?;
+    if !force && is_conflicting(mutation_type, stored_order, &remote_order)? {
+        return Ok(WriteResult::Conflict(remote_order));
+    }
+
+    match mutation_type {
+        MutationType::MarkPaid => mark_as_paid(&access_token, &stored_order.wix_id)?,
+        MutationType::MerchantComment => add_merchant_comment(&access_token, &stored_order.wix_id, payload)?,
+        MutationType::AddTracking => add_tracking(&access_token, &stored_order.wix_id, &remote_order, &payload)?
+    }
+
+    //The write went through, so not being able to fetch the result doesn't fail the mutation
+    match get_remote_order(&access_token, &stored_order.wix_id) {
+        Ok(updated_order) => Ok(WriteResult::Written(updated_order)),
+        Err(e) => {
+            eprintln!("Unable to fetch order {} from Wix after writing to it: {}", stored_order.wix_id, e);
+            Ok(WriteResult::Written(remote_order))
+        }
+    }
+}
+
+/**
+Whether the order was edited in Wix since we last saw it, in a way the mutation depends on.
+Edits of unrelated fields, e.g. the buyer's note, don't block a mutation
+*/
+fn is_conflicting(mutation_type: MutationType, stored_order: &StoredOrder, remote_order: &RemoteOrder) -> Result<bool, String> {
+    if parse_timestamp(&remote_order.last_updated)? == stored_order.last_updated {
+        return Ok(false);
+    }
+
+    let conflicting = match mutation_type {
+        //Comments are only appended to the activity log
+        MutationType::MerchantComment => false,
+        MutationType::MarkPaid => remote_order.payment_status.to_string() != stored_order.payment_status,
+        MutationType::AddTracking => match &remote_order.fulfillment_status {
+            Some(fulfillment_status) => fulfillment_status.to_string() != stored_order.fulfillment_status,
+            None => false
+        }
+    };
+
+    Ok(conflicting)
+}
+
+/**
+Store the lastUpdated and statuses of the order as it is in Wix, and let subscribed systems and the instance's rules react to changes
+*/
+fn store_remote_order(database: &Database, order_id: String, stored_order: &StoredOrder, remote_order: &RemoteOrder) -> Result<(), String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<usize, &str, Params>("UPDATE orders SET last_updated = :last_updated, payment_status = :payment_status, \
+        fulfillment_status = COALESCE(:fulfillment_status, fulfillment_status) WHERE order_id = :order_id", params! {
+        "last_updated" => parse_timestamp(&remote_order.last_updated)?,
+        "payment_status" => remote_order.payment_status.to_string(),
+        "fulfillment_status" => remote_order.fulfillment_status.as_ref().map(|status| status.to_string()),
+        "order_id" => order_id.clone()
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    //Let subscribed systems know about status changes, e.g. an order which became paid or fulfilled
+    let previous = crate::outbound_webhooks::PreviousStatus {
+        payment_status:     stored_order.payment_status.clone(),
+        fulfillment_status: stored_order.fulfillment_status.clone()
+    };
+
+    if let Err(e) = crate::outbound_webhooks::publish_status_change(database.clone(), &order_id, previous) {
+        eprintln!("Unable to publish the status change of order {}: {}", stored_order.wix_id, e);
+    }
+
+    //The stored order changed, the instance's rules may react to that
+    if let Err(e) = crate::rules::evaluate_order(database.clone(), order_id, crate::rules::Trigger::OrderUpdated) {
+        eprintln!("Unable to evaluate the rules for order {}: {}", stored_order.wix_id, e);
+    }
+
+    Ok(())
+}
+
+fn get_stored_order(database: &Database, instance_id: &str, order_id: &str) -> Result<StoredOrder, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let result = conn.exec::<Row, &str, Params>("SELECT instance_id, wix_id, last_updated, payment_status, fulfillment_status FROM orders \
+        WHERE order_id = :order_id AND instance_id = :instance_id", params! {
+        "order_id" => order_id,
+        "instance_id" => instance_id
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    match result.unwrap().first() {
+        Some(row) => Ok(StoredOrder {
+            instance_id:        row.get("instance_id").unwrap(),
+            wix_id:             row.get("wix_id").unwrap(),
+            last_updated:       row.get("last_updated").unwrap(),
+            payment_status:     row.get("payment_status").unwrap(),
+            fulfillment_status: row.get("fulfillment_status").unwrap()
+        }),
+        None => Err(format!("No order with ID {}", order_id))
+    }
+}
+
+fn get_remote_order(access_token: &str, wix_id: &str) -> Result<RemoteOrder, String> {
+    let result = reqwest::blocking::Client::new().get(&format!("{}/{}", WIX_ORDERS_ENDPOINT, wix_id))
+        .header(AUTHORIZATION, access_token)
+        .send();
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let response: Result<GetOrderResponse, reqwest::Error> = result.unwrap().json();
+    match response {
+        Ok(response) => Ok(response.order),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn mark_as_paid(access_token: &str, wix_id: &str) -> Result<(), String> {
+    let result = reqwest::blocking::Client::new().post(&format!("{}/{}/mark-as-paid", WIX_ORDERS_ENDPOINT, wix_id))
+        .header(AUTHORIZATION, access_token)
+        .send();
+
+    match result {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("Wix responded with status {} while marking order {} as paid", response.status(), wix_id)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn add_merchant_comment(access_token: &str, wix_id: &str, message: String) -> Result<(), String> {
+    let request = AddActivityRequest {
+        activity: Activity {
+            activity_type:  "MERCHANT_COMMENT".to_string(),
+            message
+        }
+    };
+
+    let result = reqwest::blocking::Client::new().post(&format!("{}/{}/activities", WIX_ORDERS_ENDPOINT, wix_id))
+        .header(AUTHORIZATION, access_token)
+        .json(&request)
+        .send();
+
+    match result {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("Wix responded with status {} while adding a comment to order {}", response.status(), wix_id)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn add_tracking(access_token: &str, wix_id: &str, remote_order: &RemoteOrder, payload: &str) -> Result<(), String> {
+    let tracking_info: TrackingInfo = match serde_json::from_str(payload) {
+        Ok(tracking_info) => tracking_info,
+        Err(e) => return Err(e.to_string())
+    };
+
+    //A shipment contains the whole order
+    let request = CreateFulfillmentRequest {
+        fulfillment: Fulfillment {
+            line_items: remote_order.line_items.iter().map(|item| FulfillmentLineItem { index: item.index, quantity: item.quantity }).collect(),
+            tracking_info: &tracking_info
+        }
+    };
+
+    let result = reqwest::blocking::Client::new().post(&format!("{}/{}/fulfillments", WIX_ORDERS_ENDPOINT, wix_id))
+        .header(AUTHORIZATION, access_token)
+        .json(&request)
+        .send();
+
+    match result {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("Wix responded with status {} while adding tracking to order {}", response.status(), wix_id)),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Wix timestamps have millisecond precision, keep it so edits within the same second are still detected
+fn parse_timestamp(value: &str) -> Result<i64, String> {
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(dt) => Ok(dt.timestamp_millis()),
+        Err(e) => Err(e.to_string())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,381 @@
+use std::collections::HashSet;
+
+use serde::{Serialize, Deserialize};
+use mysql::{Params, Row, Transaction, params};
+use mysql::prelude::Queryable;
+use rand::Rng;
+
+use crate::database::Database;
+
+/// A step in an instance's own order process, e.g. 'Picking' or 'On hold'. Kept in OrderSync only, Wix is not updated
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowState {
+    /// Identifier of the state, e.g. 'ON_HOLD'
+    pub key:            String,
+
+    /// Name shown to staff
+    pub name:           String,
+
+    /// Keys of the states an order in this state may move to
+    #[serde(default)]
+    pub transitions:    Vec<String>
+}
+
+/// The workflow state and assignee of an order, with its history
+#[derive(Serialize)]
+pub struct OrderWorkflow {
+    pub order_id:       String,
+
+    /// None if the order has not entered the workflow
+    pub state:          Option<String>,
+
+    /// Staff member working on the order
+    pub assignee:       Option<String>,
+    pub history:        Vec<WorkflowChange>
+}
+
+/// A change of the state or assignee of an order
+#[derive(Serialize)]
+pub struct WorkflowChange {
+    pub from_state:     Option<String>,
+    pub to_state:       Option<String>,
+    pub assignee:       Option<String>,
+
+    /// Staff member who made the change
+    pub changed_by:     String,
+
+    /// Epoch seconds
+    pub changed_at:     i64
+}
+
+/// Outcome of a bulk move or assignment
+#[derive(Serialize, Default)]
+pub struct BulkResult {
+    /// Orders which were changed
+    pub updated:        Vec<String>,
+
+    /// Orders which were left as they are
+    pub rejected:       Vec<RejectedOrder>
+}
+
+#[derive(Serialize)]
+pub struct RejectedOrder {
+    pub order_id:       String,
+    pub reason:         String
+}
+
+/**
+Get the workflow states of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to get the states for
+
+## Returns
+    **Ok**: The states, in their configured order
+    **Err**: A summary of what went wrong
+*/
+pub fn get_states(database: Database, instance_id: String) -> Result<Vec<WorkflowState>, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let states = conn.exec::<Row, &str, Params>("SELECT state_key, name FROM workflow_states WHERE instance_id = :instance_id ORDER BY position ASC", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    if states.is_err() {
+        return Err(states.err().unwrap().to_string());
+    }
+
+    let transitions = conn.exec::<(String, String), &str, Params>("SELECT from_state, to_state FROM workflow_transitions WHERE instance_id = :instance_id", params! {
+        "instance_id" => instance_id
+    });
+
+    if transitions.is_err() {
+        return Err(transitions.err().unwrap().to_string());
+    }
+
+    let transitions = transitions.unwrap();
+    Ok(states.unwrap().iter().map(|row| {
+        let key: String = row.get("state_key").unwrap();
+        WorkflowState {
+            transitions:    transitions.iter().filter(|(from, _)| *from == key).map(|(_, to)| to.clone()).collect(),
+            name:           row.get("name").unwrap(),
+            key
+        }
+    }).collect())
+}
+
+/**
+Replace the workflow states of an instance
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance to set the states for
+    **states** The new states, in the order they are shown
+
+## Returns
+    **Ok**: Nothing
+    **Err**: The states are invalid, a removed state still has orders, or a summary of what went wrong
+*/
+pub fn set_states(database: Database, instance_id: String, states: Vec<WorkflowState>) -> Result<(), String> {
+    let mut keys = HashSet::new();
+    for state in &states {
+        if state.key.trim().is_empty() || state.name.trim().is_empty() {
+            return Err("Every state must have a key and a name".to_string());
+        }
+
+        if !keys.insert(state.key.as_str()) {
+            return Err(format!("State '{}' is listed more than once", state.key));
+        }
+    }
+
+    for state in &states {
+        if let Some(unknown) = state.transitions.iter().find(|to| !keys.contains(to.as_str())) {
+            return Err(format!("State '{}' has a transition to unknown state '{}'", state.key, unknown));
+        }
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    //Orders may not be left in a state which no longer exists
+    let in_use = tx.exec::<String, &str, Params>("SELECT DISTINCT w.state_key FROM order_workflow w INNER JOIN orders o ON o.order_id = w.order_id \
+        WHERE o.instance_id = :instance_id AND w.state_key IS NOT NULL", params! {
+        "instance_id" => instance_id.clone()
+    });
+
+    match in_use {
+        Ok(in_use) => if let Some(removed) = in_use.iter().find(|key| !keys.contains(key.as_str())) {
+            return Err(format!("State '{}' can't be removed, there are orders in it", removed));
+        },
+        Err(e) => return Err(e.to_string())
+    }
+
+    for table in &["workflow_transitions", "workflow_states"] {
+        let result = tx.exec_drop(format!("DELETE FROM {} WHERE instance_id = :instance_id", table), params! {
+            "instance_id" => instance_id.clone()
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+    }
+
+    for (position, state) in states.iter().enumerate() {
+        let result = tx.exec_drop("INSERT INTO workflow_states (instance_id, state_key, name, position) VALUES (:instance_id, :state_key, :name, :position)", params! {
+            "instance_id" => instance_id.clone(),
+            "state_key" => state.key.clone(),
+            "name" => state.name.clone(),
+            "position" => position
+        });
+
+        if result.is_err() {
+            return Err(result.err().unwrap().to_string());
+        }
+
+        for to in &state.transitions {
+            let result = tx.exec_drop("INSERT INTO workflow_transitions (instance_id, from_state, to_state) VALUES (:instance_id, :from_state, :to_state)", params! {
+                "instance_id" => instance_id.clone(),
+                "from_state" => state.key.clone(),
+                "to_state" => to.clone()
+            });
+
+            if result.is_err() {
+                return Err(result.err().unwrap().to_string());
+            }
+        }
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Get the workflow state, assignee and history of an order
+
+## Params
+    **database** Instance of a Database object
+    **order_id** The OrderSync ID of the order
+
+## Returns
+    **Ok**: The workflow of the order
+    **Err**: A summary of what went wrong
+*/
+pub fn get_order_workflow(database: Database, order_id: String) -> Result<OrderWorkflow, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let current = conn.exec_first::<(Option<String>, Option<String>), &str, Params>("SELECT state_key, assignee FROM order_workflow WHERE order_id = :order_id", params! {
+        "order_id" => order_id.clone()
+    });
+
+    let (state, assignee) = match current {
+        Ok(current) => current.unwrap_or((None, None)),
+        Err(e) => return Err(e.to_string())
+    };
+
+    let history = conn.exec::<Row, &str, Params>("SELECT from_state, to_state, assignee, changed_by, changed_at FROM order_workflow_history WHERE order_id = :order_id ORDER BY changed_at ASC", params! {
+        "order_id" => order_id.clone()
+    });
+
+    match history {
+        Ok(rows) => Ok(OrderWorkflow {
+            order_id,
+            state,
+            assignee,
+            history: rows.iter().map(|row| WorkflowChange {
+                from_state: row.get("from_state").unwrap(),
+                to_state:   row.get("to_state").unwrap(),
+                assignee:   row.get("assignee").unwrap(),
+                changed_by: row.get("changed_by").unwrap(),
+                changed_at: row.get("changed_at").unwrap()
+            }).collect()
+        }),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Move orders to a workflow state. An order is only moved if its current state allows the transition,
+orders which have not entered the workflow may move to any state.
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the orders belong to
+    **order_ids** The orders to move
+    **to_state** Key of the state to move to
+    **changed_by** Staff member moving the orders
+
+## Returns
+    **Ok**: Which orders were moved and which were not
+    **Err**: The state does not exist, or a summary of what went wrong
+*/
+pub fn move_orders(database: Database, instance_id: String, order_ids: Vec<String>, to_state: String, changed_by: String) -> Result<BulkResult, String> {
+    let states = get_states(database.clone(), instance_id.clone())?;
+    if !states.iter().any(|state| state.key == to_state) {
+        return Err(format!("Unknown state '{}'", to_state));
+    }
+
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut result = BulkResult::default();
+    for order_id in order_ids {
+        let (from_state, assignee) = match current_workflow(&mut tx, &instance_id, &order_id)? {
+            Some(current) => current,
+            None => {
+                result.rejected.push(RejectedOrder { order_id, reason: "No order with this ID".to_string() });
+                continue;
+            }
+        };
+
+        if let Some(from) = &from_state {
+            let allowed = states.iter().any(|state| state.key == *from && state.transitions.contains(&to_state));
+            if !allowed {
+                result.rejected.push(RejectedOrder { order_id, reason: format!("Moving from '{}' to '{}' is not allowed", from, to_state) });
+                continue;
+            }
+        }
+
+        update_workflow(&mut tx, &order_id, from_state, Some(to_state.clone()), assignee, &changed_by)?;
+        result.updated.push(order_id);
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(result),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/**
+Assign orders to a staff member, keeping their workflow state
+
+## Params
+    **database** Instance of a Database object
+    **instance_id** The instance the orders belong to
+    **order_ids** The orders to assign
+    **assignee** The staff member, or None to unassign the orders
+    **changed_by** Staff member making the assignment
+
+## Returns
+    **Ok**: Which orders were assigned and which were not
+    **Err**: A summary of what went wrong
+*/
+pub fn assign_orders(database: Database, instance_id: String, order_ids: Vec<String>, assignee: Option<String>, changed_by: String) -> Result<BulkResult, String> {
+    let mut conn = database.pool.get_conn().unwrap();
+    let mut tx = match conn.start_transaction(mysql::TxOpts::default()) {
+        Ok(tx) => tx,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut result = BulkResult::default();
+    for order_id in order_ids {
+        let state = match current_workflow(&mut tx, &instance_id, &order_id)? {
+            Some((state, _)) => state,
+            None => {
+                result.rejected.push(RejectedOrder { order_id, reason: "No order with this ID".to_string() });
+                continue;
+            }
+        };
+
+        update_workflow(&mut tx, &order_id, state.clone(), state, assignee.clone(), &changed_by)?;
+        result.updated.push(order_id);
+    }
+
+    match tx.commit() {
+        Ok(_) => Ok(result),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Get the state and assignee of an order, None if the order does not exist in the instance
+fn current_workflow(tx: &mut Transaction, instance_id: &str, order_id: &str) -> Result<Option<(Option<String>, Option<String>)>, String> {
+    let result = tx.exec_first::<(Option<String>, Option<String>), &str, Params>("SELECT w.state_key, w.assignee FROM orders o \
+        LEFT JOIN order_workflow w ON w.order_id = o.order_id WHERE o.order_id = :order_id AND o.instance_id = :instance_id FOR UPDATE", params! {
+        "order_id" => order_id,
+        "instance_id" => instance_id
+    });
+
+    match result {
+        Ok(current) => Ok(current),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn update_workflow(tx: &mut Transaction, order_id: &str, from_state: Option<String>, to_state: Option<String>, assignee: Option<String>, changed_by: &str) -> Result<(), String> {
+    let changed_at = chrono::Utc::now().timestamp();
+    let result = tx.exec_drop("INSERT INTO order_workflow (order_id, state_key, assignee, updated_at) VALUES (:order_id, :state_key, :assignee, :updated_at) \
+        ON DUPLICATE KEY UPDATE state_key = :state_key, assignee = :assignee, updated_at = :updated_at", params! {
+        "order_id" => order_id,
+        "state_key" => to_state.clone(),
+        "assignee" => assignee.clone(),
+        "updated_at" => changed_at
+    });
+
+    if result.is_err() {
+        return Err(result.err().unwrap().to_string());
+    }
+
+    let history_id: String = rand::thread_rng().sample_iter(&rand::distributions::Alphanumeric).take(64).map(char::from).collect();
+    let result = tx.exec_drop("INSERT INTO order_workflow_history (history_id, order_id, from_state, to_state, assignee, changed_by, changed_at) \
+        VALUES (:history_id, :order_id, :from_state, :to_state, :assignee, :changed_by, :changed_at)", params! {
+        "history_id" => history_id,
+        "order_id" => order_id,
+        "from_state" => from_state,
+        "to_state" => to_state,
+        "assignee" => assignee,
+        "changed_by" => changed_by,
+        "changed_at" => changed_at
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string())
+    }
+}
\ No newline at end of file